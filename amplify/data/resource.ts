@@ -14,6 +14,10 @@ const schema = a.schema({
       packSize: a.integer().required(),
       createdAt: a.datetime().required(),
       updatedAt: a.datetime().required(),
+      // Gum Wall Calculator - products with a gum type and pack price are the packs it is sold in
+      gumTypeId: a.id(),
+      gumType: a.belongsTo('GumType', 'gumTypeId'),
+      packPrice: a.float(),                 // Price per pack in the gum type's currency
     })
     .identifier(['productId'])
     .authorization((allow) => [allow.publicApiKey()]),
@@ -81,10 +85,16 @@ const schema = a.schema({
       gumId: a.string().required(),      // Unique identifier (e.g., "dubble_bubble_pink")
       name: a.string().required(),        // Display name (e.g., "Dubble Bubble Original")
       hexColor: a.string().required(),    // Hex color code (e.g., "#FF69B4")
-      pricePerPiece: a.float().required(), // Price in USD (e.g., 0.05)
+      pricePerPiece: a.float().required(), // Price in `currency` (e.g., 0.05)
       brand: a.string(),                  // Brand name (e.g., "Dubble Bubble")
       flavor: a.string(),                 // Flavor description
       isAvailable: a.boolean().default(true),
+      currency: a.string(),               // ISO 4217 code; defaults to the MCP pricing currency (USD)
+      products: a.hasMany('GumProduct', 'gumTypeId'), // Packs sold; bought per piece when none
+      weightGrams: a.float(),             // Weight per piece; unset uses the MCP defaults
+      chewMinutes: a.float(),             // Chewing time per piece before placing
+      sugarGrams: a.float(),              // Sugar per piece (0 for sugar-free gum)
+      volumeCm3: a.float(),               // Volume per piece
     })
     .authorization((allow) => [allow.publicApiKey()]),
 });
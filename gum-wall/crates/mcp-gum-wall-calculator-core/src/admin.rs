@@ -0,0 +1,834 @@
+//! Admin tools for managing the gum catalog through AppSync mutations
+//!
+//! These tools write to the Amplify `GumType` model, so they are only
+//! registered when `admin.enabled` is set in the configuration.
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use crate::catalog::GumCatalog;
+use crate::config::AppSyncConfig;
+use crate::context::AppContext;
+use crate::error::GumWallError;
+use crate::graphql;
+use crate::types::{
+    BulkPriceUpdateInput, BulkPriceUpdateOutput, CreateGumTypeData, CreateGumTypeInput,
+    GumPackPrice, GumProperties, GumType, GumTypeRecord, ListGumTypesData, PriceUpdateResult,
+    RetireGumTypeInput, UpdateGumTypeData, UpdateGumTypeInput,
+};
+use pmcp::{ServerBuilder, TypedTool};
+use serde_json::json;
+use std::sync::Arc;
+use validator::Validate;
+
+/// Fields returned for every `GumType` record
+const GUM_TYPE_FIELDS: &str = "id gumId name hexColor pricePerPiece brand flavor isAvailable \
+    currency products { items { productId packSize packPrice } } weightGrams chewMinutes \
+    sugarGrams volumeCm3";
+
+/// Mutation creating a `GumProduct` (one pack size of a gum type)
+const CREATE_GUM_PRODUCT_MUTATION: &str =
+    "mutation CreateGumProduct($input: CreateGumProductInput!) { \
+    createGumProduct(input: $input) { productId } }";
+
+/// Mutation deleting a `GumProduct`
+const DELETE_GUM_PRODUCT_MUTATION: &str =
+    "mutation DeleteGumProduct($input: DeleteGumProductInput!) { \
+    deleteGumProduct(input: $input) { productId } }";
+
+/// Mutation deleting a `GumType` record (used to roll back a failed create)
+const DELETE_GUM_TYPE_MUTATION: &str =
+    "mutation DeleteGumType($input: DeleteGumTypeInput!) { deleteGumType(input: $input) { id } }";
+
+/// Page size used when searching for a record by `gumId`
+const FIND_PAGE_SIZE: u32 = 100;
+
+/// Maximum pages scanned when searching for a record by `gumId`
+const FIND_MAX_PAGES: u32 = 50;
+
+/// Find the AppSync record for a gum type by its `gumId`
+///
+/// The Amplify model is keyed by a generated `id`, so updates first look up
+/// the record. Filtered list queries may return empty pages with a
+/// `nextToken`, so all pages are scanned.
+pub async fn find_gum_type_record(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    gum_id: &str,
+) -> Result<Option<GumTypeRecord>> {
+    let query = format!(
+        "query FindGumType($filter: ModelGumTypeFilterInput, $limit: Int, $nextToken: String) {{ \
+            listGumTypes(filter: $filter, limit: $limit, nextToken: $nextToken) {{ items {{ {} }} nextToken }} \
+        }}",
+        GUM_TYPE_FIELDS
+    );
+    let mut next_token: Option<String> = None;
+
+    for _ in 0..FIND_MAX_PAGES {
+        let data: ListGumTypesData = graphql::execute(
+            client,
+            &appsync.api_url,
+            appsync.api_key.expose(),
+            &query,
+            json!({
+                "filter": {"gumId": {"eq": gum_id}},
+                "limit": FIND_PAGE_SIZE,
+                "nextToken": next_token
+            }),
+        )
+        .await?;
+
+        let result = data.list_gum_types;
+        if let Some(record) = result.items.into_iter().find(|r| r.gum_id == gum_id) {
+            return Ok(Some(record));
+        }
+        match result.next_token {
+            Some(token) => next_token = Some(token),
+            None => return Ok(None),
+        }
+    }
+
+    Err(anyhow!("Gave up searching for gum type '{}' after {} pages", gum_id, FIND_MAX_PAGES))
+}
+
+/// Record ID for a gum type created by the admin tools
+///
+/// Derived from the `gumId` (like order IDs from idempotency keys), so two
+/// concurrent creates of the same gum type collide in AppSync instead of
+/// both inserting a record.
+pub fn gum_type_record_id(gum_id: &str) -> String {
+    format!("gt-{}", gum_id)
+}
+
+/// Create a new gum type; fails with `Conflict` if the `gumId` already exists
+///
+/// When a pack can't be created the new record is deleted again, so a retry
+/// starts from scratch; the error names anything that could not be removed.
+pub async fn create_gum_type(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    input: &CreateGumTypeInput,
+) -> Result<GumType> {
+    let already_exists = || {
+        GumWallError::Conflict(format!("Gum type '{}' already exists", input.gum_id))
+    };
+    // Records created before IDs were derived from the gumId have random IDs
+    if find_gum_type_record(client, appsync, &input.gum_id).await?.is_some() {
+        return Err(already_exists().into());
+    }
+
+    let query = format!(
+        "mutation CreateGumType($input: CreateGumTypeInput!) {{ createGumType(input: $input) {{ {} }} }}",
+        GUM_TYPE_FIELDS
+    );
+    let mut fields = serde_json::Map::new();
+    fields.insert("id".to_string(), json!(gum_type_record_id(&input.gum_id)));
+    fields.insert("gumId".to_string(), json!(input.gum_id));
+    fields.insert("name".to_string(), json!(input.name));
+    fields.insert("hexColor".to_string(), json!(input.hex_color.to_uppercase()));
+    fields.insert("pricePerPiece".to_string(), json!(input.price_per_piece));
+    fields.insert("brand".to_string(), json!(input.brand));
+    fields.insert("flavor".to_string(), json!(input.flavor));
+    fields.insert("isAvailable".to_string(), json!(input.is_available));
+    if let Some(currency) = &input.currency {
+        fields.insert("currency".to_string(), json!(currency.to_uppercase()));
+    }
+    insert_properties(&mut fields, &input.properties);
+
+    let created: Result<CreateGumTypeData> = graphql::execute(
+        client,
+        &appsync.api_url,
+        appsync.api_key.expose(),
+        &query,
+        json!({ "input": fields }),
+    )
+    .await;
+    let record = match created {
+        Ok(data) => data.create_gum_type,
+        // A concurrent create of the same gum type got there first
+        Err(e) => match find_gum_type_record(client, appsync, &input.gum_id).await? {
+            Some(_) => return Err(already_exists().into()),
+            None => return Err(e),
+        },
+    };
+
+    if let Err(e) = create_packs(client, appsync, &record, &input.packs).await {
+        let deleted: Result<serde_json::Value> = graphql::execute(
+            client,
+            &appsync.api_url,
+            appsync.api_key.expose(),
+            DELETE_GUM_TYPE_MUTATION,
+            json!({ "input": { "id": record.id } }),
+        )
+        .await;
+        return Err(match deleted {
+            Ok(_) => anyhow!("Gum type '{}' was not created: {}", input.gum_id, e),
+            Err(delete_error) => anyhow!(
+                "Gum type '{}' was created without its packs ({}) and could not be removed \
+                 (record '{}': {})",
+                input.gum_id,
+                e,
+                record.id,
+                delete_error
+            ),
+        });
+    }
+    let mut gum_type = GumType::from(record);
+    gum_type.packs = input.packs.clone();
+    Ok(gum_type)
+}
+
+/// Update the provided fields of an existing gum type
+pub async fn update_gum_type(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    input: &UpdateGumTypeInput,
+) -> Result<GumType> {
+    let record = find_gum_type_record(client, appsync, &input.gum_id)
+        .await?
+        .ok_or_else(|| GumWallError::NotFound(format!("Gum type '{}' not found", input.gum_id)))?;
+
+    let mut changes = serde_json::Map::new();
+    changes.insert("id".to_string(), json!(record.id));
+    if let Some(name) = &input.name {
+        changes.insert("name".to_string(), json!(name));
+    }
+    if let Some(hex_color) = &input.hex_color {
+        changes.insert("hexColor".to_string(), json!(hex_color.to_uppercase()));
+    }
+    if let Some(price) = input.price_per_piece {
+        changes.insert("pricePerPiece".to_string(), json!(price));
+    }
+    if let Some(brand) = &input.brand {
+        changes.insert("brand".to_string(), json!(brand));
+    }
+    if let Some(flavor) = &input.flavor {
+        changes.insert("flavor".to_string(), json!(flavor));
+    }
+    if let Some(is_available) = input.is_available {
+        changes.insert("isAvailable".to_string(), json!(is_available));
+    }
+    if let Some(currency) = &input.currency {
+        changes.insert("currency".to_string(), json!(currency.to_uppercase()));
+    }
+    if let Some(properties) = &input.properties {
+        insert_properties(&mut changes, properties);
+    }
+
+    let query = format!(
+        "mutation UpdateGumType($input: UpdateGumTypeInput!) {{ updateGumType(input: $input) {{ {} }} }}",
+        GUM_TYPE_FIELDS
+    );
+    let data: UpdateGumTypeData = graphql::execute(
+        client,
+        &appsync.api_url,
+        appsync.api_key.expose(),
+        &query,
+        json!({ "input": changes }),
+    )
+    .await?;
+
+    let mut gum_type = GumType::from(data.update_gum_type);
+    if let Some(packs) = &input.packs {
+        replace_packs(client, appsync, &record, packs).await.map_err(|e| {
+            anyhow!("Updated gum type '{}' but not its packs: {}", input.gum_id, e)
+        })?;
+        gum_type.packs = packs.clone();
+    }
+    Ok(gum_type)
+}
+
+/// Add the properties that are set to a `GumType` mutation input
+fn insert_properties(
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+    properties: &GumProperties,
+) {
+    let values = [
+        ("weightGrams", properties.weight_g),
+        ("chewMinutes", properties.chew_minutes),
+        ("sugarGrams", properties.sugar_g),
+        ("volumeCm3", properties.volume_cm3),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value {
+            fields.insert(name.to_string(), json!(value));
+        }
+    }
+}
+
+/// Replace the packs a gum type is sold in
+///
+/// Packs are `GumProduct` records linked to the gum type by `gumTypeId`. The
+/// new products are created first and the old priced products are deleted
+/// only once every create succeeded, so a failure leaves the old packs in place.
+async fn replace_packs(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    record: &GumTypeRecord,
+    packs: &[GumPackPrice],
+) -> Result<()> {
+    let products = record.products.as_ref().map(|p| p.items.as_slice()).unwrap_or_default();
+    let old_products: Vec<String> = products
+        .iter()
+        .filter(|p| p.pack_price.is_some())
+        .filter_map(|p| p.product_id.clone())
+        .collect();
+
+    create_packs(client, appsync, record, packs).await?;
+
+    let left_behind = delete_products(client, appsync, &old_products).await;
+    if !left_behind.is_empty() {
+        return Err(anyhow!(
+            "New packs were created but these old products could not be deleted: {}",
+            left_behind.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Create one `GumProduct` per pack for a gum type
+///
+/// If a create fails, the products created so far are deleted again; the
+/// error names any that could not be removed.
+async fn create_packs(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    record: &GumTypeRecord,
+    packs: &[GumPackPrice],
+) -> Result<()> {
+    let now = Utc::now().to_rfc3339();
+    let mut created = Vec::with_capacity(packs.len());
+    for pack in packs {
+        let product_id = uuid::Uuid::new_v4().to_string();
+        let result: Result<serde_json::Value> = graphql::execute(
+            client,
+            &appsync.api_url,
+            appsync.api_key.expose(),
+            CREATE_GUM_PRODUCT_MUTATION,
+            json!({
+                "input": {
+                    "productId": product_id,
+                    "gumTypeId": record.id,
+                    "brandName": record.brand.as_deref().unwrap_or(&record.name),
+                    "color": record.hex_color,
+                    "flavor": record.flavor.as_deref().unwrap_or_default(),
+                    "packSize": pack.pack_size,
+                    "packPrice": pack.price,
+                    "createdAt": now,
+                    "updatedAt": now
+                }
+            }),
+        )
+        .await;
+
+        if let Err(e) = result {
+            let left_behind = delete_products(client, appsync, &created).await;
+            let message = format!("Failed to create the {}-pack: {}", pack.pack_size, e);
+            return Err(if left_behind.is_empty() {
+                anyhow!(message)
+            } else {
+                anyhow!(
+                    "{}; these new products could not be deleted: {}",
+                    message,
+                    left_behind.join(", ")
+                )
+            });
+        }
+        created.push(product_id);
+    }
+    Ok(())
+}
+
+/// Delete `GumProduct` records, returning the IDs that could not be deleted
+async fn delete_products(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    product_ids: &[String],
+) -> Vec<String> {
+    let mut left_behind = Vec::new();
+    for product_id in product_ids {
+        let result: Result<serde_json::Value> = graphql::execute(
+            client,
+            &appsync.api_url,
+            appsync.api_key.expose(),
+            DELETE_GUM_PRODUCT_MUTATION,
+            json!({ "input": { "productId": product_id } }),
+        )
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to delete gum product '{}': {}", product_id, e);
+            left_behind.push(product_id.clone());
+        }
+    }
+    left_behind
+}
+
+/// Apply several price changes, reporting success or failure for each
+pub async fn bulk_update_prices(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    input: &BulkPriceUpdateInput,
+) -> BulkPriceUpdateOutput {
+    let mut results = Vec::with_capacity(input.updates.len());
+
+    for update in &input.updates {
+        let change = UpdateGumTypeInput {
+            gum_id: update.gum_id.clone(),
+            name: None,
+            hex_color: None,
+            price_per_piece: Some(update.price_per_piece),
+            brand: None,
+            flavor: None,
+            is_available: None,
+            currency: None,
+            packs: None,
+            properties: None,
+        };
+        let result = update_gum_type(client, appsync, &change).await;
+        results.push(PriceUpdateResult {
+            gum_id: update.gum_id.clone(),
+            updated: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        });
+    }
+
+    let updated_count = results.iter().filter(|r| r.updated).count() as u32;
+    BulkPriceUpdateOutput {
+        failed_count: results.len() as u32 - updated_count,
+        updated_count,
+        results,
+    }
+}
+
+/// Refresh the catalog after a write so other tools see the change
+async fn refresh_after_write(catalog: &dyn GumCatalog) {
+    if let Err(e) = catalog.refresh().await {
+        tracing::warn!("Failed to refresh gum catalog after admin change: {}", e);
+    }
+}
+
+/// Register the catalog management tools on the server builder
+pub(crate) fn register_admin_tools(
+    builder: ServerBuilder,
+    appsync: AppSyncConfig,
+    context: Arc<AppContext>,
+) -> ServerBuilder {
+    let appsync = Arc::new(appsync);
+    let create_appsync = appsync.clone();
+    let create_context = context.clone();
+    let update_appsync = appsync.clone();
+    let update_context = context.clone();
+    let retire_appsync = appsync.clone();
+    let retire_context = context.clone();
+    let bulk_appsync = appsync;
+    let bulk_context = context;
+
+    builder
+        // Admin tool: Create a gum type
+        .tool(
+            "create_gum_type",
+            TypedTool::new("create_gum_type", move |input: CreateGumTypeInput, _extra| {
+                let appsync = create_appsync.clone();
+                let context = create_context.clone();
+                Box::pin(async move {
+                    input
+                        .validate()
+                        .map_err(|e| GumWallError::Validation(e.to_string()))?;
+                    tracing::info!("Admin: creating gum type '{}'", input.gum_id);
+
+                    let client = &context.http;
+                    let gum_type = create_gum_type(client, &appsync, &input).await.map_err(|e| {
+                        GumWallError::or_wrap(e, GumWallError::CatalogWriteFailed)
+                    })?;
+                    refresh_after_write(context.catalog.as_ref()).await;
+
+                    Ok(json!({ "created": true, "gum_type": gum_type }))
+                })
+            })
+            .with_description(
+                "[Admin] Create a new gum type in the catalog. \
+                Requires a unique gum_id, a #RRGGBB hex color and a positive price per piece.",
+            ),
+        )
+        // Admin tool: Update a gum type
+        .tool(
+            "update_gum_type",
+            TypedTool::new("update_gum_type", move |input: UpdateGumTypeInput, _extra| {
+                let appsync = update_appsync.clone();
+                let context = update_context.clone();
+                Box::pin(async move {
+                    input
+                        .validate()
+                        .map_err(|e| GumWallError::Validation(e.to_string()))?;
+                    tracing::info!("Admin: updating gum type '{}'", input.gum_id);
+
+                    let client = &context.http;
+                    let gum_type = update_gum_type(client, &appsync, &input).await.map_err(|e| {
+                        GumWallError::or_wrap(e, GumWallError::CatalogWriteFailed)
+                    })?;
+                    refresh_after_write(context.catalog.as_ref()).await;
+
+                    Ok(json!({ "updated": true, "gum_type": gum_type }))
+                })
+            })
+            .with_description(
+                "[Admin] Update an existing gum type. Only the provided fields are changed.",
+            ),
+        )
+        // Admin tool: Retire a gum type
+        .tool(
+            "retire_gum_type",
+            TypedTool::new("retire_gum_type", move |input: RetireGumTypeInput, _extra| {
+                let appsync = retire_appsync.clone();
+                let context = retire_context.clone();
+                Box::pin(async move {
+                    input
+                        .validate()
+                        .map_err(|e| GumWallError::Validation(e.to_string()))?;
+                    tracing::info!("Admin: retiring gum type '{}'", input.gum_id);
+
+                    let change = UpdateGumTypeInput {
+                        gum_id: input.gum_id,
+                        name: None,
+                        hex_color: None,
+                        price_per_piece: None,
+                        brand: None,
+                        flavor: None,
+                        is_available: Some(false),
+                        currency: None,
+                        packs: None,
+                        properties: None,
+                    };
+                    let client = &context.http;
+                    let gum_type = update_gum_type(client, &appsync, &change).await.map_err(|e| {
+                        GumWallError::or_wrap(e, GumWallError::CatalogWriteFailed)
+                    })?;
+                    refresh_after_write(context.catalog.as_ref()).await;
+
+                    Ok(json!({ "retired": true, "gum_type": gum_type }))
+                })
+            })
+            .with_description(
+                "[Admin] Mark a gum type as unavailable. It stays in the catalog for reference \
+                but is no longer used in designs or priced.",
+            ),
+        )
+        // Admin tool: Update many prices at once
+        .tool(
+            "bulk_update_gum_prices",
+            TypedTool::new("bulk_update_gum_prices", move |input: BulkPriceUpdateInput, _extra| {
+                let appsync = bulk_appsync.clone();
+                let context = bulk_context.clone();
+                Box::pin(async move {
+                    input
+                        .validate()
+                        .map_err(|e| GumWallError::Validation(e.to_string()))?;
+                    tracing::info!("Admin: updating {} gum prices", input.updates.len());
+
+                    let client = &context.http;
+                    let output = bulk_update_prices(client, &appsync, &input).await;
+                    if output.updated_count > 0 {
+                        refresh_after_write(context.catalog.as_ref()).await;
+                    }
+
+                    Ok(serde_json::to_value(output)
+                        .map_err(|e| GumWallError::Internal(e.to_string()))?)
+                })
+            })
+            .with_description(
+                "[Admin] Update the price per piece of several gum types. \
+                Each update is applied independently and reported in the results.",
+            ),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Secret;
+    use crate::test_support::MockServer;
+    use crate::types::PriceUpdate;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    fn record(id: &str, gum_id: &str, price: f64, is_available: bool) -> serde_json::Value {
+        json!({
+            "id": id,
+            "gumId": gum_id,
+            "name": "Red",
+            "hexColor": "#FF0000",
+            "pricePerPiece": price,
+            "brand": null,
+            "flavor": null,
+            "isAvailable": is_available
+        })
+    }
+
+    /// Stub AppSync holding a single "red" gum type with record id "rec-1"
+    ///
+    /// Creating "racy" fails as if a concurrent request created it first, and
+    /// 999-packs can't be created. Deleted record and product IDs are logged.
+    async fn mock_appsync() -> (MockServer, Arc<Mutex<Vec<String>>>) {
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let log = deleted.clone();
+        let racy_created = Arc::new(AtomicBool::new(false));
+        let server = MockServer::start(move |request| {
+            let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+            let variables = &body["variables"];
+            let query = body["query"].as_str().unwrap_or_default();
+            let error = |message: &str| {
+                (200, json!({"data": null, "errors": [{"message": message}]}).to_string())
+            };
+
+            let data = if query.contains("listGumTypes") {
+                let gum_id = &variables["filter"]["gumId"]["eq"];
+                let items = if gum_id == "red" {
+                    let mut red = record("rec-1", "red", 0.05, true);
+                    red["products"] = json!({"items": [
+                        {"productId": "red-pack", "packSize": 50, "packPrice": 2.0}
+                    ]});
+                    vec![red]
+                } else if gum_id == "racy" && racy_created.load(Ordering::SeqCst) {
+                    vec![record("gt-racy", "racy", 0.05, true)]
+                } else {
+                    vec![]
+                };
+                json!({"listGumTypes": {"items": items, "nextToken": null}})
+            } else if query.contains("createGumType") {
+                let input = &variables["input"];
+                if input["id"] != json!(format!("gt-{}", input["gumId"].as_str().unwrap())) {
+                    return error("bad id");
+                }
+                if input["gumId"] == "racy" {
+                    racy_created.store(true, Ordering::SeqCst);
+                    return error("ConditionalCheckFailedException");
+                }
+                json!({"createGumType": {
+                    "id": input["id"],
+                    "gumId": input["gumId"],
+                    "name": input["name"],
+                    "hexColor": input["hexColor"],
+                    "pricePerPiece": input["pricePerPiece"],
+                    "brand": null,
+                    "flavor": null,
+                    "isAvailable": input["isAvailable"]
+                }})
+            } else if query.contains("deleteGumType") {
+                let id = variables["input"]["id"].as_str().unwrap_or_default();
+                log.lock().unwrap().push(id.to_string());
+                json!({"deleteGumType": {"id": id}})
+            } else if query.contains("deleteGumProduct") {
+                let product_id = variables["input"]["productId"].as_str().unwrap_or_default();
+                log.lock().unwrap().push(product_id.to_string());
+                json!({"deleteGumProduct": {"productId": product_id}})
+            } else if query.contains("createGumProduct") {
+                let input = &variables["input"];
+                let red = input["gumTypeId"] == "rec-1" && input["brandName"] == "Red";
+                if !(red || input["gumTypeId"] == "gt-blue") || input["packSize"] == 999 {
+                    return error("bad input");
+                }
+                json!({"createGumProduct": {"productId": input["productId"]}})
+            } else if query.contains("updateGumType") {
+                let input = &variables["input"];
+                if input["id"] != "rec-1" {
+                    return error("bad id");
+                }
+                let price = input["pricePerPiece"].as_f64().unwrap_or(0.05);
+                let available = input["isAvailable"].as_bool().unwrap_or(true);
+                json!({"updateGumType": record("rec-1", "red", price, available)})
+            } else {
+                return (400, "{}".to_string());
+            };
+            (200, json!({ "data": data }).to_string())
+        })
+        .await;
+        (server, deleted)
+    }
+
+    fn appsync(server: &MockServer) -> AppSyncConfig {
+        AppSyncConfig {
+            api_url: server.url.clone(),
+            api_key: Secret::new("test-key"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_gum_type() {
+        let (server, _) = mock_appsync().await;
+        let client = reqwest::Client::new();
+        let input = CreateGumTypeInput {
+            gum_id: "blue".to_string(),
+            name: "Blue".to_string(),
+            hex_color: "#0000ff".to_string(),
+            price_per_piece: 0.07,
+            brand: None,
+            flavor: None,
+            is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
+        };
+
+        let gum_type = create_gum_type(&client, &appsync(&server), &input).await.unwrap();
+        assert_eq!(gum_type.gum_id, "blue");
+        assert_eq!(gum_type.hex_color, "#0000FF");
+
+        let duplicate = CreateGumTypeInput { gum_id: "red".to_string(), ..input.clone() };
+        let error = create_gum_type(&client, &appsync(&server), &duplicate).await.unwrap_err();
+        let error = GumWallError::or_wrap(error, GumWallError::CatalogWriteFailed);
+        assert_eq!(error.code(), "conflict");
+
+        // Created concurrently by someone else after the lookup
+        let racy = CreateGumTypeInput { gum_id: "racy".to_string(), ..input };
+        let error = create_gum_type(&client, &appsync(&server), &racy).await.unwrap_err();
+        let error = GumWallError::or_wrap(error, GumWallError::CatalogWriteFailed);
+        assert_eq!(error.code(), "conflict");
+    }
+
+    #[tokio::test]
+    async fn test_create_rolls_back_when_a_pack_fails() {
+        let (server, deleted) = mock_appsync().await;
+        let client = reqwest::Client::new();
+        let input = CreateGumTypeInput {
+            gum_id: "blue".to_string(),
+            name: "Blue".to_string(),
+            hex_color: "#0000FF".to_string(),
+            price_per_piece: 0.07,
+            brand: None,
+            flavor: None,
+            is_available: true,
+            currency: None,
+            packs: vec![
+                GumPackPrice { pack_size: 100, price: 3.5 },
+                GumPackPrice { pack_size: 999, price: 30.0 },
+            ],
+            properties: GumProperties::default(),
+        };
+
+        let error = create_gum_type(&client, &appsync(&server), &input).await.unwrap_err();
+        assert!(error.to_string().contains("was not created"));
+        // The 100-pack and then the gum type record are deleted again
+        let deleted = deleted.lock().unwrap();
+        assert_eq!(deleted.len(), 2);
+        assert_eq!(deleted[1], "gt-blue");
+    }
+
+    #[tokio::test]
+    async fn test_update_replaces_packs() {
+        let (server, deleted) = mock_appsync().await;
+        let client = reqwest::Client::new();
+        let packs = vec![
+            GumPackPrice { pack_size: 100, price: 3.5 },
+            GumPackPrice { pack_size: 500, price: 15.0 },
+        ];
+        let input = UpdateGumTypeInput {
+            gum_id: "red".to_string(),
+            name: None,
+            hex_color: None,
+            price_per_piece: None,
+            brand: None,
+            flavor: None,
+            is_available: None,
+            currency: Some("eur".to_string()),
+            packs: Some(packs.clone()),
+            properties: Some(GumProperties { weight_g: Some(1.2), ..Default::default() }),
+        };
+
+        let gum_type = update_gum_type(&client, &appsync(&server), &input).await.unwrap();
+        assert_eq!(gum_type.packs, packs);
+        // Lookup, update, two creates and then one delete
+        assert_eq!(server.hits(), 5);
+        assert_eq!(*deleted.lock().unwrap(), ["red-pack"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_pack_replace_keeps_old_packs() {
+        let (server, deleted) = mock_appsync().await;
+        let client = reqwest::Client::new();
+        let input = UpdateGumTypeInput {
+            gum_id: "red".to_string(),
+            name: None,
+            hex_color: None,
+            price_per_piece: None,
+            brand: None,
+            flavor: None,
+            is_available: None,
+            currency: None,
+            packs: Some(vec![
+                GumPackPrice { pack_size: 100, price: 3.5 },
+                GumPackPrice { pack_size: 999, price: 30.0 },
+            ]),
+            properties: None,
+        };
+
+        let error = update_gum_type(&client, &appsync(&server), &input).await.unwrap_err();
+        assert!(error.to_string().contains("not its packs"));
+        // Only the new 100-pack is deleted again
+        let deleted = deleted.lock().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_ne!(deleted[0], "red-pack");
+    }
+
+    #[tokio::test]
+    async fn test_bulk_update_reports_each_item() {
+        let (server, _) = mock_appsync().await;
+        let client = reqwest::Client::new();
+        let input = BulkPriceUpdateInput {
+            updates: vec![
+                PriceUpdate { gum_id: "red".to_string(), price_per_piece: 0.06 },
+                PriceUpdate { gum_id: "missing".to_string(), price_per_piece: 0.06 },
+            ],
+        };
+
+        let output = bulk_update_prices(&client, &appsync(&server), &input).await;
+        assert_eq!(output.updated_count, 1);
+        assert_eq!(output.failed_count, 1);
+        assert!(output.results[1].error.as_ref().unwrap().contains("not found"));
+    }
+
+    #[test]
+    fn test_admin_input_validation() {
+        let valid = CreateGumTypeInput {
+            gum_id: "blue".to_string(),
+            name: "Blue".to_string(),
+            hex_color: "#0000FF".to_string(),
+            price_per_piece: 0.07,
+            brand: None,
+            flavor: None,
+            is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
+        };
+        assert!(valid.validate().is_ok());
+
+        let bad_color = CreateGumTypeInput { hex_color: "blue".to_string(), ..valid.clone() };
+        assert!(bad_color.validate().is_err());
+
+        let free = CreateGumTypeInput { price_per_piece: 0.0, ..valid.clone() };
+        assert!(free.validate().is_err());
+
+        let bad_update = UpdateGumTypeInput {
+            gum_id: "red".to_string(),
+            name: None,
+            hex_color: Some("#GGGGGG".to_string()),
+            price_per_piece: None,
+            brand: None,
+            flavor: None,
+            is_available: None,
+            currency: None,
+            packs: None,
+            properties: None,
+        };
+        assert!(bad_update.validate().is_err());
+
+        let bad_pack = UpdateGumTypeInput {
+            hex_color: None,
+            packs: Some(vec![GumPackPrice { pack_size: 0, price: 1.0 }]),
+            ..bad_update.clone()
+        };
+        assert!(bad_pack.validate().is_err());
+
+        let bad_currency = CreateGumTypeInput { currency: Some("dollars".to_string()), ..valid };
+        assert!(bad_currency.validate().is_err());
+    }
+}
@@ -0,0 +1,480 @@
+//! Design files for the S3 output mode
+//!
+//! Instead of returning the SVG inline, analyze_image_for_gum_wall can render
+//! the design as SVG, PNG, PDF and JSON, upload the files to S3 and return
+//! presigned download URLs.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use crate::config::ArtifactsConfig;
+use crate::context::AppContext;
+use crate::image_source::ImageSource;
+use crate::s3;
+use crate::types::{AnalyzeImageOutput, ArtifactFormat, DesignArtifact, GumType, Rgb};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::Duration;
+
+/// Largest PNG width or height; the cell size shrinks for big walls
+const MAX_PNG_SIDE: u32 = 4096;
+
+/// Largest PDF page width or height, in points (the PDF 1.4 limit)
+const MAX_PDF_SIDE: f64 = 14_400.0;
+
+/// Color of cells missing from the legend (same as the SVG)
+const UNKNOWN_COLOR: Rgb = Rgb {
+    r: 0xCC,
+    g: 0xCC,
+    b: 0xCC,
+};
+
+/// Where the design files for one analysis are stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtifactLocation {
+    pub bucket: String,
+    /// Key without the file extension, e.g. "photos/cat-gum-wall-1a2b3c4d"
+    pub base_key: String,
+}
+
+impl ArtifactLocation {
+    /// Decide where the design files for an image go
+    ///
+    /// Files go to `artifacts.bucket` under `artifacts.prefix` (or the source
+    /// image's folder). Without `artifacts.bucket` they only go next to images
+    /// in `uploads_bucket`; the server never writes to other buckets named in a
+    /// request. Files are named after the image with a random suffix so
+    /// repeated runs never overwrite each other.
+    pub fn resolve(
+        config: &ArtifactsConfig,
+        uploads_bucket: Option<&str>,
+        source: &ImageSource,
+    ) -> Result<Self> {
+        let (source_bucket, folder, file_name) = match source {
+            ImageSource::S3 { bucket, key } => {
+                let (folder, file_name) = match key.rfind('/') {
+                    Some(pos) => key.split_at(pos + 1),
+                    None => ("", key.as_str()),
+                };
+                (Some(bucket.as_str()), folder, file_name.to_string())
+            }
+            ImageSource::Https(url) => {
+                let path = url.split(['?', '#']).next().unwrap_or_default();
+                (
+                    None,
+                    "",
+                    path.rsplit('/').next().unwrap_or_default().to_string(),
+                )
+            }
+            ImageSource::File(path) => {
+                let file_name = path.file_name().map(|name| name.to_string_lossy());
+                (None, "", file_name.unwrap_or_default().to_string())
+            }
+            ImageSource::Inline { .. } => (None, "", String::new()),
+        };
+
+        let bucket = match (config.bucket.as_deref(), source_bucket) {
+            (Some(bucket), _) => bucket.to_string(),
+            (None, Some(source)) if uploads_bucket == Some(source) => source.to_string(),
+            (None, Some(source)) => {
+                return Err(anyhow!(
+                    "S3 output needs artifacts.bucket; design files are not written to '{}'",
+                    source
+                ))
+            }
+            (None, None) => {
+                return Err(anyhow!(
+                    "S3 output needs artifacts.bucket when the image is not stored in S3"
+                ))
+            }
+        };
+
+        let prefix = match config.prefix.as_deref() {
+            Some(prefix) if !prefix.is_empty() && !prefix.ends_with('/') => format!("{}/", prefix),
+            Some(prefix) => prefix.to_string(),
+            None => folder.to_string(),
+        };
+
+        let suffix = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        Ok(ArtifactLocation {
+            bucket,
+            base_key: format!("{}{}-gum-wall-{}", prefix, file_stem(&file_name), suffix),
+        })
+    }
+
+    /// Key of the file in the given format
+    pub fn key(&self, format: ArtifactFormat) -> String {
+        format!("{}.{}", self.base_key, format.extension())
+    }
+}
+
+/// File name without its extension, limited to characters that are safe in S3 keys
+pub(crate) fn file_stem(file_name: &str) -> String {
+    let stem = match file_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => file_name,
+    };
+    let stem: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        "design".to_string()
+    } else {
+        stem
+    }
+}
+
+/// Render all design files, upload them and presign their download URLs
+///
+/// `design` is uploaded as the JSON file; it should not contain the SVG.
+pub async fn publish_design(
+    context: &AppContext,
+    location: &ArtifactLocation,
+    design: &AnalyzeImageOutput,
+    grid: &[Vec<u8>],
+    svg: String,
+) -> Result<Vec<DesignArtifact>> {
+    let config = &context.config.artifacts;
+    let files = vec![
+        (ArtifactFormat::Svg, svg.into_bytes()),
+        (
+            ArtifactFormat::Png,
+            render_png(grid, &design.legend, config.cell_size)?,
+        ),
+        (
+            ArtifactFormat::Pdf,
+            render_pdf(grid, &design.legend, config.cell_size),
+        ),
+        (ArtifactFormat::Json, serde_json::to_vec_pretty(design)?),
+    ];
+
+    let client = context.s3().await?;
+    let expires_in = Duration::from_secs(config.url_expiry_secs);
+    let expires_at = expires_at(Utc::now(), expires_in);
+
+    let mut artifacts = Vec::with_capacity(files.len());
+    for (format, bytes) in files {
+        let key = location.key(format);
+        s3::put_object(client, &location.bucket, &key, format.content_type(), bytes).await?;
+        let url = s3::presign_get(client, &location.bucket, &key, expires_in).await?;
+        artifacts.push(DesignArtifact {
+            format,
+            content_type: format.content_type().to_string(),
+            s3_uri: format!("s3://{}/{}", location.bucket, key),
+            url,
+            expires_at: expires_at.clone(),
+        });
+    }
+    Ok(artifacts)
+}
+
+fn expires_at(now: DateTime<Utc>, expires_in: Duration) -> String {
+    let expires_in = chrono::Duration::from_std(expires_in).unwrap_or(chrono::Duration::zero());
+    (now + expires_in).to_rfc3339()
+}
+
+/// Colors of the legend entries by grid value
+fn cell_colors(legend: &HashMap<String, GumType>) -> HashMap<u8, Rgb> {
+    legend
+        .iter()
+        .filter_map(|(index, gum_type)| Some((index.parse().ok()?, gum_type.to_rgb()?)))
+        .collect()
+}
+
+/// Render the design as a PNG with one filled circle per gum piece
+pub fn render_png(
+    grid: &[Vec<u8>],
+    legend: &HashMap<String, GumType>,
+    cell_size: u32,
+) -> Result<Vec<u8>> {
+    let rows = grid.len() as u32;
+    let cols = grid.first().map_or(0, |row| row.len()) as u32;
+    if rows == 0 || cols == 0 {
+        return Err(anyhow!("Cannot render an empty design"));
+    }
+
+    let cell = cell_size.min(MAX_PNG_SIDE / rows.max(cols)).max(1);
+    let colors = cell_colors(legend);
+    let mut image = image::RgbImage::from_pixel(cols * cell, rows * cell, image::Rgb([255; 3]));
+
+    // Pixel offsets inside a cell that belong to the circle
+    let center = (cell as f64 - 1.0) / 2.0;
+    let radius = cell as f64 / 2.0;
+    let mask: Vec<(u32, u32)> = (0..cell)
+        .flat_map(|y| (0..cell).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let (dx, dy) = (x as f64 - center, y as f64 - center);
+            dx * dx + dy * dy <= radius * radius
+        })
+        .collect();
+
+    for (row_idx, row) in grid.iter().enumerate() {
+        for (col_idx, value) in row.iter().enumerate() {
+            let color = colors.get(value).unwrap_or(&UNKNOWN_COLOR);
+            let pixel = image::Rgb([color.r, color.g, color.b]);
+            for &(x, y) in &mask {
+                image.put_pixel(col_idx as u32 * cell + x, row_idx as u32 * cell + y, pixel);
+            }
+        }
+    }
+
+    let mut bytes = Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png)?;
+    Ok(bytes.into_inner())
+}
+
+/// Render the design as a single-page PDF with one filled circle per gum piece
+///
+/// The PDF is written directly: one page whose content stream draws each
+/// circle as four Bézier curves.
+pub fn render_pdf(grid: &[Vec<u8>], legend: &HashMap<String, GumType>, cell_size: u32) -> Vec<u8> {
+    let rows = grid.len();
+    let cols = grid.first().map_or(0, |row| row.len());
+    let cell = (cell_size as f64).min(MAX_PDF_SIDE / rows.max(cols).max(1) as f64);
+    let width = cols as f64 * cell;
+    let height = rows as f64 * cell;
+    let colors = cell_colors(legend);
+
+    // Control point distance for approximating a quarter circle
+    const KAPPA: f64 = 0.552_284_75;
+    let r = cell / 2.0;
+    let k = KAPPA * r;
+
+    let mut content = String::new();
+    let mut current: Option<&Rgb> = None;
+    for (row_idx, row) in grid.iter().enumerate() {
+        for (col_idx, value) in row.iter().enumerate() {
+            let color = colors.get(value).unwrap_or(&UNKNOWN_COLOR);
+            if current != Some(color) {
+                content.push_str(&format!(
+                    "{:.3} {:.3} {:.3} rg\n",
+                    color.r as f64 / 255.0,
+                    color.g as f64 / 255.0,
+                    color.b as f64 / 255.0
+                ));
+                current = Some(color);
+            }
+            // PDF coordinates start at the bottom left
+            let x = col_idx as f64 * cell + r;
+            let y = height - (row_idx as f64 * cell + r);
+            let quarters = [
+                [(x + r, y + k), (x + k, y + r), (x, y + r)],
+                [(x - k, y + r), (x - r, y + k), (x - r, y)],
+                [(x - r, y - k), (x - k, y - r), (x, y - r)],
+                [(x + k, y - r), (x + r, y - k), (x + r, y)],
+            ];
+            content.push_str(&format!("{:.2} {:.2} m\n", x + r, y));
+            for points in quarters {
+                for (px, py) in points {
+                    content.push_str(&format!("{:.2} {:.2} ", px, py));
+                }
+                content.push_str("c\n");
+            }
+            content.push_str("f\n");
+        }
+    }
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Contents 4 0 R >>",
+            width, height
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+    ];
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+    let xref_offset = pdf.len();
+    let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        xref.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    xref.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ));
+    pdf.extend_from_slice(xref.as_bytes());
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GumWallConfig;
+    use crate::test_support::{test_context, MockS3};
+    use crate::types::{GridDimensions, StageTimings};
+
+    fn gum(gum_id: &str, hex_color: &str) -> GumType {
+        GumType {
+            gum_id: gum_id.to_string(),
+            name: gum_id.to_string(),
+            hex_color: hex_color.to_string(),
+            price_per_piece: 0.05,
+            brand: None,
+            flavor: None,
+            is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: Default::default(),
+        }
+    }
+
+    fn legend() -> HashMap<String, GumType> {
+        HashMap::from([
+            ("0".to_string(), gum("red", "#FF0000")),
+            ("1".to_string(), gum("blue", "#0000FF")),
+        ])
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        let config = ArtifactsConfig::default();
+        let source = ImageSource::S3 {
+            bucket: "art".to_string(),
+            key: "photos/market cat.jpg".to_string(),
+        };
+        // The caller's bucket is only written to when it is the uploads bucket
+        assert!(ArtifactLocation::resolve(&config, None, &source).is_err());
+        assert!(ArtifactLocation::resolve(&config, Some("intake"), &source).is_err());
+        let location = ArtifactLocation::resolve(&config, Some("art"), &source).unwrap();
+        assert_eq!(location.bucket, "art");
+        assert!(location.base_key.starts_with("photos/market_cat-gum-wall-"));
+        assert!(location.key(ArtifactFormat::Pdf).ends_with(".pdf"));
+
+        let config = ArtifactsConfig {
+            bucket: Some("designs".to_string()),
+            prefix: Some("out".to_string()),
+            ..ArtifactsConfig::default()
+        };
+        let location = ArtifactLocation::resolve(&config, None, &source).unwrap();
+        assert_eq!(location.bucket, "designs");
+        assert!(location.base_key.starts_with("out/market_cat-gum-wall-"));
+
+        // Inline images have no bucket of their own
+        let inline = ImageSource::Inline {
+            data: Vec::new(),
+            mime_type: None,
+        };
+        assert!(ArtifactLocation::resolve(&ArtifactsConfig::default(), None, &inline).is_err());
+        let location = ArtifactLocation::resolve(&config, None, &inline).unwrap();
+        assert!(location.base_key.starts_with("out/design-gum-wall-"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_design_to_s3_compatible_endpoint() {
+        let s3 = MockS3::start().await;
+        let config = GumWallConfig {
+            s3: s3.config(),
+            ..GumWallConfig::default()
+        };
+        let context = test_context(config);
+        let location = ArtifactLocation {
+            bucket: "art".to_string(),
+            base_key: "photos/cat-gum-wall-0001".to_string(),
+        };
+        let grid = vec![vec![0, 1]];
+        let design = AnalyzeImageOutput {
+            grid_rle: crate::types::rle_encode_grid(&grid),
+            legend: legend(),
+            dimensions: GridDimensions {
+                rows: 1,
+                cols: 2,
+                total_positions: 2,
+            },
+            gum_counts: HashMap::new(),
+            purchase_counts: HashMap::new(),
+            total_gums: 2,
+            total_spare_pieces: 0,
+            image_description: String::new(),
+            image_analysis: None,
+            moderation: None,
+            timings: StageTimings::default(),
+            svg: None,
+            artifacts: Vec::new(),
+        };
+
+        let artifacts = publish_design(&context, &location, &design, &grid, "<svg/>".to_string())
+            .await
+            .unwrap();
+        assert_eq!(artifacts.len(), 4);
+        assert_eq!(
+            s3.keys("art"),
+            [
+                "photos/cat-gum-wall-0001.json",
+                "photos/cat-gum-wall-0001.pdf",
+                "photos/cat-gum-wall-0001.png",
+                "photos/cat-gum-wall-0001.svg",
+            ]
+        );
+        let (content_type, bytes) = s3.get("art", "photos/cat-gum-wall-0001.png").unwrap();
+        assert_eq!(content_type, "image/png");
+        assert_eq!(bytes, render_png(&grid, &legend(), 10).unwrap());
+
+        let svg = &artifacts[0];
+        assert_eq!(svg.s3_uri, "s3://art/photos/cat-gum-wall-0001.svg");
+        assert!(svg.url.contains("X-Amz-Expires=3600"));
+        assert!(svg.url.contains("X-Amz-Signature="));
+
+        // The presigned URL downloads the file
+        let body = reqwest::get(&svg.url).await.unwrap().text().await.unwrap();
+        assert_eq!(body, "<svg/>");
+    }
+
+    #[test]
+    fn test_render_png() {
+        let grid = vec![vec![0, 1], vec![1, 0]];
+        let png = render_png(&grid, &legend(), 10).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgb8();
+        assert_eq!(image.dimensions(), (20, 20));
+        assert_eq!(image.get_pixel(5, 5), &image::Rgb([255, 0, 0]));
+        assert_eq!(image.get_pixel(15, 5), &image::Rgb([0, 0, 255]));
+        // Corners between the gum pieces stay white
+        assert_eq!(image.get_pixel(0, 0), &image::Rgb([255, 255, 255]));
+
+        assert!(render_png(&[], &legend(), 10).is_err());
+    }
+
+    #[test]
+    fn test_render_pdf() {
+        let grid = vec![vec![0, 1, 1]];
+        let pdf = render_pdf(&grid, &legend(), 10);
+        let text = String::from_utf8(pdf.clone()).unwrap();
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.contains("/MediaBox [0 0 30.00 10.00]"));
+        // Consecutive pieces of the same color share one color operator
+        assert_eq!(text.matches(" rg\n").count(), 2);
+
+        // The xref table points at each object
+        let startxref: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(text[startxref..].starts_with("xref"));
+        let first_object = text[startxref..].lines().nth(3).unwrap()[..10]
+            .parse::<usize>()
+            .unwrap();
+        assert!(text[first_object..].starts_with("1 0 obj"));
+    }
+}
@@ -0,0 +1,315 @@
+//! TTL cache for gum catalogs
+//!
+//! Wraps any [`GumCatalog`] so repeated tool calls reuse the last fetched gum
+//! types instead of hitting AppSync every time. Entries older than the TTL are
+//! still served for a stale window while a background task refreshes them.
+//! Concurrent cache misses share a single fetch.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::catalog::GumCatalog;
+use crate::types::GumType;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+/// Default time a fetched catalog is considered fresh
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Default time an expired catalog may still be served while refreshing
+pub const DEFAULT_STALE_WINDOW: Duration = Duration::from_secs(600);
+
+struct CacheEntry {
+    gum_types: Vec<GumType>,
+    fetched_at: Instant,
+}
+
+/// Shared cache state, cloned into background refresh tasks
+struct CacheState<C> {
+    inner: C,
+    entry: RwLock<Option<CacheEntry>>,
+    refreshing: AtomicBool,
+    /// Held while a cache miss is being fetched
+    loading: Mutex<()>,
+}
+
+impl<C: GumCatalog> CacheState<C> {
+    /// The cached gum types, if they are younger than `ttl`
+    async fn fresh(&self, ttl: Duration) -> Option<Vec<GumType>> {
+        let entry = self.entry.read().await;
+        entry
+            .as_ref()
+            .filter(|entry| entry.fetched_at.elapsed() < ttl)
+            .map(|entry| entry.gum_types.clone())
+    }
+
+    /// Fetch from the inner catalog and store the result
+    async fn fetch_and_store(&self) -> Result<Vec<GumType>> {
+        let gum_types = self.inner.refresh().await?;
+        *self.entry.write().await = Some(CacheEntry {
+            gum_types: gum_types.clone(),
+            fetched_at: Instant::now(),
+        });
+        Ok(gum_types)
+    }
+}
+
+/// Clears the refreshing flag when a background refresh ends, even if it panics
+struct RefreshGuard<C>(Arc<CacheState<C>>);
+
+impl<C> Drop for RefreshGuard<C> {
+    fn drop(&mut self) {
+        self.0.refreshing.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A [`GumCatalog`] wrapper with TTL-based caching and stale-while-revalidate
+pub struct CachedCatalog<C> {
+    state: Arc<CacheState<C>>,
+    ttl: Duration,
+    stale_window: Duration,
+}
+
+impl<C: GumCatalog + 'static> CachedCatalog<C> {
+    /// Cache `inner` for `ttl`, serving stale data for up to `stale_window`
+    /// past expiry while refreshing in the background
+    pub fn new(inner: C, ttl: Duration, stale_window: Duration) -> Self {
+        CachedCatalog {
+            state: Arc::new(CacheState {
+                inner,
+                entry: RwLock::new(None),
+                refreshing: AtomicBool::new(false),
+                loading: Mutex::new(()),
+            }),
+            ttl,
+            stale_window,
+        }
+    }
+
+    /// Start a background refresh unless one is already running
+    fn spawn_refresh(&self) {
+        if self.state.refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let guard = RefreshGuard(self.state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = guard.0.fetch_and_store().await {
+                tracing::warn!("Background gum catalog refresh failed: {}", e);
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl<C: GumCatalog + 'static> GumCatalog for CachedCatalog<C> {
+    fn source(&self) -> &str {
+        self.state.inner.source()
+    }
+
+    async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+        let stale = {
+            let entry = self.state.entry.read().await;
+            match entry.as_ref() {
+                Some(entry) => {
+                    let age = entry.fetched_at.elapsed();
+                    if age < self.ttl {
+                        tracing::debug!(age_secs = age.as_secs(), "Gum catalog cache hit");
+                        return Ok(entry.gum_types.clone());
+                    }
+                    if age < self.ttl + self.stale_window {
+                        tracing::info!(
+                            age_secs = age.as_secs(),
+                            "Gum catalog cache stale, refreshing in background"
+                        );
+                        Some(entry.gum_types.clone())
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            }
+        };
+
+        if let Some(gum_types) = stale {
+            self.spawn_refresh();
+            return Ok(gum_types);
+        }
+
+        // Only one caller fetches; the others wait for it and reuse the result
+        let _loading = self.state.loading.lock().await;
+        if let Some(gum_types) = self.state.fresh(self.ttl).await {
+            return Ok(gum_types);
+        }
+        tracing::info!("Gum catalog cache miss, fetching from {}", self.source());
+        self.state.fetch_and_store().await
+    }
+
+    async fn refresh(&self) -> Result<Vec<GumType>> {
+        tracing::info!("Refreshing gum catalog cache from {}", self.source());
+        self.state.fetch_and_store().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::AppSyncCatalog;
+    use crate::test_support::MockServer;
+    use std::sync::atomic::AtomicUsize;
+
+    const GRAPHQL_BODY: &str = r##"{"data":{"listGumTypes":{"items":[
+        {"id":"1","gumId":"red","name":"Red","hexColor":"#FF0000","pricePerPiece":0.05,"brand":null,"flavor":null,"isAvailable":true}
+    ]}}}"##;
+
+    async fn mock_appsync() -> MockServer {
+        MockServer::start(|request| {
+            let authorized = request.method == "POST"
+                && request.path == "/"
+                && request.header("x-api-key") == Some("test-key")
+                && request.body.contains("listGumTypes");
+            if authorized {
+                (200, GRAPHQL_BODY.to_string())
+            } else {
+                (
+                    401,
+                    r#"{"errors":[{"message":"Unauthorized"}]}"#.to_string(),
+                )
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_within_ttl() {
+        let server = mock_appsync().await;
+        let catalog = CachedCatalog::new(
+            AppSyncCatalog::new(server.url.clone(), "test-key"),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+
+        assert_eq!(catalog.list_gum_types().await.unwrap().len(), 1);
+        assert_eq!(catalog.list_gum_types().await.unwrap().len(), 1);
+        assert_eq!(server.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_entry_served_while_refreshing() {
+        let server = mock_appsync().await;
+        let catalog = CachedCatalog::new(
+            AppSyncCatalog::new(server.url.clone(), "test-key"),
+            Duration::ZERO,
+            Duration::from_secs(60),
+        );
+
+        catalog.list_gum_types().await.unwrap();
+        // Expired but within the stale window: served from cache, refreshed in background
+        assert_eq!(catalog.list_gum_types().await.unwrap()[0].gum_id, "red");
+
+        for _ in 0..50 {
+            if server.hits() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(server.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_fetched_synchronously() {
+        let server = mock_appsync().await;
+        let catalog = CachedCatalog::new(
+            AppSyncCatalog::new(server.url.clone(), "test-key"),
+            Duration::ZERO,
+            Duration::ZERO,
+        );
+
+        catalog.list_gum_types().await.unwrap();
+        catalog.list_gum_types().await.unwrap();
+        assert_eq!(server.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_refresh_bypasses_cache() {
+        let server = mock_appsync().await;
+        let catalog = CachedCatalog::new(
+            AppSyncCatalog::new(server.url.clone(), "test-key"),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+
+        catalog.list_gum_types().await.unwrap();
+        catalog.refresh().await.unwrap();
+        catalog.list_gum_types().await.unwrap();
+        assert_eq!(server.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_fetch_once() {
+        let server = mock_appsync().await;
+        let catalog = CachedCatalog::new(
+            AppSyncCatalog::new(server.url.clone(), "test-key"),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+
+        let (a, b, c) = tokio::join!(
+            catalog.list_gum_types(),
+            catalog.list_gum_types(),
+            catalog.list_gum_types()
+        );
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        assert_eq!(server.hits(), 1);
+    }
+
+    /// Catalog whose second fetch panics
+    struct PanickyCatalog {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl GumCatalog for PanickyCatalog {
+        fn source(&self) -> &str {
+            "panicky"
+        }
+
+        async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) == 1 {
+                panic!("catalog fetch panicked");
+            }
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_resumes_after_panic() {
+        let catalog = CachedCatalog::new(
+            PanickyCatalog {
+                calls: AtomicUsize::new(0),
+            },
+            Duration::ZERO,
+            Duration::from_secs(60),
+        );
+        let calls = || catalog.state.inner.calls.load(Ordering::SeqCst);
+
+        catalog.list_gum_types().await.unwrap();
+        // The background refresh panics, which must not leave it marked as running
+        catalog.list_gum_types().await.unwrap();
+        for _ in 0..50 {
+            if calls() == 2 && !catalog.state.refreshing.load(Ordering::SeqCst) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        catalog.list_gum_types().await.unwrap();
+        for _ in 0..50 {
+            if calls() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(calls(), 3);
+    }
+}
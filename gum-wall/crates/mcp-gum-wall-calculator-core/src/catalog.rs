@@ -0,0 +1,458 @@
+//! Gum catalog sources
+//!
+//! Tools look up gum types through the [`GumCatalog`] trait, so the price list
+//! can come from AppSync, a local file, an in-memory list, or the built-in
+//! fallback data. This keeps the server usable offline and makes tests
+//! deterministic.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use crate::cache::CachedCatalog;
+use crate::config::{AppSyncConfig, CatalogSource, GumWallConfig};
+use crate::graphql;
+use crate::types::GumType;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A source of gum types (colors and prices)
+#[async_trait]
+pub trait GumCatalog: Send + Sync {
+    /// Short label describing where the gum types come from (e.g., "appsync")
+    fn source(&self) -> &str;
+
+    /// Fetch all gum types from this source
+    async fn list_gum_types(&self) -> Result<Vec<GumType>>;
+
+    /// Fetch gum types, bypassing any caching layer
+    async fn refresh(&self) -> Result<Vec<GumType>> {
+        self.list_gum_types().await
+    }
+
+    /// True when the gum types last returned are the built-in fallback data
+    /// rather than live prices
+    fn is_fallback(&self) -> bool {
+        self.source() == FallbackCatalog.source()
+    }
+}
+
+// ============================================================================
+// AppSync GraphQL
+// ============================================================================
+
+/// Gum types from the Amplify `GumType` model via AppSync GraphQL
+pub struct AppSyncCatalog {
+    api_url: String,
+    api_key: String,
+    page_size: u32,
+    max_pages: u32,
+    client: reqwest::Client,
+}
+
+impl AppSyncCatalog {
+    pub fn new(api_url: impl Into<String>, api_key: impl Into<String>) -> Self {
+        AppSyncCatalog {
+            api_url: api_url.into(),
+            api_key: api_key.into(),
+            page_size: graphql::DEFAULT_PAGE_SIZE,
+            max_pages: graphql::DEFAULT_MAX_PAGES,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Set the `listGumTypes` page size and the maximum number of pages to follow
+    pub fn with_pagination(mut self, page_size: u32, max_pages: u32) -> Self {
+        self.page_size = page_size;
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Use a shared HTTP client instead of a dedicated one
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn from_config(config: &AppSyncConfig) -> Self {
+        AppSyncCatalog::new(config.api_url.clone(), config.api_key.expose())
+    }
+}
+
+#[async_trait]
+impl GumCatalog for AppSyncCatalog {
+    fn source(&self) -> &str {
+        "appsync"
+    }
+
+    async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+        graphql::fetch_gum_types(
+            &self.client,
+            &self.api_url,
+            &self.api_key,
+            self.page_size,
+            self.max_pages,
+        )
+        .await
+    }
+}
+
+// ============================================================================
+// Local file (JSON / TOML / CSV)
+// ============================================================================
+
+/// Supported file formats for [`FileCatalog`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogFormat {
+    Json,
+    Toml,
+    Csv,
+}
+
+impl CatalogFormat {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "json" => Some(CatalogFormat::Json),
+            "toml" => Some(CatalogFormat::Toml),
+            "csv" => Some(CatalogFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Wrapper accepted by the JSON and TOML formats: `{ "gum_types": [...] }`
+#[derive(Debug, Deserialize)]
+struct GumTypeList {
+    gum_types: Vec<GumType>,
+}
+
+/// Parse gum types from file contents in the given format
+///
+/// JSON accepts either a bare array or an object with a `gum_types` array
+/// (the shape returned by `list_gum_types`). TOML expects `[[gum_types]]`
+/// tables. CSV expects a header row using the `GumType` field names.
+pub fn parse_gum_types(contents: &str, format: CatalogFormat) -> Result<Vec<GumType>> {
+    match format {
+        CatalogFormat::Json => {
+            let value: serde_json::Value =
+                serde_json::from_str(contents).context("Invalid JSON gum catalog")?;
+            if value.is_array() {
+                serde_json::from_value(value).context("Invalid JSON gum catalog")
+            } else {
+                let list: GumTypeList =
+                    serde_json::from_value(value).context("Invalid JSON gum catalog")?;
+                Ok(list.gum_types)
+            }
+        }
+        CatalogFormat::Toml => {
+            let list: GumTypeList = toml::from_str(contents).context("Invalid TOML gum catalog")?;
+            Ok(list.gum_types)
+        }
+        CatalogFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(contents.as_bytes());
+            reader
+                .deserialize()
+                .collect::<std::result::Result<Vec<GumType>, _>>()
+                .context("Invalid CSV gum catalog")
+        }
+    }
+}
+
+/// Gum types read from a local JSON, TOML or CSV file
+///
+/// The file is re-read on every call so edits are picked up without a restart.
+pub struct FileCatalog {
+    path: PathBuf,
+    format: CatalogFormat,
+}
+
+impl FileCatalog {
+    /// Create a file catalog, detecting the format from the file extension
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let format = CatalogFormat::from_path(&path).ok_or_else(|| {
+            anyhow!(
+                "Unsupported gum catalog file '{}': expected .json, .toml or .csv",
+                path.display()
+            )
+        })?;
+        Ok(FileCatalog { path, format })
+    }
+
+    /// Create a file catalog with an explicit format
+    pub fn with_format(path: impl Into<PathBuf>, format: CatalogFormat) -> Self {
+        FileCatalog {
+            path: path.into(),
+            format,
+        }
+    }
+}
+
+#[async_trait]
+impl GumCatalog for FileCatalog {
+    fn source(&self) -> &str {
+        "file"
+    }
+
+    async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("Failed to read gum catalog '{}'", self.path.display()))?;
+        parse_gum_types(&contents, self.format)
+    }
+}
+
+// ============================================================================
+// In-memory and built-in
+// ============================================================================
+
+/// A fixed list of gum types held in memory (useful for tests)
+pub struct InMemoryCatalog {
+    gum_types: Vec<GumType>,
+}
+
+impl InMemoryCatalog {
+    pub fn new(gum_types: Vec<GumType>) -> Self {
+        InMemoryCatalog { gum_types }
+    }
+}
+
+#[async_trait]
+impl GumCatalog for InMemoryCatalog {
+    fn source(&self) -> &str {
+        "memory"
+    }
+
+    async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+        Ok(self.gum_types.clone())
+    }
+}
+
+/// The hardcoded gum types shipped with the server
+pub struct FallbackCatalog;
+
+#[async_trait]
+impl GumCatalog for FallbackCatalog {
+    fn source(&self) -> &str {
+        "fallback"
+    }
+
+    async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+        Ok(graphql::get_fallback_gum_types())
+    }
+}
+
+/// Use a primary catalog, falling back to the built-in gum types when it
+/// fails or returns nothing
+///
+/// The source reports "fallback" while the last load came from the built-in
+/// gum types.
+pub struct WithFallback<C> {
+    primary: C,
+    using_fallback: AtomicBool,
+}
+
+impl<C: GumCatalog> WithFallback<C> {
+    pub fn new(primary: C) -> Self {
+        WithFallback {
+            primary,
+            using_fallback: AtomicBool::new(false),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: GumCatalog> GumCatalog for WithFallback<C> {
+    fn source(&self) -> &str {
+        if self.using_fallback.load(Ordering::Relaxed) {
+            FallbackCatalog.source()
+        } else {
+            self.primary.source()
+        }
+    }
+
+    async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+        let result = self.primary.list_gum_types().await;
+        self.or_fallback(result).await
+    }
+
+    async fn refresh(&self) -> Result<Vec<GumType>> {
+        let result = self.primary.refresh().await;
+        self.or_fallback(result).await
+    }
+}
+
+impl<C: GumCatalog> WithFallback<C> {
+    async fn or_fallback(&self, result: Result<Vec<GumType>>) -> Result<Vec<GumType>> {
+        let using_fallback = !matches!(&result, Ok(types) if !types.is_empty());
+        self.using_fallback.store(using_fallback, Ordering::Relaxed);
+        match result {
+            Ok(types) if !types.is_empty() => Ok(types),
+            Ok(_) => {
+                tracing::warn!(
+                    "No gum types found in {}, using fallback",
+                    self.primary.source()
+                );
+                FallbackCatalog.list_gum_types().await
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to fetch gum types from {}: {}. Using fallback.",
+                    self.primary.source(),
+                    e
+                );
+                FallbackCatalog.list_gum_types().await
+            }
+        }
+    }
+}
+
+/// Build the catalog selected by the configuration
+///
+/// AppSync is cached and falls back to the built-in list on failure; a file
+/// catalog is read as-is so edits show up immediately.
+pub fn catalog_from_config(config: &GumWallConfig) -> Result<Arc<dyn GumCatalog>> {
+    catalog_from_config_with_client(config, reqwest::Client::new())
+}
+
+/// Build the catalog selected by the configuration, sending AppSync requests
+/// through the given HTTP client
+pub fn catalog_from_config_with_client(
+    config: &GumWallConfig,
+    http: reqwest::Client,
+) -> Result<Arc<dyn GumCatalog>> {
+    let source = config.catalog_source().ok_or_else(|| {
+        anyhow!("No gum catalog configured; set catalog.source = \"builtin\" for the built-in list")
+    })?;
+    match source {
+        CatalogSource::Appsync => {
+            let appsync = config
+                .appsync
+                .as_ref()
+                .ok_or_else(|| anyhow!("AppSync catalog selected but AppSync is not configured"))?;
+            Ok(Arc::new(WithFallback::new(CachedCatalog::new(
+                AppSyncCatalog::from_config(appsync)
+                    .with_pagination(config.catalog.page_size, config.catalog.max_pages)
+                    .with_client(http),
+                Duration::from_secs(config.catalog.cache_ttl_secs),
+                Duration::from_secs(config.catalog.stale_secs),
+            ))))
+        }
+        CatalogSource::File => {
+            let path = config
+                .catalog
+                .file
+                .as_ref()
+                .ok_or_else(|| anyhow!("File catalog selected but catalog.file is not set"))?;
+            Ok(Arc::new(FileCatalog::new(path.clone())?))
+        }
+        CatalogSource::Builtin => {
+            tracing::warn!("Using the built-in gum types; prices are not live");
+            Ok(Arc::new(FallbackCatalog))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingCatalog;
+
+    #[async_trait]
+    impl GumCatalog for FailingCatalog {
+        fn source(&self) -> &str {
+            "failing"
+        }
+
+        async fn list_gum_types(&self) -> Result<Vec<GumType>> {
+            Err(anyhow!("unavailable"))
+        }
+    }
+
+    #[test]
+    fn test_parse_json_catalog() {
+        let bare = r##"[{"gum_id": "red", "name": "Red", "hex_color": "#FF0000", "price_per_piece": 0.05}]"##;
+        let types = parse_gum_types(bare, CatalogFormat::Json).unwrap();
+        assert_eq!(types.len(), 1);
+        assert!(types[0].is_available);
+
+        let wrapped = r##"{"gum_types": [{"gum_id": "red", "name": "Red", "hex_color": "#FF0000", "price_per_piece": 0.05}], "count": 1}"##;
+        let types = parse_gum_types(wrapped, CatalogFormat::Json).unwrap();
+        assert_eq!(types[0].gum_id, "red");
+    }
+
+    #[test]
+    fn test_parse_toml_catalog() {
+        let contents = r##"
+[[gum_types]]
+gum_id = "red"
+name = "Red"
+hex_color = "#FF0000"
+price_per_piece = 0.05
+brand = "Acme"
+
+[[gum_types]]
+gum_id = "blue"
+name = "Blue"
+hex_color = "#0000FF"
+price_per_piece = 0.07
+is_available = false
+"##;
+        let types = parse_gum_types(contents, CatalogFormat::Toml).unwrap();
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0].brand.as_deref(), Some("Acme"));
+        assert!(!types[1].is_available);
+    }
+
+    #[test]
+    fn test_parse_csv_catalog() {
+        let contents = "gum_id,name,hex_color,price_per_piece,brand,flavor\n\
+                        red,Red Gum,#FF0000,0.05,Acme,\n\
+                        blue,Blue Gum,#0000FF,0.07,,Mint\n";
+        let types = parse_gum_types(contents, CatalogFormat::Csv).unwrap();
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0].brand.as_deref(), Some("Acme"));
+        assert!(types[0].flavor.is_none());
+        assert_eq!(types[1].flavor.as_deref(), Some("Mint"));
+    }
+
+    #[test]
+    fn test_catalog_format_from_path() {
+        assert_eq!(
+            CatalogFormat::from_path(Path::new("gums.JSON")),
+            Some(CatalogFormat::Json)
+        );
+        assert_eq!(
+            CatalogFormat::from_path(Path::new("gums.csv")),
+            Some(CatalogFormat::Csv)
+        );
+        assert!(FileCatalog::new("gums.xlsx").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_fallback_uses_builtin_types() {
+        let catalog = WithFallback::new(FailingCatalog);
+        let types = catalog.list_gum_types().await.unwrap();
+        assert_eq!(types.len(), graphql::get_fallback_gum_types().len());
+        assert_eq!(catalog.source(), "fallback");
+        assert!(catalog.is_fallback());
+
+        let catalog = WithFallback::new(InMemoryCatalog::new(Vec::new()));
+        assert!(!catalog.list_gum_types().await.unwrap().is_empty());
+        assert!(catalog.is_fallback());
+    }
+
+    #[tokio::test]
+    async fn test_with_fallback_reports_primary_source() {
+        let catalog = WithFallback::new(InMemoryCatalog::new(graphql::get_fallback_gum_types()));
+        catalog.list_gum_types().await.unwrap();
+        assert_eq!(catalog.source(), "memory");
+        assert!(!catalog.is_fallback());
+    }
+}
@@ -0,0 +1,1525 @@
+//! Server configuration
+//!
+//! Settings are read from an optional TOML file (path in `GUM_WALL_CONFIG`),
+//! then overridden by environment variables, then validated before the server
+//! starts. Nothing falls back to embedded credentials: if a source is selected
+//! but not configured, startup fails.
+
+use crate::cache::{DEFAULT_CACHE_TTL, DEFAULT_STALE_WINDOW};
+use crate::graphql::{DEFAULT_MAX_PAGES, DEFAULT_PAGE_SIZE};
+use crate::types::{ModerationCategory, OutputMode};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Environment variable holding the path to the TOML config file
+pub const CONFIG_PATH_ENV: &str = "GUM_WALL_CONFIG";
+
+/// Old environment variable names, still read (with a warning) when the new name is unset
+const DEPRECATED_ENV_NAMES: [(&str, &str); 12] = [
+    ("GUM_WALL_APPSYNC_API_URL", "APPSYNC_API_URL"),
+    ("GUM_WALL_APPSYNC_API_KEY", "APPSYNC_API_KEY"),
+    ("GUM_WALL_CATALOG_SOURCE", "GUM_CATALOG_SOURCE"),
+    ("GUM_WALL_CATALOG_FILE", "GUM_CATALOG_FILE"),
+    ("GUM_WALL_CATALOG_CACHE_TTL_SECS", "GUM_CATALOG_CACHE_TTL_SECS"),
+    ("GUM_WALL_CATALOG_STALE_SECS", "GUM_CATALOG_STALE_SECS"),
+    ("GUM_WALL_VISION_PROVIDER", "VISION_PROVIDER"),
+    ("GUM_WALL_VISION_API_URL", "OPENAI_API_URL"),
+    ("GUM_WALL_VISION_MODEL", "VISION_MODEL"),
+    ("GUM_WALL_VISION_TIMEOUT_SECS", "VISION_TIMEOUT_SECS"),
+    ("GUM_WALL_VISION_MAX_RETRIES", "VISION_MAX_RETRIES"),
+    ("GUM_WALL_BEDROCK_MODEL_ID", "BEDROCK_MODEL_ID"),
+];
+
+/// Errors raised while loading or validating configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file '{path}': {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to parse config file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Invalid value for {name}: {message}")]
+    InvalidEnv { name: String, message: String },
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+/// A secret value that is never printed in logs or debug output
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    /// Access the underlying secret value
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(****)")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("****")
+    }
+}
+
+// ============================================================================
+// Sections
+// ============================================================================
+
+/// AppSync GraphQL endpoint for the Amplify data models
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppSyncConfig {
+    /// GraphQL endpoint URL
+    pub api_url: String,
+    /// AppSync API key
+    pub api_key: Secret,
+}
+
+/// Where gum types come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogSource {
+    /// AppSync, falling back to the built-in list on failure
+    Appsync,
+    /// A local JSON, TOML or CSV file
+    File,
+    /// The built-in gum types only
+    Builtin,
+}
+
+impl std::str::FromStr for CatalogSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "appsync" => Ok(CatalogSource::Appsync),
+            "file" => Ok(CatalogSource::File),
+            "builtin" => Ok(CatalogSource::Builtin),
+            other => Err(format!("unknown catalog source '{}'", other)),
+        }
+    }
+}
+
+/// Gum catalog settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CatalogConfig {
+    /// Catalog source; inferred from the other settings when unset
+    pub source: Option<CatalogSource>,
+    /// Path to a local catalog file (for `source = "file"`)
+    pub file: Option<PathBuf>,
+    /// Seconds fetched gum types are considered fresh
+    pub cache_ttl_secs: u64,
+    /// Seconds expired gum types may still be served while refreshing
+    pub stale_secs: u64,
+    /// Gum types requested per AppSync page
+    pub page_size: u32,
+    /// Maximum number of AppSync pages fetched for one listing
+    pub max_pages: u32,
+}
+
+impl Default for CatalogConfig {
+    fn default() -> Self {
+        CatalogConfig {
+            source: None,
+            file: None,
+            cache_ttl_secs: DEFAULT_CACHE_TTL.as_secs(),
+            stale_secs: DEFAULT_STALE_WINDOW.as_secs(),
+            page_size: DEFAULT_PAGE_SIZE,
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+}
+
+/// Which vision model describes analyzed images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VisionProviderKind {
+    /// An OpenAI-compatible chat completions API
+    Openai,
+    /// The Amazon Bedrock Converse API
+    Bedrock,
+    /// Offline descriptions computed from the image's colors and brightness
+    Local,
+}
+
+impl std::str::FromStr for VisionProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(VisionProviderKind::Openai),
+            "bedrock" => Ok(VisionProviderKind::Bedrock),
+            "local" => Ok(VisionProviderKind::Local),
+            other => Err(format!("unknown vision provider '{}'", other)),
+        }
+    }
+}
+
+/// Vision model settings
+///
+/// The top-level fields configure an OpenAI-compatible chat completions API;
+/// `[vision.bedrock]` configures Amazon Bedrock.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisionConfig {
+    /// Vision provider; inferred from the configured API keys when unset
+    pub provider: Option<VisionProviderKind>,
+    /// Chat completions endpoint URL
+    pub api_url: String,
+    /// Model name
+    pub model: String,
+    /// API key for the OpenAI-compatible API
+    pub api_key: Option<Secret>,
+    /// Maximum tokens in the model response
+    pub max_tokens: u32,
+    /// Longest image edge sent to the model, in pixels; larger images are downscaled
+    pub max_image_edge: u32,
+    /// Timeout for each vision API request, in seconds
+    pub timeout_secs: u64,
+    /// Retries after a rate limit (429), server error (5xx) or timeout
+    pub max_retries: u32,
+    /// Delay before the first retry, in milliseconds; doubled for each further retry
+    pub retry_delay_ms: u64,
+    /// Amazon Bedrock settings
+    pub bedrock: BedrockVisionConfig,
+}
+
+impl Default for VisionConfig {
+    fn default() -> Self {
+        VisionConfig {
+            provider: None,
+            api_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            api_key: None,
+            max_tokens: 300,
+            max_image_edge: 1024,
+            timeout_secs: 30,
+            max_retries: 3,
+            retry_delay_ms: 500,
+            bedrock: BedrockVisionConfig::default(),
+        }
+    }
+}
+
+impl VisionConfig {
+    /// The provider to use: OpenAI when its API key is set, then Bedrock, otherwise local
+    pub fn provider_kind(&self) -> VisionProviderKind {
+        match self.provider {
+            Some(provider) => provider,
+            None if self.api_key.is_some() => VisionProviderKind::Openai,
+            None if self.bedrock.api_key.is_some() => VisionProviderKind::Bedrock,
+            None => VisionProviderKind::Local,
+        }
+    }
+}
+
+/// Amazon Bedrock vision settings (Converse API with a Bedrock API key)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BedrockVisionConfig {
+    /// Bedrock Runtime endpoint; defaults to the endpoint of `region`
+    pub endpoint_url: Option<String>,
+    /// AWS region of the default endpoint
+    pub region: String,
+    /// Model ID or inference profile ID
+    pub model_id: String,
+    /// Bedrock API key, sent as a bearer token
+    pub api_key: Option<Secret>,
+}
+
+impl Default for BedrockVisionConfig {
+    fn default() -> Self {
+        BedrockVisionConfig {
+            endpoint_url: None,
+            region: "us-east-1".to_string(),
+            model_id: "anthropic.claude-3-haiku-20240307-v1:0".to_string(),
+            api_key: None,
+        }
+    }
+}
+
+impl BedrockVisionConfig {
+    /// The Bedrock Runtime endpoint to call
+    pub fn endpoint(&self) -> String {
+        match &self.endpoint_url {
+            Some(endpoint_url) => endpoint_url.trim_end_matches('/').to_string(),
+            None => format!("https://bedrock-runtime.{}.amazonaws.com", self.region),
+        }
+    }
+}
+
+/// What happens to images that content moderation flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationMode {
+    /// Images are not screened
+    #[default]
+    Off,
+    /// Flagged images are designed as usual, with the moderation result in the output
+    Flag,
+    /// Flagged images are refused
+    Reject,
+}
+
+impl std::str::FromStr for ModerationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(ModerationMode::Off),
+            "flag" => Ok(ModerationMode::Flag),
+            "reject" => Ok(ModerationMode::Reject),
+            other => Err(format!("unknown moderation mode '{}'", other)),
+        }
+    }
+}
+
+/// Content moderation settings
+///
+/// Images are screened by a vision provider before a wall is built from them.
+/// `provider` picks a different provider than `[vision]` uses, e.g. `local`
+/// for the offline stub classifier.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationConfig {
+    pub mode: ModerationMode,
+    /// Provider that screens images; defaults to the vision provider
+    pub provider: Option<VisionProviderKind>,
+    /// Categories that count against an image; all categories when empty
+    pub categories: Vec<ModerationCategory>,
+    /// Whether an image whose check fails counts as flagged
+    pub fail_closed: bool,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        ModerationConfig {
+            mode: ModerationMode::Off,
+            provider: None,
+            categories: Vec::new(),
+            fail_closed: true,
+        }
+    }
+}
+
+impl ModerationConfig {
+    /// Vision settings for the provider that screens images
+    pub fn vision_config(&self, vision: &VisionConfig) -> VisionConfig {
+        let mut config = vision.clone();
+        if let Some(provider) = self.provider {
+            config.provider = Some(provider);
+        }
+        config
+    }
+}
+
+/// S3 client settings
+///
+/// Unset values fall back to the AWS SDK defaults (environment variables,
+/// shared config files, instance roles). Set `endpoint_url` and
+/// `force_path_style` to use an S3-compatible store such as MinIO or LocalStack.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    /// Custom endpoint (e.g. "http://localhost:9000")
+    pub endpoint_url: Option<String>,
+    /// AWS region (e.g. "us-east-1")
+    pub region: Option<String>,
+    /// Address buckets as path segments (`http://host/bucket/key`) instead of subdomains
+    pub force_path_style: bool,
+    /// Named profile from the shared AWS config files
+    pub profile: Option<String>,
+    /// Static access key ID; set together with `secret_access_key`
+    pub access_key_id: Option<String>,
+    /// Static secret access key
+    pub secret_access_key: Option<Secret>,
+}
+
+/// Image source settings for analyze_image_for_gum_wall
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImagesConfig {
+    /// Largest image accepted from any source, in bytes
+    pub max_bytes: u64,
+    /// Largest image width or height, in pixels
+    pub max_dimension: u32,
+    /// Largest image area (width × height), in pixels; guards against decompression bombs
+    pub max_pixels: u64,
+    /// Allow `file://` image URIs (reads files on the server; meant for local testing)
+    pub allow_file_urls: bool,
+    /// Timeout for downloading https images, in seconds
+    pub download_timeout_secs: u64,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        ImagesConfig {
+            max_bytes: 20 * 1024 * 1024,
+            max_dimension: 12_000,
+            max_pixels: 40_000_000,
+            allow_file_urls: false,
+            download_timeout_secs: 30,
+        }
+    }
+}
+
+/// Longest lifetime S3 allows for a presigned URL (7 days)
+pub const MAX_URL_EXPIRY_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Design file output settings for analyze_image_for_gum_wall
+///
+/// In `s3` output mode the SVG, PNG, PDF and design JSON are uploaded next to
+/// the source image (or to `bucket`/`prefix`) and returned as presigned URLs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArtifactsConfig {
+    /// Output mode used when the tool call does not choose one
+    pub output: OutputMode,
+    /// Bucket for design files; required for S3 output unless the image is in `uploads.bucket`
+    pub bucket: Option<String>,
+    /// Key prefix for design files (e.g. "designs/"); defaults to the source image's folder
+    pub prefix: Option<String>,
+    /// Lifetime of the presigned download URLs, in seconds
+    pub url_expiry_secs: u64,
+    /// Size of one gum piece in the PNG (pixels) and PDF (points)
+    pub cell_size: u32,
+}
+
+impl Default for ArtifactsConfig {
+    fn default() -> Self {
+        ArtifactsConfig {
+            output: OutputMode::Inline,
+            bucket: None,
+            prefix: None,
+            url_expiry_secs: 3600,
+            cell_size: 10,
+        }
+    }
+}
+
+/// Settings for the create_upload_url tool
+///
+/// The tool is registered when `bucket` is set. Clients only choose a file
+/// name; keys are always generated under `prefix`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UploadsConfig {
+    /// Bucket that receives uploaded images
+    pub bucket: Option<String>,
+    /// Key prefix for uploaded images
+    pub prefix: String,
+    /// Content types clients may upload
+    pub allowed_content_types: Vec<String>,
+    /// Largest upload accepted, in bytes (at most `images.max_bytes`)
+    pub max_bytes: u64,
+    /// Lifetime of the presigned upload URLs, in seconds
+    pub url_expiry_secs: u64,
+}
+
+impl Default for UploadsConfig {
+    fn default() -> Self {
+        UploadsConfig {
+            bucket: None,
+            prefix: "uploads/".to_string(),
+            allowed_content_types: ["image/jpeg", "image/png", "image/gif", "image/webp"]
+                .map(String::from)
+                .to_vec(),
+            max_bytes: 20 * 1024 * 1024,
+            url_expiry_secs: 900,
+        }
+    }
+}
+
+/// Pricing settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PricingConfig {
+    /// ISO 4217 base currency; gum types without a currency are priced in it
+    pub currency: String,
+    /// Exchange rates: value of one unit of each listed currency in the base currency
+    pub rates: HashMap<String, Decimal>,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        PricingConfig {
+            currency: "USD".to_string(),
+            rates: HashMap::new(),
+        }
+    }
+}
+
+/// Spare pieces bought on top of the design counts for drops and rejects
+///
+/// Each gum type gets the larger of `percent` of its count (rounded up) and
+/// `min_spare` extra pieces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverageConfig {
+    /// Spare pieces as a percentage of each gum type's count
+    pub percent: f64,
+    /// Minimum spare pieces per gum type
+    pub min_spare: u32,
+}
+
+impl OverageConfig {
+    /// These settings with any per-request overrides applied
+    pub fn with_overrides(&self, percent: Option<f64>, min_spare: Option<u32>) -> Self {
+        OverageConfig {
+            percent: percent.unwrap_or(self.percent),
+            min_spare: min_spare.unwrap_or(self.min_spare),
+        }
+    }
+
+    /// Spare pieces to add to `count` pieces of one gum type
+    pub fn spare_pieces(&self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let by_percent = (count as f64 * self.percent / 100.0).ceil() as u32;
+        by_percent.max(self.min_spare)
+    }
+}
+
+/// Per-piece properties for gum types that don't set their own
+///
+/// Defaults describe a typical sugared stick of gum.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GumDefaultsConfig {
+    /// Weight in grams (USDA FoodData Central: one stick of chewing gum is 3 g)
+    pub weight_g: f64,
+    /// Minutes of chewing before a piece is soft enough to place
+    pub chew_minutes: f64,
+    /// Sugar in grams (USDA FoodData Central: chewing gum is about 66% sugar)
+    pub sugar_g: f64,
+    /// Volume in cubic centimeters (3 g at roughly 1.2 g/cm³)
+    pub volume_cm3: f64,
+    /// Calories burned per hour of chewing (Levine et al., NEJM 1999: about 11 kcal/h)
+    pub calories_per_chew_hour: f64,
+}
+
+impl Default for GumDefaultsConfig {
+    fn default() -> Self {
+        GumDefaultsConfig {
+            weight_g: 3.0,
+            chew_minutes: 5.0,
+            sugar_g: 2.0,
+            volume_cm3: 2.5,
+            calories_per_chew_hour: 11.0,
+        }
+    }
+}
+
+/// Installation labor settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LaborConfig {
+    /// Default number of people on the crew
+    pub crew_size: u32,
+    /// Pieces one person places per hour
+    pub placement_rate: f64,
+    /// Minutes of surface preparation per square meter of wall
+    pub prep_minutes_per_m2: f64,
+    /// Working hours per person per day
+    pub hours_per_day: f64,
+    /// Hourly rate per person, in the base pricing currency
+    pub hourly_rate: Decimal,
+}
+
+impl Default for LaborConfig {
+    fn default() -> Self {
+        LaborConfig {
+            crew_size: 2,
+            placement_rate: 120.0,
+            prep_minutes_per_m2: 10.0,
+            hours_per_day: 8.0,
+            hourly_rate: Decimal::new(25, 0),
+        }
+    }
+}
+
+/// A volume discount applied when the materials subtotal reaches `min_subtotal`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiscountTier {
+    /// Materials subtotal (base currency) from which the tier applies
+    pub min_subtotal: Decimal,
+    /// Discount on materials, in percent
+    pub percent: Decimal,
+}
+
+/// Quote settings: tax, volume discounts, shipping and validity
+///
+/// Amounts are in the base pricing currency and converted for each quote.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuoteConfig {
+    /// Sales tax rate as a fraction (e.g. 0.0825 for 8.25%)
+    pub tax_rate: Decimal,
+    /// Whether shipping is taxed
+    pub tax_shipping: bool,
+    /// Whether installation labor is taxed
+    pub tax_labor: bool,
+    /// Volume discount tiers; the highest tier reached applies
+    pub discount_tiers: Vec<DiscountTier>,
+    /// Flat shipping charge
+    pub shipping_base: Decimal,
+    /// Shipping charge per kilogram of gum
+    pub shipping_per_kg: Decimal,
+    /// Discounted materials subtotal from which shipping is free
+    pub free_shipping_over: Option<Decimal>,
+    /// Days a quote stays valid
+    pub validity_days: u32,
+}
+
+impl Default for QuoteConfig {
+    fn default() -> Self {
+        QuoteConfig {
+            tax_rate: Decimal::ZERO,
+            tax_shipping: false,
+            tax_labor: false,
+            discount_tiers: Vec::new(),
+            shipping_base: Decimal::new(500, 2),
+            shipping_per_kg: Decimal::new(150, 2),
+            free_shipping_over: None,
+            validity_days: 30,
+        }
+    }
+}
+
+/// Admin tool settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Register the catalog management tools (create/update/retire gum types)
+    pub enabled: bool,
+}
+
+/// Order placement settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OrdersConfig {
+    /// Register the place_gum_order tool (writes `Order` records through AppSync)
+    pub enabled: bool,
+}
+
+// ============================================================================
+// Top-level config
+// ============================================================================
+
+/// Complete configuration for the Gum Wall Calculator server
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GumWallConfig {
+    pub appsync: Option<AppSyncConfig>,
+    pub catalog: CatalogConfig,
+    pub vision: VisionConfig,
+    pub moderation: ModerationConfig,
+    pub images: ImagesConfig,
+    pub s3: S3Config,
+    pub artifacts: ArtifactsConfig,
+    pub uploads: UploadsConfig,
+    pub pricing: PricingConfig,
+    pub overage: OverageConfig,
+    pub gum_defaults: GumDefaultsConfig,
+    pub labor: LaborConfig,
+    pub quote: QuoteConfig,
+    pub admin: AdminConfig,
+    pub orders: OrdersConfig,
+}
+
+impl GumWallConfig {
+    /// Load configuration from the config file (if any) and the process environment
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match std::env::var(CONFIG_PATH_ENV) {
+            Ok(path) => {
+                let path = PathBuf::from(path);
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|source| ConfigError::Read { path, source })?;
+                Self::from_toml(&contents)?
+            }
+            Err(_) => GumWallConfig::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse configuration from TOML
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Apply environment variable overrides using the given lookup
+    pub fn apply_env<F>(&mut self, lookup: F) -> Result<(), ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let lookup = |name: &str| lookup_env(&lookup, name);
+        match (lookup("GUM_WALL_APPSYNC_API_URL"), lookup("GUM_WALL_APPSYNC_API_KEY")) {
+            (Some(api_url), Some(api_key)) => {
+                self.appsync = Some(AppSyncConfig {
+                    api_url,
+                    api_key: Secret::new(api_key),
+                });
+            }
+            (Some(api_url), None) => match self.appsync.as_mut() {
+                Some(appsync) => appsync.api_url = api_url,
+                None => {
+                    return Err(ConfigError::Invalid(
+                        "GUM_WALL_APPSYNC_API_URL is set but no AppSync API key is configured"
+                            .to_string(),
+                    ))
+                }
+            },
+            (None, Some(api_key)) => match self.appsync.as_mut() {
+                Some(appsync) => appsync.api_key = Secret::new(api_key),
+                None => {
+                    return Err(ConfigError::Invalid(
+                        "GUM_WALL_APPSYNC_API_KEY is set but no AppSync API URL is configured"
+                            .to_string(),
+                    ))
+                }
+            },
+            (None, None) => {}
+        }
+
+        if let Some(source) = lookup("GUM_WALL_CATALOG_SOURCE") {
+            self.catalog.source = Some(parse_env("GUM_WALL_CATALOG_SOURCE", &source)?);
+        }
+        if let Some(file) = lookup("GUM_WALL_CATALOG_FILE") {
+            self.catalog.file = Some(PathBuf::from(file));
+        }
+        if let Some(ttl) = lookup("GUM_WALL_CATALOG_CACHE_TTL_SECS") {
+            self.catalog.cache_ttl_secs = parse_env("GUM_WALL_CATALOG_CACHE_TTL_SECS", &ttl)?;
+        }
+        if let Some(stale) = lookup("GUM_WALL_CATALOG_STALE_SECS") {
+            self.catalog.stale_secs = parse_env("GUM_WALL_CATALOG_STALE_SECS", &stale)?;
+        }
+
+        if let Some(provider) = lookup("GUM_WALL_VISION_PROVIDER") {
+            self.vision.provider = Some(parse_env("GUM_WALL_VISION_PROVIDER", &provider)?);
+        }
+        if let Some(api_key) = lookup("OPENAI_API_KEY") {
+            self.vision.api_key = Some(Secret::new(api_key));
+        }
+        if let Some(base_url) = lookup("OPENAI_BASE_URL") {
+            self.vision.api_url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
+        }
+        if let Some(api_url) = lookup("GUM_WALL_VISION_API_URL") {
+            self.vision.api_url = api_url;
+        }
+        if let Some(model) = lookup("GUM_WALL_VISION_MODEL") {
+            self.vision.model = model;
+        }
+        if let Some(timeout) = lookup("GUM_WALL_VISION_TIMEOUT_SECS") {
+            self.vision.timeout_secs = parse_env("GUM_WALL_VISION_TIMEOUT_SECS", &timeout)?;
+        }
+        if let Some(retries) = lookup("GUM_WALL_VISION_MAX_RETRIES") {
+            self.vision.max_retries = parse_env("GUM_WALL_VISION_MAX_RETRIES", &retries)?;
+        }
+        if let Some(mode) = lookup("GUM_WALL_MODERATION") {
+            self.moderation.mode = parse_env("GUM_WALL_MODERATION", &mode)?;
+        }
+        if let Some(provider) = lookup("GUM_WALL_MODERATION_PROVIDER") {
+            self.moderation.provider = Some(parse_env("GUM_WALL_MODERATION_PROVIDER", &provider)?);
+        }
+        if let Some(api_key) = lookup("AWS_BEARER_TOKEN_BEDROCK") {
+            self.vision.bedrock.api_key = Some(Secret::new(api_key));
+        }
+        if let Some(model_id) = lookup("GUM_WALL_BEDROCK_MODEL_ID") {
+            self.vision.bedrock.model_id = model_id;
+        }
+
+        if let Some(endpoint_url) = lookup("GUM_WALL_S3_ENDPOINT_URL") {
+            self.s3.endpoint_url = Some(endpoint_url);
+        }
+        if let Some(region) = lookup("GUM_WALL_S3_REGION") {
+            self.s3.region = Some(region);
+        }
+        if let Some(path_style) = lookup("GUM_WALL_S3_FORCE_PATH_STYLE") {
+            self.s3.force_path_style = parse_env("GUM_WALL_S3_FORCE_PATH_STYLE", &path_style)?;
+        }
+        if let Some(profile) = lookup("GUM_WALL_S3_PROFILE") {
+            self.s3.profile = Some(profile);
+        }
+
+        if let Some(output) = lookup("GUM_WALL_OUTPUT_MODE") {
+            self.artifacts.output = parse_env("GUM_WALL_OUTPUT_MODE", &output)?;
+        }
+        if let Some(bucket) = lookup("GUM_WALL_ARTIFACTS_BUCKET") {
+            self.artifacts.bucket = Some(bucket);
+        }
+        if let Some(prefix) = lookup("GUM_WALL_ARTIFACTS_PREFIX") {
+            self.artifacts.prefix = Some(prefix);
+        }
+
+        if let Some(bucket) = lookup("GUM_WALL_UPLOAD_BUCKET") {
+            self.uploads.bucket = Some(bucket);
+        }
+        if let Some(prefix) = lookup("GUM_WALL_UPLOAD_PREFIX") {
+            self.uploads.prefix = prefix;
+        }
+
+        if let Some(max_bytes) = lookup("GUM_WALL_MAX_IMAGE_BYTES") {
+            self.images.max_bytes = parse_env("GUM_WALL_MAX_IMAGE_BYTES", &max_bytes)?;
+        }
+        if let Some(max_pixels) = lookup("GUM_WALL_MAX_IMAGE_PIXELS") {
+            self.images.max_pixels = parse_env("GUM_WALL_MAX_IMAGE_PIXELS", &max_pixels)?;
+        }
+        if let Some(allow) = lookup("GUM_WALL_ALLOW_FILE_IMAGES") {
+            self.images.allow_file_urls = parse_env("GUM_WALL_ALLOW_FILE_IMAGES", &allow)?;
+        }
+
+        if let Some(currency) = lookup("GUM_WALL_CURRENCY") {
+            self.pricing.currency = currency.trim().to_uppercase();
+        }
+
+        if let Some(percent) = lookup("GUM_WALL_OVERAGE_PERCENT") {
+            self.overage.percent = parse_env("GUM_WALL_OVERAGE_PERCENT", &percent)?;
+        }
+        if let Some(min_spare) = lookup("GUM_WALL_MIN_SPARE") {
+            self.overage.min_spare = parse_env("GUM_WALL_MIN_SPARE", &min_spare)?;
+        }
+
+        if let Some(crew_size) = lookup("GUM_WALL_CREW_SIZE") {
+            self.labor.crew_size = parse_env("GUM_WALL_CREW_SIZE", &crew_size)?;
+        }
+        if let Some(hourly_rate) = lookup("GUM_WALL_HOURLY_RATE") {
+            self.labor.hourly_rate = parse_env("GUM_WALL_HOURLY_RATE", &hourly_rate)?;
+        }
+
+        if let Some(tax_rate) = lookup("GUM_WALL_TAX_RATE") {
+            self.quote.tax_rate = parse_env("GUM_WALL_TAX_RATE", &tax_rate)?;
+        }
+
+        if let Some(enabled) = lookup("GUM_WALL_ADMIN_ENABLED") {
+            self.admin.enabled = parse_env("GUM_WALL_ADMIN_ENABLED", &enabled)?;
+        }
+
+        if let Some(enabled) = lookup("GUM_WALL_ORDERS_ENABLED") {
+            self.orders.enabled = parse_env("GUM_WALL_ORDERS_ENABLED", &enabled)?;
+        }
+
+        Ok(())
+    }
+
+    /// The catalog source to use, inferred from the configured settings when unset
+    ///
+    /// `None` when nothing is configured: the built-in gum types are only used
+    /// when `catalog.source = "builtin"` is set explicitly.
+    pub fn catalog_source(&self) -> Option<CatalogSource> {
+        match self.catalog.source {
+            Some(source) => Some(source),
+            None if self.appsync.is_some() => Some(CatalogSource::Appsync),
+            None if self.catalog.file.is_some() => Some(CatalogSource::File),
+            None => None,
+        }
+    }
+
+    /// Check that the configuration is complete and consistent
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(appsync) = &self.appsync {
+            validate_url("appsync.api_url", &appsync.api_url)?;
+            if appsync.api_key.expose().trim().is_empty() {
+                return Err(ConfigError::Invalid(
+                    "appsync.api_key must not be empty".to_string(),
+                ));
+            }
+        }
+
+        if self.catalog.page_size == 0 || self.catalog.max_pages == 0 {
+            return Err(ConfigError::Invalid(
+                "catalog.page_size and catalog.max_pages must be at least 1".to_string(),
+            ));
+        }
+
+        match self.catalog_source() {
+            None => {
+                return Err(ConfigError::Invalid(
+                    "no gum catalog configured: set [appsync], catalog.file, or \
+                     catalog.source = \"builtin\" to use the built-in gum types"
+                        .to_string(),
+                ));
+            }
+            Some(CatalogSource::Appsync) if self.appsync.is_none() => {
+                return Err(ConfigError::Invalid(
+                    "catalog source is 'appsync' but no [appsync] settings are configured"
+                        .to_string(),
+                ));
+            }
+            Some(CatalogSource::File) if self.catalog.file.is_none() => {
+                return Err(ConfigError::Invalid(
+                    "catalog source is 'file' but catalog.file is not set".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        validate_url("vision.api_url", &self.vision.api_url)?;
+        if self.vision.model.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "vision.model must not be empty".to_string(),
+            ));
+        }
+        if !(64..=8192).contains(&self.vision.max_image_edge) {
+            return Err(ConfigError::Invalid(
+                "vision.max_image_edge must be between 64 and 8192".to_string(),
+            ));
+        }
+        if self.vision.timeout_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "vision.timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        if self.vision.max_retries > 10 || self.vision.retry_delay_ms > 60_000 {
+            return Err(ConfigError::Invalid(
+                "vision.max_retries must be at most 10 and vision.retry_delay_ms at most 60000"
+                    .to_string(),
+            ));
+        }
+        let bedrock = &self.vision.bedrock;
+        if let Some(endpoint_url) = &bedrock.endpoint_url {
+            validate_url("vision.bedrock.endpoint_url", endpoint_url)?;
+        }
+        if bedrock.region.trim().is_empty() || bedrock.model_id.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "vision.bedrock.region and vision.bedrock.model_id must not be empty".to_string(),
+            ));
+        }
+        self.check_vision_credentials("vision", self.vision.provider_kind())?;
+        if self.moderation.mode != ModerationMode::Off {
+            let moderation = self.moderation.vision_config(&self.vision);
+            self.check_vision_credentials("moderation", moderation.provider_kind())?;
+        }
+
+        if let Some(endpoint_url) = &self.s3.endpoint_url {
+            validate_url("s3.endpoint_url", endpoint_url)?;
+        }
+        if self.s3.region.as_deref().is_some_and(|r| r.trim().is_empty())
+            || self.s3.profile.as_deref().is_some_and(|p| p.trim().is_empty())
+        {
+            return Err(ConfigError::Invalid(
+                "s3.region and s3.profile must not be empty when set".to_string(),
+            ));
+        }
+        if self.s3.access_key_id.is_some() != self.s3.secret_access_key.is_some() {
+            return Err(ConfigError::Invalid(
+                "s3.access_key_id and s3.secret_access_key must be set together".to_string(),
+            ));
+        }
+
+        let artifacts = &self.artifacts;
+        if artifacts.bucket.as_deref().is_some_and(|b| b.trim().is_empty()) {
+            return Err(ConfigError::Invalid(
+                "artifacts.bucket must not be empty when set".to_string(),
+            ));
+        }
+        if !(1..=MAX_URL_EXPIRY_SECS).contains(&artifacts.url_expiry_secs) {
+            return Err(ConfigError::Invalid(format!(
+                "artifacts.url_expiry_secs must be between 1 and {}",
+                MAX_URL_EXPIRY_SECS
+            )));
+        }
+        if !(1..=100).contains(&artifacts.cell_size) {
+            return Err(ConfigError::Invalid(
+                "artifacts.cell_size must be between 1 and 100".to_string(),
+            ));
+        }
+
+        let images = &self.images;
+        if images.max_bytes == 0
+            || images.max_dimension == 0
+            || images.max_pixels == 0
+            || images.download_timeout_secs == 0
+        {
+            return Err(ConfigError::Invalid(
+                "images.max_bytes, max_dimension, max_pixels and download_timeout_secs must be \
+                 at least 1"
+                    .to_string(),
+            ));
+        }
+
+        let uploads = &self.uploads;
+        if uploads.bucket.as_deref().is_some_and(|b| b.trim().is_empty()) {
+            return Err(ConfigError::Invalid(
+                "uploads.bucket must not be empty when set".to_string(),
+            ));
+        }
+        if uploads.prefix.starts_with('/') {
+            return Err(ConfigError::Invalid(
+                "uploads.prefix must not start with '/'".to_string(),
+            ));
+        }
+        if uploads.allowed_content_types.is_empty()
+            || uploads
+                .allowed_content_types
+                .iter()
+                .any(|content_type| !content_type.starts_with("image/"))
+        {
+            return Err(ConfigError::Invalid(
+                "uploads.allowed_content_types must list at least one image/* type".to_string(),
+            ));
+        }
+        if uploads.max_bytes == 0 || uploads.max_bytes > images.max_bytes {
+            return Err(ConfigError::Invalid(
+                "uploads.max_bytes must be between 1 and images.max_bytes".to_string(),
+            ));
+        }
+        if !(1..=MAX_URL_EXPIRY_SECS).contains(&uploads.url_expiry_secs) {
+            return Err(ConfigError::Invalid(format!(
+                "uploads.url_expiry_secs must be between 1 and {}",
+                MAX_URL_EXPIRY_SECS
+            )));
+        }
+
+        validate_currency("pricing.currency", &self.pricing.currency)?;
+        for (currency, rate) in &self.pricing.rates {
+            validate_currency("pricing.rates", currency)?;
+            if *rate <= Decimal::ZERO {
+                return Err(ConfigError::Invalid(format!(
+                    "pricing.rates.{} must be greater than 0",
+                    currency
+                )));
+            }
+        }
+
+        if !(0.0..=100.0).contains(&self.overage.percent) {
+            return Err(ConfigError::Invalid(
+                "overage.percent must be between 0 and 100".to_string(),
+            ));
+        }
+        if self.overage.min_spare > 100_000 {
+            return Err(ConfigError::Invalid(
+                "overage.min_spare must be at most 100000".to_string(),
+            ));
+        }
+
+        // Written so that NaN fails every check
+        let labor = &self.labor;
+        if labor.crew_size == 0
+            || !labor.placement_rate.is_finite()
+            || labor.placement_rate <= 0.0
+            || !(labor.hours_per_day > 0.0 && labor.hours_per_day <= 24.0)
+        {
+            return Err(ConfigError::Invalid(
+                "labor.crew_size, labor.placement_rate and labor.hours_per_day (at most 24) \
+                 must be finite and greater than 0"
+                    .to_string(),
+            ));
+        }
+        if !(labor.prep_minutes_per_m2 >= 0.0 && labor.prep_minutes_per_m2.is_finite())
+            || labor.hourly_rate < Decimal::ZERO
+        {
+            return Err(ConfigError::Invalid(
+                "labor.prep_minutes_per_m2 and labor.hourly_rate must not be negative".to_string(),
+            ));
+        }
+
+        let gum = &self.gum_defaults;
+        if [gum.weight_g, gum.chew_minutes, gum.sugar_g, gum.volume_cm3, gum.calories_per_chew_hour]
+            .iter()
+            .any(|value| !(*value >= 0.0 && value.is_finite()))
+        {
+            return Err(ConfigError::Invalid(
+                "gum_defaults values must be finite and not negative".to_string(),
+            ));
+        }
+        if gum.chew_minutes / 60.0 + 1.0 / labor.placement_rate > labor.hours_per_day {
+            return Err(ConfigError::Invalid(
+                "a single piece takes longer than labor.hours_per_day".to_string(),
+            ));
+        }
+
+        let quote = &self.quote;
+        if quote.tax_rate < Decimal::ZERO || quote.tax_rate > Decimal::ONE {
+            return Err(ConfigError::Invalid(
+                "quote.tax_rate must be a fraction between 0 and 1".to_string(),
+            ));
+        }
+        if quote.shipping_base < Decimal::ZERO
+            || quote.shipping_per_kg < Decimal::ZERO
+            || quote.free_shipping_over.is_some_and(|amount| amount < Decimal::ZERO)
+        {
+            return Err(ConfigError::Invalid(
+                "quote shipping amounts must not be negative".to_string(),
+            ));
+        }
+        for tier in &quote.discount_tiers {
+            if tier.min_subtotal < Decimal::ZERO
+                || tier.percent < Decimal::ZERO
+                || tier.percent > Decimal::ONE_HUNDRED
+            {
+                return Err(ConfigError::Invalid(
+                    "quote.discount_tiers need a non-negative min_subtotal and a percent \
+                     between 0 and 100"
+                        .to_string(),
+                ));
+            }
+        }
+        if quote.validity_days == 0 {
+            return Err(ConfigError::Invalid(
+                "quote.validity_days must be at least 1".to_string(),
+            ));
+        }
+
+        if self.admin.enabled && self.appsync.is_none() {
+            return Err(ConfigError::Invalid(
+                "admin tools are enabled but no [appsync] settings are configured".to_string(),
+            ));
+        }
+        if self.orders.enabled && self.appsync.is_none() {
+            return Err(ConfigError::Invalid(
+                "orders are enabled but no [appsync] settings are configured".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the API key of a remote vision provider is set
+    fn check_vision_credentials(
+        &self,
+        setting: &str,
+        provider: VisionProviderKind,
+    ) -> Result<(), ConfigError> {
+        match provider {
+            VisionProviderKind::Openai if self.vision.api_key.is_none() => {
+                Err(ConfigError::Invalid(format!(
+                    "{} provider is 'openai' but vision.api_key is not set",
+                    setting
+                )))
+            }
+            VisionProviderKind::Bedrock if self.vision.bedrock.api_key.is_none() => {
+                Err(ConfigError::Invalid(format!(
+                    "{} provider is 'bedrock' but vision.bedrock.api_key is not set",
+                    setting
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Look up an environment variable, falling back to its deprecated name
+fn lookup_env<F>(lookup: &F, name: &str) -> Option<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    lookup(name).or_else(|| {
+        let (_, old) = DEPRECATED_ENV_NAMES.iter().find(|(new, _)| *new == name)?;
+        let value = lookup(old)?;
+        tracing::warn!("{} is deprecated; set {} instead", old, name);
+        Some(value)
+    })
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e: T::Err| ConfigError::InvalidEnv {
+            name: name.to_string(),
+            message: e.to_string(),
+        })
+}
+
+fn validate_url(name: &str, url: &str) -> Result<(), ConfigError> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| ConfigError::Invalid(format!("{} is not a valid URL: {}", name, e)))?;
+    if parsed.scheme() != "https" && parsed.scheme() != "http" {
+        return Err(ConfigError::Invalid(format!(
+            "{} must be an http(s) URL",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn validate_currency(name: &str, currency: &str) -> Result<(), ConfigError> {
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ConfigError::Invalid(format!(
+            "{} has invalid currency code '{}' (expected e.g. \"USD\")",
+            name, currency
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    /// Default configuration with the built-in catalog selected
+    fn builtin() -> GumWallConfig {
+        let mut config = GumWallConfig::default();
+        config.catalog.source = Some(CatalogSource::Builtin);
+        config
+    }
+
+    /// Parse TOML, selecting the built-in catalog when no source is configured
+    fn parse(contents: &str) -> GumWallConfig {
+        let mut config = GumWallConfig::from_toml(contents).unwrap();
+        if config.catalog_source().is_none() {
+            config.catalog.source = Some(CatalogSource::Builtin);
+        }
+        config
+    }
+
+    #[test]
+    fn test_catalog_source_is_required() {
+        let mut config = GumWallConfig::default();
+        assert_eq!(config.catalog_source(), None);
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("no gum catalog configured"));
+
+        config.catalog.source = Some(CatalogSource::Builtin);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.catalog_source(), Some(CatalogSource::Builtin));
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = parse(
+            r#"
+[appsync]
+api_url = "https://example.appsync-api.us-west-2.amazonaws.com/graphql"
+api_key = "file-key"
+
+[vision]
+model = "gpt-4o"
+"#,
+        );
+        config
+            .apply_env(env(&[
+                ("GUM_WALL_APPSYNC_API_KEY", "env-key"),
+                ("GUM_WALL_CATALOG_CACHE_TTL_SECS", "30"),
+            ]))
+            .unwrap();
+
+        assert!(config.validate().is_ok());
+        assert_eq!(config.catalog_source(), Some(CatalogSource::Appsync));
+        assert_eq!(config.appsync.as_ref().unwrap().api_key.expose(), "env-key");
+        assert_eq!(config.catalog.cache_ttl_secs, 30);
+        assert_eq!(config.vision.model, "gpt-4o");
+    }
+
+    #[test]
+    fn test_moderation_settings() {
+        let config = builtin();
+        assert_eq!(config.moderation.mode, ModerationMode::Off);
+        assert!(config.moderation.fail_closed);
+
+        let mut config = parse(
+            "[moderation]\nmode = \"flag\"\ncategories = [\"sexual\", \"hate\"]",
+        );
+        config
+            .apply_env(env(&[
+                ("GUM_WALL_MODERATION", "reject"),
+                ("GUM_WALL_MODERATION_PROVIDER", "local"),
+            ]))
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.moderation.mode, ModerationMode::Reject);
+        assert_eq!(config.moderation.categories.len(), 2);
+        assert_eq!(
+            config.moderation.vision_config(&config.vision).provider_kind(),
+            VisionProviderKind::Local
+        );
+
+        // A remote moderation provider needs its API key
+        let config = parse("[moderation]\nmode = \"flag\"\nprovider = \"bedrock\"");
+        assert!(config.validate().is_err());
+        assert!(GumWallConfig::from_toml("[moderation]\nmode = \"block\"").is_err());
+    }
+
+    #[test]
+    fn test_vision_provider_selection() {
+        assert_eq!(
+            builtin().vision.provider_kind(),
+            VisionProviderKind::Local
+        );
+
+        let mut config = builtin();
+        config
+            .apply_env(env(&[
+                ("AWS_BEARER_TOKEN_BEDROCK", "bedrock-key"),
+                ("OPENAI_BASE_URL", "http://localhost:11434/v1/"),
+            ]))
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.vision.provider_kind(), VisionProviderKind::Bedrock);
+        assert_eq!(config.vision.api_url, "http://localhost:11434/v1/chat/completions");
+        assert_eq!(
+            config.vision.bedrock.endpoint(),
+            "https://bedrock-runtime.us-east-1.amazonaws.com"
+        );
+
+        let config = parse("[vision]\nprovider = \"openai\"");
+        assert!(config.validate().is_err());
+
+        let mut config = parse("[vision]\nmax_image_edge = 512");
+        config
+            .apply_env(env(&[
+                ("GUM_WALL_VISION_TIMEOUT_SECS", "5"),
+                ("GUM_WALL_VISION_MAX_RETRIES", "0"),
+            ]))
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.vision.max_image_edge, 512);
+        assert_eq!((config.vision.timeout_secs, config.vision.max_retries), (5, 0));
+        let config = parse("[vision]\ntimeout_secs = 0");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_incomplete_appsync_settings_fail() {
+        let mut config = builtin();
+        assert!(config
+            .apply_env(env(&[("GUM_WALL_APPSYNC_API_URL", "https://example.com/graphql")]))
+            .is_err());
+
+        let config = parse("[catalog]\nsource = \"appsync\"");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_env_value() {
+        let mut config = builtin();
+        let result = config.apply_env(env(&[("GUM_WALL_CATALOG_CACHE_TTL_SECS", "soon")]));
+        assert!(matches!(result, Err(ConfigError::InvalidEnv { .. })));
+    }
+
+    #[test]
+    fn test_deprecated_env_names() {
+        let mut config = builtin();
+        config
+            .apply_env(env(&[
+                ("APPSYNC_API_URL", "https://example.com/graphql"),
+                ("APPSYNC_API_KEY", "old-key"),
+                ("GUM_CATALOG_CACHE_TTL_SECS", "30"),
+                ("GUM_CATALOG_STALE_SECS", "45"),
+                ("GUM_WALL_CATALOG_STALE_SECS", "90"),
+            ]))
+            .unwrap();
+        assert_eq!(config.appsync.as_ref().unwrap().api_key.expose(), "old-key");
+        assert_eq!(config.catalog.cache_ttl_secs, 30);
+        // The new name wins when both are set
+        assert_eq!(config.catalog.stale_secs, 90);
+    }
+
+    #[test]
+    fn test_pricing_rates() {
+        let config = parse(
+            r#"
+[pricing]
+currency = "EUR"
+rates = { USD = "0.92", GBP = 1.17 }
+"#,
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.pricing.rates["USD"], "0.92".parse::<Decimal>().unwrap());
+
+        let config = parse("[pricing]\nrates = { eur = 1.1 }");
+        assert!(config.validate().is_err());
+        let config = parse("[pricing]\nrates = { EUR = 0 }");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_overage_spare_pieces() {
+        let overage = OverageConfig {
+            percent: 5.0,
+            min_spare: 10,
+        };
+        assert_eq!(overage.spare_pieces(1000), 50);
+        assert_eq!(overage.spare_pieces(101), 10);
+        assert_eq!(overage.spare_pieces(0), 0);
+        assert_eq!(overage.with_overrides(Some(2.5), Some(0)).spare_pieces(101), 3);
+        assert_eq!(OverageConfig::default().spare_pieces(1000), 0);
+    }
+
+    #[test]
+    fn test_s3_settings() {
+        let config = parse(
+            r#"
+[s3]
+endpoint_url = "http://localhost:9000"
+region = "us-east-1"
+force_path_style = true
+access_key_id = "minio"
+secret_access_key = "minio-secret"
+"#,
+        );
+        assert!(config.validate().is_ok());
+        assert!(!format!("{:?}", config.s3).contains("minio-secret"));
+
+        let config = parse("[s3]\naccess_key_id = \"minio\"");
+        assert!(config.validate().is_err());
+
+        let mut config = builtin();
+        config
+            .apply_env(env(&[("GUM_WALL_S3_ENDPOINT_URL", "localhost:9000")]))
+            .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_artifacts_settings() {
+        let config = parse(
+            r#"
+[artifacts]
+output = "s3"
+bucket = "gum-wall-designs"
+prefix = "designs/"
+"#,
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.artifacts.output, OutputMode::S3);
+        assert_eq!(config.artifacts.url_expiry_secs, 3600);
+
+        let mut config = builtin();
+        config
+            .apply_env(env(&[("GUM_WALL_OUTPUT_MODE", "S3")]))
+            .unwrap();
+        assert_eq!(config.artifacts.output, OutputMode::S3);
+        assert!(config
+            .apply_env(env(&[("GUM_WALL_OUTPUT_MODE", "email")]))
+            .is_err());
+
+        // Presigned URLs live at most 7 days
+        let config = parse("[artifacts]\nurl_expiry_secs = 604801");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_upload_settings() {
+        let config = parse(
+            r#"
+[uploads]
+bucket = "gum-wall-uploads"
+allowed_content_types = ["image/png"]
+max_bytes = 1048576
+"#,
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.uploads.prefix, "uploads/");
+
+        let config = parse("[uploads]\nmax_bytes = 104857600");
+        assert!(config.validate().is_err());
+
+        let config = parse("[uploads]\nallowed_content_types = [\"text/html\"]");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_labor_settings_must_be_finite() {
+        assert!(builtin().validate().is_ok());
+        for value in [f64::NAN, f64::INFINITY, 0.0, -1.0] {
+            let mut config = builtin();
+            config.labor.placement_rate = value;
+            assert!(config.validate().is_err(), "placement_rate = {}", value);
+
+            let mut config = builtin();
+            config.labor.hours_per_day = value;
+            assert!(config.validate().is_err(), "hours_per_day = {}", value);
+        }
+
+        let mut config = builtin();
+        config.labor.prep_minutes_per_m2 = f64::NAN;
+        assert!(config.validate().is_err());
+        let mut config = builtin();
+        config.gum_defaults.chew_minutes = f64::NAN;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_quote_settings() {
+        let config = parse(
+            r#"
+[quote]
+tax_rate = "0.0825"
+free_shipping_over = "500"
+discount_tiers = [{ min_subtotal = "250", percent = "5" }]
+"#,
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.quote.discount_tiers.len(), 1);
+        assert_eq!(config.quote.validity_days, 30);
+
+        let mut config = builtin();
+        config
+            .apply_env(env(&[("GUM_WALL_TAX_RATE", "8.25")]))
+            .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_admin_requires_appsync() {
+        let mut config = builtin();
+        config
+            .apply_env(env(&[
+                ("GUM_WALL_ADMIN_ENABLED", "true"),
+                ("GUM_WALL_ORDERS_ENABLED", "true"),
+            ]))
+            .unwrap();
+        assert!(config.admin.enabled);
+        assert!(config.orders.enabled);
+        assert!(config.validate().is_err());
+
+        config
+            .apply_env(env(&[
+                ("GUM_WALL_APPSYNC_API_URL", "https://example.com/graphql"),
+                ("GUM_WALL_APPSYNC_API_KEY", "da2-key"),
+            ]))
+            .unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_secrets_are_redacted() {
+        let mut config = builtin();
+        config
+            .apply_env(env(&[
+                ("GUM_WALL_APPSYNC_API_URL", "https://example.com/graphql"),
+                ("GUM_WALL_APPSYNC_API_KEY", "da2-supersecret"),
+                ("OPENAI_API_KEY", "sk-supersecret"),
+            ]))
+            .unwrap();
+
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("supersecret"));
+        assert!(debug.contains("****"));
+    }
+}
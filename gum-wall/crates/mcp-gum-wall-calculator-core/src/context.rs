@@ -0,0 +1,90 @@
+//! Shared application context for tool handlers
+//!
+//! The configuration, gum catalog and network clients are created once when
+//! the server is built and shared by every tool call, so HTTP connections are
+//! pooled and AWS credentials are resolved only once.
+
+use anyhow::Result;
+use aws_sdk_s3::Client as S3Client;
+use crate::catalog::GumCatalog;
+use crate::config::GumWallConfig;
+use crate::image_source;
+use crate::s3;
+use crate::vision::{self, VisionProvider};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+/// State shared by all tool handlers
+pub struct AppContext {
+    pub config: GumWallConfig,
+    pub catalog: Arc<dyn GumCatalog>,
+    /// HTTP client for AppSync and the vision API
+    pub http: reqwest::Client,
+    /// HTTP client for https image URLs; only reaches public hosts
+    pub downloads: reqwest::Client,
+    /// Describes analyzed images
+    pub vision: Arc<dyn VisionProvider>,
+    /// Screens images when moderation uses a different provider than `vision`
+    moderator: Option<Arc<dyn VisionProvider>>,
+    s3: OnceCell<S3Client>,
+}
+
+impl AppContext {
+    pub fn new(config: GumWallConfig, catalog: Arc<dyn GumCatalog>, http: reqwest::Client) -> Self {
+        let vision = vision::provider_from_config(&config.vision, http.clone());
+        let moderator = config.moderation.provider.map(|_| {
+            let moderation = config.moderation.vision_config(&config.vision);
+            vision::provider_from_config(&moderation, http.clone())
+        });
+        AppContext {
+            config,
+            catalog,
+            http,
+            downloads: image_source::download_client(),
+            vision,
+            moderator,
+            s3: OnceCell::new(),
+        }
+    }
+
+    /// Use the given vision provider instead of the configured one
+    pub fn with_vision(mut self, vision: Arc<dyn VisionProvider>) -> Self {
+        self.vision = vision;
+        self
+    }
+
+    /// Screen images with the given provider instead of the configured one
+    pub fn with_moderator(mut self, moderator: Arc<dyn VisionProvider>) -> Self {
+        self.moderator = Some(moderator);
+        self
+    }
+
+    /// The provider that screens images for content moderation
+    pub fn moderator(&self) -> &Arc<dyn VisionProvider> {
+        self.moderator.as_ref().unwrap_or(&self.vision)
+    }
+
+    /// The shared S3 client
+    ///
+    /// Loading the AWS configuration is async, so the client is created on
+    /// first use and reused afterwards.
+    pub async fn s3(&self) -> Result<&S3Client> {
+        self.s3
+            .get_or_try_init(|| s3::create_s3_client(&self.config.s3))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_context;
+
+    #[tokio::test]
+    async fn test_s3_client_is_created_once() {
+        let context = test_context(GumWallConfig::default());
+        let first = context.s3().await.unwrap() as *const S3Client;
+        let second = context.s3().await.unwrap() as *const S3Client;
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,535 @@
+//! Cost calculation for gum wall projects
+
+use anyhow::Result;
+use crate::config::GumWallConfig;
+use crate::error::GumWallError;
+use crate::labor;
+use crate::money::{self, CurrencyConverter};
+use crate::purchase::{self, PackOffer, MAX_PIECES_PER_GUM};
+use crate::quantize;
+use crate::stats::ProjectTotals;
+use crate::types::{
+    CalculateCostInput, CalculateCostOutput, CostBreakdownItem, CostWarning, CostWarningKind,
+    GumPackPrice, GumSubstitute, GumType, ShoppingListItem,
+};
+use rust_decimal::Decimal;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Number of nearest-color substitutes suggested for each problem gum
+const MAX_SUBSTITUTES: usize = 3;
+
+/// Price the requested gum counts against the catalog
+///
+/// Only available gum types are priced. Unavailable (discontinued) and
+/// unknown gum types are reported as warnings, or rejected in strict mode.
+///
+/// Errors carry a [`GumWallError`]: bad requests are `InvalidInput`, and catalog
+/// prices that can't be converted are `Configuration`.
+///
+/// Spare pieces from the overage settings (config defaults, overridable per
+/// request) are added to each count before buying.
+///
+/// Each gum type is bought in the cheapest combination of its packs (per
+/// piece when it has none). Prices are converted to the output currency and
+/// rounded to 4 decimal places per piece or pack; each shopping list line is
+/// rounded to the currency's minor unit, and subtotals and the total are the
+/// exact sums of those lines.
+pub fn calculate_cost(
+    input: &CalculateCostInput,
+    gum_types: &[GumType],
+    config: &GumWallConfig,
+) -> Result<CalculateCostOutput> {
+    let converter = CurrencyConverter::new(&config.pricing, input.currency.as_deref())
+        .map_err(|e| GumWallError::InvalidInput(e.to_string()))?;
+    let overage = config
+        .overage
+        .with_overrides(input.overage_percent, input.min_spare_pieces);
+    let gum_map: HashMap<&str, &GumType> =
+        gum_types.iter().map(|g| (g.gum_id.as_str(), g)).collect();
+
+    let mut purchases: Vec<(CostBreakdownItem, Vec<ShoppingListItem>)> = Vec::new();
+    let mut warnings: Vec<CostWarning> = Vec::new();
+    let mut total_pieces: u32 = 0;
+    let mut total_spare_pieces: u32 = 0;
+    let mut unpriced_pieces: u32 = 0;
+    let mut total_cost = Decimal::ZERO;
+    let mut totals = ProjectTotals::default();
+
+    for (gum_id, count) in &input.gum_counts {
+        let gum_type = gum_map.get(gum_id.as_str()).copied();
+        totals.add(gum_type, *count, &config.gum_defaults);
+
+        match gum_type {
+            Some(gum_type) if gum_type.is_available => {
+                // Catalog prices that can't be converted are a server problem
+                let priced = unit_price(gum_type, &converter).and_then(|price| {
+                    pack_offers(gum_type, &converter).map(|offers| (price, offers))
+                });
+                let (unit_price, offers) = priced.map_err(|e| {
+                    GumWallError::Configuration(format!("Cannot price {}: {}", gum_type.name, e))
+                })?;
+                let spare_pieces = overage.spare_pieces(*count);
+                let purchase_quantity = count.saturating_add(spare_pieces);
+                let purchase =
+                    purchase::cheapest_purchase(purchase_quantity, &offers).map_err(|e| {
+                        GumWallError::InvalidInput(format!(
+                            "Cannot plan packs for {}: {}",
+                            gum_type.name, e
+                        ))
+                    })?;
+
+                let mut subtotal = Decimal::ZERO;
+                let mut lines = Vec::new();
+                for selection in &purchase.selections {
+                    let line_total = money::round_money(
+                        selection.offer.price * Decimal::from(selection.quantity),
+                        converter.currency(),
+                    );
+                    subtotal += line_total;
+                    lines.push(ShoppingListItem {
+                        gum_id: gum_id.clone(),
+                        name: gum_type.name.clone(),
+                        pack_size: selection.offer.pack_size,
+                        quantity: selection.quantity,
+                        pack_price: money::to_f64(selection.offer.price),
+                        subtotal: money::to_f64(line_total),
+                    });
+                }
+
+                purchases.push((
+                    CostBreakdownItem {
+                        gum_id: gum_id.clone(),
+                        name: gum_type.name.clone(),
+                        hex_color: gum_type.hex_color.clone(),
+                        count: *count,
+                        spare_pieces,
+                        purchase_quantity,
+                        unit_price: money::to_f64(unit_price),
+                        subtotal: money::to_f64(subtotal),
+                        pieces_purchased: purchase.pieces,
+                        leftover_pieces: purchase.leftover(purchase_quantity),
+                    },
+                    lines,
+                ));
+                total_pieces = total_pieces.saturating_add(*count);
+                total_spare_pieces = total_spare_pieces.saturating_add(spare_pieces);
+                total_cost += subtotal;
+            }
+            Some(gum_type) => {
+                warnings.push(CostWarning {
+                    gum_id: gum_id.clone(),
+                    kind: CostWarningKind::Unavailable,
+                    count: *count,
+                    message: format!(
+                        "{} is no longer available and was not priced",
+                        gum_type.name
+                    ),
+                    substitutes: suggest_substitutes(gum_type, gum_types, &converter),
+                });
+                unpriced_pieces = unpriced_pieces.saturating_add(*count);
+            }
+            None => {
+                warnings.push(CostWarning {
+                    gum_id: gum_id.clone(),
+                    kind: CostWarningKind::Unknown,
+                    count: *count,
+                    message: format!("'{}' is not in the gum catalog and was not priced", gum_id),
+                    substitutes: Vec::new(),
+                });
+                unpriced_pieces = unpriced_pieces.saturating_add(*count);
+            }
+        }
+    }
+
+    if input.strict && !warnings.is_empty() {
+        let problems: Vec<String> = warnings.iter().map(|w| w.message.clone()).collect();
+        return Err(GumWallError::InvalidInput(format!(
+            "Cannot price all gum types: {}",
+            problems.join("; ")
+        ))
+        .into());
+    }
+
+    // Sort by count (descending)
+    purchases.sort_by_key(|(item, _)| Reverse(item.count));
+    let (breakdown, lines): (Vec<CostBreakdownItem>, Vec<Vec<ShoppingListItem>>) =
+        purchases.into_iter().unzip();
+    let shopping_list: Vec<ShoppingListItem> = lines.into_iter().flatten().collect();
+    let leftover_pieces = breakdown.iter().map(|item| item.leftover_pieces).sum();
+
+    // Every placed piece needs labor, priced or not (spares are not placed).
+    // Labor is best-effort: the gum is priced even if it can't be estimated.
+    let (labor, labor_warning) = match labor::estimate_labor(
+        totals.pieces,
+        totals.chew_hours,
+        input.wall_area_m2,
+        input.crew_size.unwrap_or(config.labor.crew_size),
+        &config.labor,
+        &converter,
+    ) {
+        Ok(labor) => (Some(labor), None),
+        Err(e) => (None, Some(format!("Installation labor not estimated: {}", e))),
+    };
+    warnings.sort_by(|a, b| a.gum_id.cmp(&b.gum_id));
+
+    // Fun stats describe the whole project, priced or not
+    let packs_needed = shopping_list.iter().map(|line| line.quantity).sum();
+    let wall_area_m2 = match &labor {
+        Some(labor) => labor.wall_area_m2,
+        None => labor::wall_area_m2(totals.pieces, input.wall_area_m2),
+    };
+    let fun_stats = totals.fun_stats(&config.gum_defaults, packs_needed, wall_area_m2);
+
+    Ok(CalculateCostOutput {
+        breakdown,
+        total_pieces,
+        total_spare_pieces,
+        unpriced_pieces,
+        total_cost: money::to_f64(total_cost),
+        currency: converter.currency().to_string(),
+        shopping_list,
+        leftover_pieces,
+        warnings,
+        labor,
+        labor_warning,
+        fun_stats,
+        // Only the caller knows where `gum_types` came from
+        fallback_prices: false,
+    })
+}
+
+/// Per-piece price of a gum type in the output currency
+fn unit_price(gum_type: &GumType, converter: &CurrencyConverter) -> Result<Decimal> {
+    let price = money::decimal_from_f64(gum_type.price_per_piece)?;
+    let converted = converter.convert(price, gum_type.currency.as_deref())?;
+    Ok(money::round_unit_price(converted))
+}
+
+/// Packs a gum type can be bought in, priced in the output currency
+///
+/// Gum types without usable packs (none, or only empty or oversized ones) are
+/// bought per piece.
+fn pack_offers(gum_type: &GumType, converter: &CurrencyConverter) -> Result<Vec<PackOffer>> {
+    let packs: Vec<&GumPackPrice> = gum_type
+        .packs
+        .iter()
+        .filter(|pack| (1..=MAX_PIECES_PER_GUM).contains(&pack.pack_size))
+        .collect();
+    if packs.len() < gum_type.packs.len() {
+        tracing::warn!(
+            "Ignoring packs of '{}' with 0 or more than {} pieces",
+            gum_type.gum_id,
+            MAX_PIECES_PER_GUM
+        );
+    }
+    if packs.is_empty() {
+        return Ok(vec![PackOffer {
+            pack_size: 1,
+            price: unit_price(gum_type, converter)?,
+        }]);
+    }
+
+    packs
+        .into_iter()
+        .map(|pack| {
+            let price = money::decimal_from_f64(pack.price)?;
+            let converted = converter.convert(price, gum_type.currency.as_deref())?;
+            Ok(PackOffer {
+                pack_size: pack.pack_size,
+                price: money::round_unit_price(converted),
+            })
+        })
+        .collect()
+}
+
+/// Suggest available gum types closest in color to `gum_type`
+///
+/// Substitutes that cannot be priced in the output currency are skipped.
+fn suggest_substitutes(
+    gum_type: &GumType,
+    gum_types: &[GumType],
+    converter: &CurrencyConverter,
+) -> Vec<GumSubstitute> {
+    let Some(target) = gum_type.to_rgb() else {
+        return Vec::new();
+    };
+
+    quantize::nearest_available_gums(target, gum_types, MAX_SUBSTITUTES)
+        .into_iter()
+        .filter(|(candidate, _)| candidate.gum_id != gum_type.gum_id)
+        .filter_map(|(candidate, distance)| {
+            Some(GumSubstitute {
+                gum_id: candidate.gum_id.clone(),
+                name: candidate.name.clone(),
+                hex_color: candidate.hex_color.clone(),
+                price_per_piece: money::to_f64(unit_price(candidate, converter).ok()?),
+                color_distance: (distance as f64).sqrt(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gum(gum_id: &str, hex_color: &str, price: f64, is_available: bool) -> GumType {
+        GumType {
+            gum_id: gum_id.to_string(),
+            name: gum_id.to_string(),
+            hex_color: hex_color.to_string(),
+            price_per_piece: price,
+            brand: None,
+            flavor: None,
+            is_available,
+            currency: None,
+            packs: Vec::new(),
+            properties: Default::default(),
+        }
+    }
+
+    fn with_packs(mut gum_type: GumType, packs: &[(u32, f64)]) -> GumType {
+        gum_type.packs = packs
+            .iter()
+            .map(|(pack_size, price)| GumPackPrice {
+                pack_size: *pack_size,
+                price: *price,
+            })
+            .collect();
+        gum_type
+    }
+
+    fn catalog() -> Vec<GumType> {
+        vec![
+            gum("red", "#FF0000", 0.05, true),
+            gum("crimson", "#DC143C", 0.08, true),
+            gum("blue", "#0000FF", 0.10, true),
+            gum("old_red", "#F00010", 0.04, false),
+        ]
+    }
+
+    fn input(counts: &[(&str, u32)], strict: bool) -> CalculateCostInput {
+        CalculateCostInput {
+            gum_counts: counts.iter().map(|(id, n)| (id.to_string(), *n)).collect(),
+            strict,
+            currency: None,
+            overage_percent: None,
+            min_spare_pieces: None,
+            crew_size: None,
+            wall_area_m2: None,
+        }
+    }
+
+    fn config() -> GumWallConfig {
+        let mut config = GumWallConfig::default();
+        config.pricing.rates = [("EUR".to_string(), Decimal::new(110, 2))]
+            .into_iter()
+            .collect();
+        config
+    }
+
+    #[test]
+    fn test_prices_available_gums() {
+        let output = calculate_cost(
+            &input(&[("red", 100), ("blue", 10)], false),
+            &catalog(),
+            &config(),
+        )
+        .unwrap();
+        assert_eq!(output.total_pieces, 110);
+        assert_eq!(output.total_cost, 6.0);
+        assert!(output.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_oversized_packs_fall_back_to_pieces() {
+        let gum_types = vec![with_packs(
+            gum("red", "#FF0000", 0.06, true),
+            &[(2_000_000_000, 1.00)],
+        )];
+        let output = calculate_cost(&input(&[("red", 10)], false), &gum_types, &config()).unwrap();
+        assert_eq!(output.breakdown[0].pieces_purchased, 10);
+        assert_eq!(output.total_cost, 0.6);
+    }
+
+    #[test]
+    fn test_buys_cheapest_packs() {
+        let gum_types = vec![
+            with_packs(gum("red", "#FF0000", 0.06, true), &[(5, 0.30), (100, 4.00)]),
+            gum("blue", "#0000FF", 0.10, true),
+        ];
+        let output = calculate_cost(
+            &input(&[("red", 93), ("blue", 3)], false),
+            &gum_types,
+            &config(),
+        )
+        .unwrap();
+
+        let red = &output.breakdown[0];
+        assert_eq!(red.pieces_purchased, 100);
+        assert_eq!(red.leftover_pieces, 7);
+        assert_eq!(red.subtotal, 4.0);
+        assert_eq!(output.total_cost, 4.3);
+        assert_eq!(output.leftover_pieces, 7);
+        assert_eq!(output.fun_stats.packs_needed, 4);
+
+        let red_lines: Vec<_> = output
+            .shopping_list
+            .iter()
+            .filter(|l| l.gum_id == "red")
+            .collect();
+        assert_eq!(red_lines.len(), 1);
+        assert_eq!((red_lines[0].pack_size, red_lines[0].quantity), (100, 1));
+    }
+
+    #[test]
+    fn test_overage_adds_spare_pieces() {
+        let gum_types = vec![with_packs(
+            gum("red", "#FF0000", 0.06, true),
+            &[(100, 4.00)],
+        )];
+        let mut config = config();
+        config.overage.percent = 5.0;
+
+        let output = calculate_cost(&input(&[("red", 190)], false), &gum_types, &config).unwrap();
+        let red = &output.breakdown[0];
+        assert_eq!(red.count, 190);
+        assert_eq!(red.spare_pieces, 10);
+        assert_eq!(red.purchase_quantity, 200);
+        assert_eq!(red.pieces_purchased, 200);
+        assert_eq!(red.leftover_pieces, 0);
+        assert_eq!(output.total_pieces, 190);
+        assert_eq!(output.total_spare_pieces, 10);
+
+        let mut request = input(&[("red", 190)], false);
+        request.min_spare_pieces = Some(20);
+        let output = calculate_cost(&request, &gum_types, &config).unwrap();
+        assert_eq!(output.breakdown[0].purchase_quantity, 210);
+        assert_eq!(output.breakdown[0].pieces_purchased, 300);
+
+        // Huge counts are rejected instead of overflowing
+        let mut request = input(&[("red", u32::MAX)], false);
+        request.min_spare_pieces = Some(100_000);
+        assert!(calculate_cost(&request, &gum_types, &config).is_err());
+    }
+
+    #[test]
+    fn test_includes_labor_estimate() {
+        let mut request = input(&[("red", 1000), ("mystery", 200)], false);
+        request.crew_size = Some(4);
+        let output = calculate_cost(&request, &catalog(), &config()).unwrap();
+
+        let labor = output.labor.as_ref().unwrap();
+        assert_eq!(labor.crew_size, 4);
+        assert_eq!(labor.schedule.last().unwrap().cumulative_pieces, 1200);
+        assert_eq!(labor.days as usize, labor.schedule.len());
+        assert!(labor.labor_cost > 0.0);
+        // Labor is reported separately from the gum cost
+        assert_eq!(output.total_cost, 50.0);
+    }
+
+    #[test]
+    fn test_labor_is_best_effort() {
+        // A crew of one can't place this many pieces within the schedule limit
+        let mut request = input(&[("red", 1_000_000)], false);
+        request.crew_size = Some(1);
+        let output = calculate_cost(&request, &catalog(), &config()).unwrap();
+
+        assert!(output.labor.is_none());
+        assert!(output.total_cost > 0.0);
+        assert!(output.warnings.is_empty());
+        assert!(output.labor_warning.unwrap().contains("not estimated"));
+        assert!(output.fun_stats.wall_load_kg_per_m2 > 0.0);
+    }
+
+    #[test]
+    fn test_subtotals_are_rounded_per_line() {
+        let gum_types = vec![
+            gum("a", "#FF0000", 0.005, true),
+            gum("b", "#00FF00", 0.005, true),
+        ];
+        let output =
+            calculate_cost(&input(&[("a", 1), ("b", 1)], false), &gum_types, &config()).unwrap();
+        assert!(output.breakdown.iter().all(|item| item.subtotal == 0.01));
+        assert_eq!(output.total_cost, 0.02);
+    }
+
+    #[test]
+    fn test_converts_gum_currencies() {
+        let mut euro_red = gum("euro_red", "#FF0000", 0.10, true);
+        euro_red.currency = Some("EUR".to_string());
+        let gum_types = vec![euro_red, gum("blue", "#0000FF", 0.11, true)];
+
+        let output =
+            calculate_cost(&input(&[("euro_red", 10)], false), &gum_types, &config()).unwrap();
+        assert_eq!(output.currency, "USD");
+        assert_eq!(output.breakdown[0].unit_price, 0.11);
+        assert_eq!(output.total_cost, 1.1);
+
+        let mut in_euros = input(&[("blue", 10)], false);
+        in_euros.currency = Some("EUR".to_string());
+        let output = calculate_cost(&in_euros, &gum_types, &config()).unwrap();
+        assert_eq!(output.currency, "EUR");
+        assert_eq!(output.total_cost, 1.0);
+
+        let mut in_pounds = input(&[("blue", 10)], false);
+        in_pounds.currency = Some("GBP".to_string());
+        let error = calculate_cost(&in_pounds, &gum_types, &config()).unwrap_err();
+        let error = GumWallError::or_wrap(error, GumWallError::Internal);
+        assert_eq!(error.code(), "invalid_input");
+
+        // A catalog price in a currency without a rate is a server problem
+        let mut pound_red = gum("pound_red", "#FF0000", 0.10, true);
+        pound_red.currency = Some("GBP".to_string());
+        let error = calculate_cost(&input(&[("pound_red", 10)], false), &[pound_red], &config())
+            .unwrap_err();
+        let error = GumWallError::or_wrap(error, GumWallError::Internal);
+        assert_eq!(error.code(), "configuration_error");
+    }
+
+    #[test]
+    fn test_unavailable_gum_warns_with_substitutes() {
+        let output = calculate_cost(
+            &input(&[("red", 10), ("old_red", 50)], false),
+            &catalog(),
+            &config(),
+        )
+        .unwrap();
+        assert_eq!(output.total_pieces, 10);
+        assert_eq!(output.unpriced_pieces, 50);
+        assert_eq!(output.total_cost, 0.5);
+
+        let warning = &output.warnings[0];
+        assert_eq!(warning.kind, CostWarningKind::Unavailable);
+        assert_eq!(warning.substitutes[0].gum_id, "red");
+        assert!(warning.substitutes.iter().all(|s| s.gum_id != "old_red"));
+    }
+
+    #[test]
+    fn test_unknown_gum_is_not_priced() {
+        let output =
+            calculate_cost(&input(&[("mystery", 20)], false), &catalog(), &config()).unwrap();
+        assert!(output.breakdown.is_empty());
+        assert_eq!(output.total_cost, 0.0);
+        assert_eq!(output.warnings[0].kind, CostWarningKind::Unknown);
+    }
+
+    #[test]
+    fn test_huge_unknown_counts_are_rejected() {
+        let request = input(&[("mystery", u32::MAX), ("enigma", u32::MAX)], false);
+        assert!(validator::Validate::validate(&request).is_err());
+
+        // Totals saturate rather than overflow if validation is skipped
+        let output = calculate_cost(&request, &catalog(), &config()).unwrap();
+        assert_eq!(output.unpriced_pieces, u32::MAX);
+        assert!(validator::Validate::validate(&input(&[("mystery", 1_000_000)], false)).is_ok());
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_problem_gums() {
+        assert!(calculate_cost(&input(&[("old_red", 5)], true), &catalog(), &config()).is_err());
+        assert!(calculate_cost(&input(&[("mystery", 5)], true), &catalog(), &config()).is_err());
+        assert!(calculate_cost(&input(&[("red", 5)], true), &catalog(), &config()).is_ok());
+    }
+}
@@ -0,0 +1,268 @@
+//! Errors returned by the MCP tools
+//!
+//! Every tool failure is a [`GumWallError`]. Each variant has a stable,
+//! machine-readable code and says whether retrying the same call can
+//! succeed; both are sent to the client in the MCP error's `data`, e.g.
+//! `{"code": "image_too_large", "retryable": false}`.
+
+use crate::image_source::{DownloadError, ImageError};
+use crate::s3::S3Error;
+use pmcp::ErrorCode;
+use serde_json::json;
+use thiserror::Error;
+
+/// A tool failure with a stable error code
+#[derive(Debug, Error)]
+pub enum GumWallError {
+    #[error("Invalid configuration: {0}")]
+    Configuration(String),
+    #[error("Validation failed: {0}")]
+    Validation(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Invalid image source: {0}")]
+    InvalidUri(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    ImageNotFound(String),
+    #[error("{0}")]
+    ImageAccessDenied(String),
+    #[error("{0}")]
+    ImageTooLarge(String),
+    #[error("{0}")]
+    UnsupportedImage(String),
+    #[error("Failed to fetch image: {0}")]
+    ImageFetchFailed(String),
+    #[error("Image decoding failed: {0}")]
+    DecodeFailed(String),
+    #[error("Image rejected by content moderation: {0}")]
+    ContentRejected(String),
+    #[error("Vision analysis failed: {0}")]
+    VisionFailed(String),
+    #[error("Gum catalog unavailable: {0}")]
+    CatalogUnavailable(String),
+    #[error("Gum catalog update failed: {0}")]
+    CatalogWriteFailed(String),
+    #[error("Failed to place order: {0}")]
+    OrderFailed(String),
+    #[error("S3 request failed: {0}")]
+    StorageUnavailable(String),
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl GumWallError {
+    /// Keep a `GumWallError` raised inside `error`, or wrap the error with `fallback`
+    pub fn or_wrap(error: anyhow::Error, fallback: fn(String) -> Self) -> Self {
+        match error.downcast::<GumWallError>() {
+            Ok(error) => error,
+            Err(error) => fallback(error.to_string()),
+        }
+    }
+
+    /// Classify an error from parsing the image given in a request
+    pub fn from_image_source(error: anyhow::Error) -> Self {
+        match error.downcast_ref::<ImageError>() {
+            Some(ImageError::TooLarge { .. }) => GumWallError::ImageTooLarge(error.to_string()),
+            _ => GumWallError::InvalidUri(error.to_string()),
+        }
+    }
+
+    /// Classify an error from fetching and checking an image
+    ///
+    /// Limits, missing or forbidden images, non-image responses and bad image
+    /// data are the caller's problem; anything else (network errors, server
+    /// errors, S3 failures) is treated as transient.
+    pub fn from_image_load(error: anyhow::Error) -> Self {
+        let message = error.to_string();
+        if let Some(image_error) = error.downcast_ref::<ImageError>() {
+            return match image_error {
+                ImageError::TooLarge { .. } | ImageError::TooManyPixels { .. } => {
+                    GumWallError::ImageTooLarge(message)
+                }
+                ImageError::UnsupportedFormat => GumWallError::UnsupportedImage(message),
+                ImageError::Invalid(_) => GumWallError::DecodeFailed(message),
+            };
+        }
+        if let Some(download_error) = error.downcast_ref::<DownloadError>() {
+            return match download_error {
+                DownloadError::Status { status, .. } => match status.as_u16() {
+                    404 | 410 => GumWallError::ImageNotFound(message),
+                    401 | 403 => GumWallError::ImageAccessDenied(message),
+                    // Timeouts and rate limits may pass
+                    408 | 429 => GumWallError::ImageFetchFailed(message),
+                    400..=499 => GumWallError::InvalidUri(message),
+                    _ => GumWallError::ImageFetchFailed(message),
+                },
+                DownloadError::NotAnImage { .. } => GumWallError::UnsupportedImage(message),
+                DownloadError::Refused { .. } => GumWallError::InvalidUri(message),
+            };
+        }
+        match error.downcast_ref::<S3Error>() {
+            Some(S3Error::InvalidUri(_)) => GumWallError::InvalidUri(message),
+            Some(S3Error::NotFound { .. }) => GumWallError::ImageNotFound(message),
+            Some(S3Error::AccessDenied { .. }) => GumWallError::ImageAccessDenied(message),
+            Some(S3Error::TooLarge { .. }) => GumWallError::ImageTooLarge(message),
+            _ => GumWallError::ImageFetchFailed(message),
+        }
+    }
+
+    /// Stable machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            GumWallError::Configuration(_) => "configuration_error",
+            GumWallError::Validation(_) => "validation_failed",
+            GumWallError::InvalidInput(_) => "invalid_input",
+            GumWallError::NotFound(_) => "not_found",
+            GumWallError::Conflict(_) => "conflict",
+            GumWallError::InvalidUri(_) => "invalid_uri",
+            GumWallError::ImageNotFound(_) => "image_not_found",
+            GumWallError::ImageAccessDenied(_) => "image_access_denied",
+            GumWallError::ImageTooLarge(_) => "image_too_large",
+            GumWallError::UnsupportedImage(_) => "unsupported_image",
+            GumWallError::ImageFetchFailed(_) => "image_fetch_failed",
+            GumWallError::DecodeFailed(_) => "decode_failed",
+            GumWallError::ContentRejected(_) => "content_rejected",
+            GumWallError::VisionFailed(_) => "vision_failed",
+            GumWallError::CatalogUnavailable(_) => "catalog_unavailable",
+            GumWallError::CatalogWriteFailed(_) => "catalog_write_failed",
+            GumWallError::OrderFailed(_) => "order_failed",
+            GumWallError::StorageUnavailable(_) => "storage_unavailable",
+            GumWallError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Whether the same call may succeed if retried later
+    ///
+    /// Orders are retryable because they are deduplicated by their
+    /// idempotency key; catalog writes are not.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            GumWallError::ImageFetchFailed(_)
+                | GumWallError::VisionFailed(_)
+                | GumWallError::CatalogUnavailable(_)
+                | GumWallError::OrderFailed(_)
+                | GumWallError::StorageUnavailable(_)
+        )
+    }
+
+    /// Whether the request itself is at fault, rather than the server or a backend
+    pub fn is_client_error(&self) -> bool {
+        matches!(
+            self,
+            GumWallError::Validation(_)
+                | GumWallError::InvalidInput(_)
+                | GumWallError::NotFound(_)
+                | GumWallError::Conflict(_)
+                | GumWallError::InvalidUri(_)
+                | GumWallError::ImageNotFound(_)
+                | GumWallError::ImageAccessDenied(_)
+                | GumWallError::ImageTooLarge(_)
+                | GumWallError::UnsupportedImage(_)
+                | GumWallError::DecodeFailed(_)
+                | GumWallError::ContentRejected(_)
+        )
+    }
+}
+
+impl From<GumWallError> for pmcp::Error {
+    fn from(error: GumWallError) -> Self {
+        let code = if error.is_client_error() {
+            ErrorCode::INVALID_PARAMS
+        } else {
+            ErrorCode::INTERNAL_ERROR
+        };
+        let data = json!({
+            "code": error.code(),
+            "retryable": error.is_retryable(),
+        });
+        pmcp::Error::protocol_with_data(code, error.to_string(), data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_image_load_errors_are_classified() {
+        let too_large = anyhow::Error::new(ImageError::TooLarge {
+            size: 20,
+            max_bytes: 10,
+        });
+        assert_eq!(GumWallError::from_image_load(too_large).code(), "image_too_large");
+
+        let missing = anyhow::Error::new(S3Error::NotFound {
+            bucket: "walls".to_string(),
+            key: "missing.png".to_string(),
+        });
+        assert_eq!(GumWallError::from_image_load(missing).code(), "image_not_found");
+
+        let corrupt = anyhow::Error::new(ImageError::Invalid("truncated".to_string()));
+        let error = GumWallError::from_image_load(corrupt);
+        assert_eq!(error.code(), "decode_failed");
+        assert!(!error.is_retryable());
+
+        let forbidden = anyhow::Error::new(DownloadError::Status {
+            url: "https://example.com/a.png".to_string(),
+            status: reqwest::StatusCode::FORBIDDEN,
+        });
+        let error = GumWallError::from_image_load(forbidden);
+        assert_eq!(error.code(), "image_access_denied");
+        assert!(!error.is_retryable());
+
+        let html = anyhow::Error::new(DownloadError::NotAnImage {
+            url: "https://example.com/".to_string(),
+            content_type: "text/html".to_string(),
+        });
+        assert_eq!(GumWallError::from_image_load(html).code(), "unsupported_image");
+
+        let unavailable = anyhow::Error::new(DownloadError::Status {
+            url: "https://example.com/a.png".to_string(),
+            status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+        });
+        assert!(GumWallError::from_image_load(unavailable).is_retryable());
+
+        let network = anyhow!("connection reset").context("Failed to download");
+        let error = GumWallError::from_image_load(network);
+        assert_eq!(error.code(), "image_fetch_failed");
+        assert!(error.is_retryable());
+
+        let source = GumWallError::from_image_source(anyhow!("Unsupported image URI"));
+        assert_eq!(source.code(), "invalid_uri");
+    }
+
+    #[test]
+    fn test_mcp_error_carries_code() {
+        let error: pmcp::Error = GumWallError::CatalogUnavailable("timed out".to_string()).into();
+        match error {
+            pmcp::Error::Protocol {
+                code,
+                message,
+                data,
+            } => {
+                assert_eq!(code, ErrorCode::INTERNAL_ERROR);
+                assert_eq!(message, "Gum catalog unavailable: timed out");
+                assert_eq!(
+                    data,
+                    Some(json!({"code": "catalog_unavailable", "retryable": true}))
+                );
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let error: pmcp::Error = GumWallError::ImageTooLarge("too big".to_string()).into();
+        assert!(matches!(
+            error,
+            pmcp::Error::Protocol {
+                code: ErrorCode::INVALID_PARAMS,
+                ..
+            }
+        ));
+    }
+}
@@ -1,12 +1,12 @@
 //! AppSync GraphQL client for fetching gum price list
 
 use anyhow::{anyhow, Context, Result};
-use crate::types::{GraphQLResponse, GumType, GumTypeRecord, ListGumTypesData};
+use crate::types::{GraphQLResponse, GumProperties, GumType, GumTypeRecord, ListGumTypesData};
 
-/// GraphQL query to list all gum types
+/// GraphQL query to list one page of gum types
 const LIST_GUM_TYPES_QUERY: &str = r#"
-query ListGumTypes {
-    listGumTypes {
+query ListGumTypes($limit: Int, $nextToken: String) {
+    listGumTypes(limit: $limit, nextToken: $nextToken) {
         items {
             id
             gumId
@@ -16,32 +16,44 @@ query ListGumTypes {
             brand
             flavor
             isAvailable
+            currency
+            products {
+                items {
+                    packSize
+                    packPrice
+                }
+            }
+            weightGrams
+            chewMinutes
+            sugarGrams
+            volumeCm3
         }
+        nextToken
     }
 }
 "#;
 
-// Hardcoded AppSync configuration for Lambda deployment simplicity
-const HARDCODED_APPSYNC_API_URL: &str = "https://ckdu2l4upbhn5efpirlmursvvi.appsync-api.us-west-2.amazonaws.com/graphql";
-const HARDCODED_APPSYNC_API_KEY: &str = "da2-jgugqfhsdrdytmngzf73ni2r4q";
+/// Number of gum types requested per `listGumTypes` page
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
 
-/// Fetch all gum types from AppSync GraphQL API
-pub async fn fetch_gum_types() -> Result<Vec<GumType>> {
-    // Use environment variables if set, otherwise use hardcoded values
-    let api_url = std::env::var("APPSYNC_API_URL")
-        .unwrap_or_else(|_| HARDCODED_APPSYNC_API_URL.to_string());
-
-    let api_key = std::env::var("APPSYNC_API_KEY")
-        .unwrap_or_else(|_| HARDCODED_APPSYNC_API_KEY.to_string());
-
-    let client = reqwest::Client::new();
+/// Safety cap on the number of pages fetched for one listing
+pub const DEFAULT_MAX_PAGES: u32 = 50;
 
+/// Send a GraphQL request to AppSync and return the `data` payload
+pub(crate) async fn execute<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    api_url: &str,
+    api_key: &str,
+    query: &str,
+    variables: serde_json::Value,
+) -> Result<T> {
     let response = client
-        .post(&api_url)
-        .header("x-api-key", &api_key)
+        .post(api_url)
+        .header("x-api-key", api_key)
         .header("Content-Type", "application/json")
         .json(&serde_json::json!({
-            "query": LIST_GUM_TYPES_QUERY
+            "query": query,
+            "variables": variables
         }))
         .send()
         .await
@@ -53,7 +65,7 @@ pub async fn fetch_gum_types() -> Result<Vec<GumType>> {
         return Err(anyhow!("AppSync API error ({}): {}", status, error_body));
     }
 
-    let graphql_response: GraphQLResponse<ListGumTypesData> = response
+    let graphql_response: GraphQLResponse<T> = response
         .json()
         .await
         .context("Failed to parse GraphQL response")?;
@@ -63,25 +75,57 @@ pub async fn fetch_gum_types() -> Result<Vec<GumType>> {
         return Err(anyhow!("GraphQL errors: {}", error_messages.join(", ")));
     }
 
-    let data = graphql_response
+    graphql_response
         .data
-        .ok_or_else(|| anyhow!("No data in GraphQL response"))?;
+        .ok_or_else(|| anyhow!("No data in GraphQL response"))
+}
 
-    let gum_types: Vec<GumType> = data
-        .list_gum_types
-        .items
-        .into_iter()
-        .filter(|g| g.is_available)
-        .map(GumType::from)
-        .collect();
+/// Fetch all gum types from AppSync GraphQL API
+///
+/// Follows `nextToken` until the listing is complete. Fails rather than
+/// returning a truncated catalog if more than `max_pages` pages are needed.
+pub async fn fetch_gum_types(
+    client: &reqwest::Client,
+    api_url: &str,
+    api_key: &str,
+    page_size: u32,
+    max_pages: u32,
+) -> Result<Vec<GumType>> {
+    let mut gum_types: Vec<GumType> = Vec::new();
+    let mut next_token: Option<String> = None;
 
-    Ok(gum_types)
-}
+    for page in 1..=max_pages {
+        let data: ListGumTypesData = execute(
+            client,
+            api_url,
+            api_key,
+            LIST_GUM_TYPES_QUERY,
+            serde_json::json!({
+                "limit": page_size,
+                "nextToken": next_token
+            }),
+        )
+        .await?;
+
+        let result = data.list_gum_types;
+        tracing::debug!(page = page, items = result.items.len(), "Fetched listGumTypes page");
+
+        gum_types.extend(result.items.into_iter().map(GumType::from));
+
+        match result.next_token {
+            Some(token) if next_token.as_deref() == Some(token.as_str()) => {
+                return Err(anyhow!("AppSync returned the same nextToken twice"));
+            }
+            Some(token) => next_token = Some(token),
+            None => return Ok(gum_types),
+        }
+    }
 
-/// Get a map of gum_id to GumType for quick lookup
-pub async fn fetch_gum_types_map() -> Result<std::collections::HashMap<String, GumType>> {
-    let gum_types = fetch_gum_types().await?;
-    Ok(gum_types.into_iter().map(|g| (g.gum_id.clone(), g)).collect())
+    Err(anyhow!(
+        "Gum catalog has more than {} pages of {} items; refusing to return a truncated list",
+        max_pages,
+        page_size
+    ))
 }
 
 /// Fallback gum types when AppSync is not available (for development/testing)
@@ -95,6 +139,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Dubble Bubble".to_string()),
             flavor: Some("Original".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
         },
         GumType {
             gum_id: "hubba_bubba_strawberry".to_string(),
@@ -104,6 +151,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Hubba Bubba".to_string()),
             flavor: Some("Strawberry".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
         },
         GumType {
             gum_id: "bazooka_classic".to_string(),
@@ -113,6 +163,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Bazooka".to_string()),
             flavor: Some("Classic".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
         },
         GumType {
             gum_id: "juicy_fruit_yellow".to_string(),
@@ -122,6 +175,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Wrigley's".to_string()),
             flavor: Some("Juicy Fruit".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
         },
         GumType {
             gum_id: "big_league_green".to_string(),
@@ -131,6 +187,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Big League Chew".to_string()),
             flavor: Some("Green Apple".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
         },
         GumType {
             gum_id: "trident_spearmint".to_string(),
@@ -140,6 +199,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Trident".to_string()),
             flavor: Some("Spearmint".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::sugar_free(),
         },
         GumType {
             gum_id: "orbit_blue".to_string(),
@@ -149,6 +211,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Orbit".to_string()),
             flavor: Some("Bubblemint".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::sugar_free(),
         },
         GumType {
             gum_id: "extra_white".to_string(),
@@ -158,6 +223,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Extra".to_string()),
             flavor: Some("Polar Ice".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::sugar_free(),
         },
         GumType {
             gum_id: "big_red".to_string(),
@@ -167,6 +235,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Wrigley's".to_string()),
             flavor: Some("Cinnamon".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
         },
         GumType {
             gum_id: "eclipse_mint".to_string(),
@@ -176,6 +247,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Eclipse".to_string()),
             flavor: Some("Winterfrost".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::sugar_free(),
         },
         GumType {
             gum_id: "grape_hubba".to_string(),
@@ -185,6 +259,9 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Hubba Bubba".to_string()),
             flavor: Some("Grape".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::default(),
         },
         GumType {
             gum_id: "orange_trident".to_string(),
@@ -194,28 +271,90 @@ pub fn get_fallback_gum_types() -> Vec<GumType> {
             brand: Some("Trident".to_string()),
             flavor: Some("Orange".to_string()),
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: GumProperties::sugar_free(),
         },
     ]
 }
 
-/// Try to fetch gum types from AppSync, fall back to hardcoded list if unavailable
-pub async fn fetch_gum_types_with_fallback() -> Vec<GumType> {
-    match fetch_gum_types().await {
-        Ok(types) if !types.is_empty() => types,
-        Ok(_) => {
-            tracing::warn!("No gum types found in AppSync, using fallback");
-            get_fallback_gum_types()
-        }
-        Err(e) => {
-            tracing::warn!("Failed to fetch gum types from AppSync: {}. Using fallback.", e);
-            get_fallback_gum_types()
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::MockServer;
+
+    /// A listGumTypes page with one item per id and an optional next token
+    fn page(ids: &[&str], next_token: Option<&str>) -> String {
+        let items: Vec<serde_json::Value> = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "gumId": id,
+                    "name": id,
+                    "hexColor": "#FF0000",
+                    "pricePerPiece": 0.05,
+                    "brand": null,
+                    "flavor": null,
+                    "isAvailable": true
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "data": {"listGumTypes": {"items": items, "nextToken": next_token}}
+        })
+        .to_string()
+    }
+
+    fn request_token(body: &str) -> Option<String> {
+        let body: serde_json::Value = serde_json::from_str(body).ok()?;
+        body["variables"]["nextToken"].as_str().map(String::from)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gum_types_follows_pages() {
+        let server = MockServer::start(|request| {
+            let body = match request_token(&request.body).as_deref() {
+                None => page(&["a", "b"], Some("page-2")),
+                Some("page-2") => page(&["c", "d"], Some("page-3")),
+                Some("page-3") => page(&["e"], None),
+                Some(_) => page(&[], None),
+            };
+            (200, body)
+        })
+        .await;
+
+        let client = reqwest::Client::new();
+        let types = fetch_gum_types(&client, &server.url, "key", 2, DEFAULT_MAX_PAGES)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = types.iter().map(|g| g.gum_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(server.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gum_types_page_cap() {
+        let server = MockServer::start(|request| {
+            let next = format!("{}x", request_token(&request.body).unwrap_or_default());
+            (200, page(&["a"], Some(&next)))
+        })
+        .await;
+
+        let result = fetch_gum_types(&reqwest::Client::new(), &server.url, "key", 1, 3).await;
+        assert!(result.is_err());
+        assert_eq!(server.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gum_types_repeated_token() {
+        let server = MockServer::start(|_request| (200, page(&["a"], Some("stuck")))).await;
+
+        let client = reqwest::Client::new();
+        let result = fetch_gum_types(&client, &server.url, "key", 1, DEFAULT_MAX_PAGES).await;
+        assert!(result.is_err());
+        assert_eq!(server.hits(), 2);
+    }
 
     #[test]
     fn test_fallback_gum_types() {
@@ -241,6 +380,12 @@ mod tests {
             brand: Some("Test Brand".to_string()),
             flavor: None,
             is_available: true,
+            currency: None,
+            products: None,
+            weight_g: None,
+            chew_minutes: None,
+            sugar_g: None,
+            volume_cm3: None,
         };
 
         let gum_type: GumType = record.into();
@@ -249,4 +394,27 @@ mod tests {
         assert!(gum_type.brand.is_some());
         assert!(gum_type.flavor.is_none());
     }
+
+    #[test]
+    fn test_packs_from_linked_products() {
+        let record: GumTypeRecord = serde_json::from_value(serde_json::json!({
+            "id": "123",
+            "gumId": "red",
+            "name": "Red",
+            "hexColor": "#FF0000",
+            "pricePerPiece": 0.06,
+            "products": {"items": [
+                {"packSize": 5, "packPrice": 0.30},
+                {"packSize": 100, "packPrice": 4.00},
+                {"packSize": 12, "packPrice": null}
+            ]}
+        }))
+        .unwrap();
+
+        let gum_type: GumType = record.into();
+        // Products without a pack price are not sold for gum walls
+        let sizes: Vec<u32> = gum_type.packs.iter().map(|p| p.pack_size).collect();
+        assert_eq!(sizes, vec![5, 100]);
+        assert_eq!(gum_type.packs[1].price, 4.00);
+    }
 }
@@ -0,0 +1,632 @@
+//! Where analyzed images come from
+//!
+//! Images can be given as S3 or https URIs, `data:` URLs, inline base64 data
+//! or MCP resources, and (when enabled) as local `file://` paths. Every
+//! source is limited to `images.max_bytes`. https images are only downloaded
+//! from public hosts, so image URLs can't reach services on the server's
+//! network.
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use crate::config::ImagesConfig;
+use crate::context::AppContext;
+use crate::s3;
+use crate::types::AnalyzeImageInput;
+use image::ImageFormat;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::Url;
+use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Image formats accepted for analysis
+const SUPPORTED_FORMATS: [ImageFormat; 5] = [
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+];
+
+/// Most redirects followed when downloading an image
+const MAX_REDIRECTS: usize = 5;
+
+/// Errors raised while checking image data
+#[derive(Debug, Error)]
+pub enum ImageError {
+    #[error("Image is too large ({size} bytes, maximum {max_bytes})")]
+    TooLarge { size: u64, max_bytes: u64 },
+    #[error("Unsupported image format; expected JPEG, PNG, GIF, WebP or BMP")]
+    UnsupportedFormat,
+    #[error(
+        "Image is {width}x{height} pixels; the limit is {max_dimension} pixels per side \
+         and {max_pixels} pixels in total"
+    )]
+    TooManyPixels {
+        width: u32,
+        height: u32,
+        max_dimension: u32,
+        max_pixels: u64,
+    },
+    #[error("Invalid image: {0}")]
+    Invalid(String),
+}
+
+/// Errors raised while downloading an https image
+#[derive(Debug, Error)]
+pub enum DownloadError {
+    #[error("Downloading {url} failed ({status})")]
+    Status {
+        url: String,
+        status: reqwest::StatusCode,
+    },
+    #[error("{url} is not an image (content type '{content_type}')")]
+    NotAnImage { url: String, content_type: String },
+    #[error("Downloading {url} was refused: {reason}")]
+    Refused { url: String, reason: String },
+}
+
+/// An image to analyze, before it is loaded
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSource {
+    /// An S3 object
+    S3 { bucket: String, key: String },
+    /// An https URL
+    Https(String),
+    /// A local file (only when `images.allow_file_urls` is set)
+    File(PathBuf),
+    /// Image data given in the request (base64, `data:` URL or resource blob)
+    Inline {
+        data: Vec<u8>,
+        mime_type: Option<String>,
+    },
+}
+
+/// Image bytes and their MIME type
+#[derive(Debug, Clone)]
+pub struct LoadedImage {
+    pub bytes: Vec<u8>,
+    pub mime_type: String,
+}
+
+impl ImageSource {
+    /// The image source given in an analyze request
+    ///
+    /// Exactly one of `image_uri`, `image_base64` and `image_resource` must be set.
+    pub fn from_input(input: &AnalyzeImageInput, config: &ImagesConfig) -> Result<Self> {
+        match (&input.image_uri, &input.image_base64, &input.image_resource) {
+            (Some(uri), None, None) => Self::parse_uri(uri, config),
+            (None, Some(data), None) => Ok(ImageSource::Inline {
+                data: decode_base64(data, config.max_bytes)?,
+                mime_type: input.image_mime_type.clone(),
+            }),
+            (None, None, Some(resource)) => match &resource.blob {
+                Some(blob) => Ok(ImageSource::Inline {
+                    data: decode_base64(blob, config.max_bytes)?,
+                    mime_type: resource.mime_type.clone(),
+                }),
+                None => Self::parse_uri(&resource.uri, config),
+            },
+            (None, None, None) => Err(anyhow!(
+                "No image given: set image_uri, image_base64 or image_resource"
+            )),
+            _ => Err(anyhow!(
+                "Set only one of image_uri, image_base64 and image_resource"
+            )),
+        }
+    }
+
+    /// Parse an `s3://`, `https://`, `data:` or `file://` image URI
+    pub fn parse_uri(uri: &str, config: &ImagesConfig) -> Result<Self> {
+        let uri = uri.trim();
+        if uri.starts_with("s3://") {
+            let (bucket, key) = s3::parse_s3_uri(uri)?;
+            Ok(ImageSource::S3 { bucket, key })
+        } else if uri.starts_with("https://") {
+            let url = Url::parse(uri).context("Invalid https image URL")?;
+            check_public_url(&url)?;
+            Ok(ImageSource::Https(uri.to_string()))
+        } else if let Some(data_url) = uri.strip_prefix("data:") {
+            parse_data_url(data_url, config.max_bytes)
+        } else if let Some(path) = uri.strip_prefix("file://") {
+            if !config.allow_file_urls {
+                return Err(anyhow!(
+                    "file:// image URIs are disabled (set images.allow_file_urls to enable them)"
+                ));
+            }
+            let path = PathBuf::from(path);
+            if !path.is_absolute() {
+                return Err(anyhow!("file:// image URIs need an absolute path"));
+            }
+            Ok(ImageSource::File(path))
+        } else if uri.starts_with("http://") {
+            Err(anyhow!("Plain http image URLs are not allowed; use https"))
+        } else {
+            Err(anyhow!(
+                "Unsupported image URI: expected s3://, https://, data: or file://"
+            ))
+        }
+    }
+
+    /// Short description for logs (never includes inline data)
+    pub fn describe(&self) -> String {
+        match self {
+            ImageSource::S3 { bucket, key } => format!("s3://{}/{}", bucket, key),
+            ImageSource::Https(url) => url.clone(),
+            ImageSource::File(path) => format!("file://{}", path.display()),
+            ImageSource::Inline { data, .. } => format!("inline image ({} bytes)", data.len()),
+        }
+    }
+}
+
+/// Load the image bytes from `source`
+///
+/// The MIME type is always taken from the image data itself; a different
+/// declared type is only logged.
+pub async fn load_image(source: ImageSource, context: &AppContext) -> Result<LoadedImage> {
+    let config = &context.config.images;
+    let (bytes, declared) = match source {
+        ImageSource::S3 { bucket, key } => {
+            let client = context.s3().await?;
+            let (bytes, content_type) =
+                s3::fetch_image_from_s3(client, &bucket, &key, config.max_bytes).await?;
+            let declared = s3::get_mime_type(&key, &content_type);
+            (bytes, Some(declared))
+        }
+        ImageSource::Https(url) => fetch_url(&context.downloads, &url, config).await?,
+        ImageSource::File(path) => {
+            let metadata = tokio::fs::metadata(&path)
+                .await
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            check_size(metadata.len(), config.max_bytes)?;
+            let bytes = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            (bytes, None)
+        }
+        ImageSource::Inline { data, mime_type } => (data, mime_type),
+    };
+
+    let mime_type = check_image(&bytes, config)?;
+    if let Some(declared) = declared.filter(|declared| *declared != mime_type) {
+        tracing::warn!(
+            "Image declared as '{}' but its data is '{}'; using '{}'",
+            declared,
+            mime_type,
+            mime_type
+        );
+    }
+    Ok(LoadedImage { bytes, mime_type })
+}
+
+/// Identify the image format from its leading bytes and check its pixel dimensions
+///
+/// Only the image header is read, so oversized images (decompression bombs)
+/// are rejected before any pixels are allocated. Returns the MIME type.
+pub fn check_image(bytes: &[u8], config: &ImagesConfig) -> Result<String, ImageError> {
+    let format = image::guess_format(bytes).map_err(|_| ImageError::UnsupportedFormat)?;
+    if !SUPPORTED_FORMATS.contains(&format) {
+        return Err(ImageError::UnsupportedFormat);
+    }
+
+    let (width, height) = image::ImageReader::with_format(Cursor::new(bytes), format)
+        .into_dimensions()
+        .map_err(|e| ImageError::Invalid(e.to_string()))?;
+    if width.max(height) > config.max_dimension || width as u64 * height as u64 > config.max_pixels
+    {
+        return Err(ImageError::TooManyPixels {
+            width,
+            height,
+            max_dimension: config.max_dimension,
+            max_pixels: config.max_pixels,
+        });
+    }
+
+    Ok(format.to_mime_type().to_string())
+}
+
+/// HTTP client for downloading https images
+///
+/// Host names must resolve to public addresses, and redirects are only
+/// followed to https URLs that pass the same checks, so neither the URL nor a
+/// redirect can point the server at its own network (e.g. the cloud metadata
+/// endpoint).
+pub fn download_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(Policy::custom(follow_redirect))
+        .build()
+        .expect("Failed to build the image download client")
+}
+
+fn follow_redirect(attempt: Attempt) -> reqwest::redirect::Action {
+    if attempt.previous().len() >= MAX_REDIRECTS {
+        return attempt.error(format!("more than {} redirects", MAX_REDIRECTS));
+    }
+    if attempt.url().scheme() != "https" {
+        return attempt.error("image URLs may only redirect to https");
+    }
+    match check_public_url(attempt.url()) {
+        Ok(()) => attempt.follow(),
+        Err(e) => attempt.error(e.to_string()),
+    }
+}
+
+/// Resolves host names to their public addresses only
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let public: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if public.is_empty() {
+                return Err(format!("{} does not resolve to a public address", host).into());
+            }
+            Ok(Box::new(public.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Reject URLs whose host is a non-public IP address
+///
+/// Host names are checked when they are resolved, by [`download_client`].
+fn check_public_url(url: &Url) -> Result<()> {
+    let host = url.host_str().ok_or_else(|| anyhow!("Image URL has no host"))?;
+    // IPv6 hosts are bracketed in URLs
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) if !is_public_ip(ip) => Err(anyhow!(
+            "Image URLs may not point at private address {}",
+            ip
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether an address is reachable on the public internet
+///
+/// Loopback, private, link-local, shared (CGNAT), documentation, multicast
+/// and unspecified addresses are not.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || ip.is_unspecified()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Download an image, enforcing the size limit while streaming
+async fn fetch_url(
+    client: &reqwest::Client,
+    url: &str,
+    config: &ImagesConfig,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let sent = client
+        .get(url)
+        .timeout(Duration::from_secs(config.download_timeout_secs))
+        .send()
+        .await;
+    let mut response = match sent {
+        Ok(response) => response,
+        Err(e) if e.is_redirect() => {
+            return Err(DownloadError::Refused {
+                url: url.to_string(),
+                reason: e.to_string(),
+            }
+            .into())
+        }
+        Err(e) => return Err(anyhow::Error::new(e).context(format!("Failed to download {}", url))),
+    };
+
+    if !response.status().is_success() {
+        return Err(DownloadError::Status {
+            url: url.to_string(),
+            status: response.status(),
+        }
+        .into());
+    }
+    if let Some(length) = response.content_length() {
+        check_size(length, config.max_bytes)?;
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let mime_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    if !mime_type.starts_with("image/") {
+        return Err(DownloadError::NotAnImage {
+            url: url.to_string(),
+            content_type: content_type.to_string(),
+        }
+        .into());
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        check_size((bytes.len() + chunk.len()) as u64, config.max_bytes)?;
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok((bytes, Some(mime_type)))
+}
+
+/// Parse the part of a `data:` URL after the scheme, e.g. `image/png;base64,iVBO...`
+fn parse_data_url(data_url: &str, max_bytes: u64) -> Result<ImageSource> {
+    let (meta, data) = data_url
+        .split_once(',')
+        .ok_or_else(|| anyhow!("Invalid data: URL: missing ','"))?;
+    let mime_type = meta
+        .strip_suffix(";base64")
+        .ok_or_else(|| anyhow!("data: URLs must be base64-encoded"))?;
+    if !mime_type.starts_with("image/") {
+        return Err(anyhow!("data: URL is not an image ('{}')", mime_type));
+    }
+    Ok(ImageSource::Inline {
+        data: decode_base64(data, max_bytes)?,
+        mime_type: Some(mime_type.to_string()),
+    })
+}
+
+/// Decode base64 image data, checking the size before decoding
+fn decode_base64(data: &str, max_bytes: u64) -> Result<Vec<u8>> {
+    let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    check_size(data.len() as u64 / 4 * 3, max_bytes)?;
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .context("Invalid base64 image data")
+}
+
+fn check_size(bytes: u64, max_bytes: u64) -> Result<(), ImageError> {
+    if bytes > max_bytes {
+        return Err(ImageError::TooLarge {
+            size: bytes,
+            max_bytes,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GumWallConfig;
+    use crate::test_support::{png, test_context, MockServer};
+    use crate::types::ImageResource;
+
+    fn encode(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    fn context(images: ImagesConfig) -> AppContext {
+        let config = GumWallConfig {
+            images,
+            ..GumWallConfig::default()
+        };
+        test_context(config)
+    }
+
+    fn input() -> AnalyzeImageInput {
+        AnalyzeImageInput {
+            image_uri: None,
+            image_base64: None,
+            image_mime_type: None,
+            image_resource: None,
+            wall_width_cm: 100.0,
+            wall_height_cm: 100.0,
+            gum_diameter_cm: 2.0,
+            overage_percent: None,
+            min_spare_pieces: None,
+            output: None,
+            skip_vision: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inline_sources() {
+        let config = ImagesConfig::default();
+
+        let base64 = AnalyzeImageInput {
+            image_base64: Some(encode(&png())),
+            ..input()
+        };
+        let source = ImageSource::from_input(&base64, &config).unwrap();
+        let image = load_image(source, &context(config.clone())).await.unwrap();
+        assert_eq!(image.mime_type, "image/png");
+        assert_eq!(image.bytes, png());
+
+        let data_url = format!("data:image/png;base64,{}", encode(&png()));
+        let source = ImageSource::parse_uri(&data_url, &config).unwrap();
+        assert!(matches!(source, ImageSource::Inline { .. }));
+
+        let resource = AnalyzeImageInput {
+            image_resource: Some(ImageResource {
+                uri: "resource://images/red.png".to_string(),
+                mime_type: Some("image/png".to_string()),
+                blob: Some(encode(&png())),
+            }),
+            ..input()
+        };
+        let source = ImageSource::from_input(&resource, &config).unwrap();
+        assert_eq!(load_image(source, &context(config)).await.unwrap().bytes, png());
+    }
+
+    #[test]
+    fn test_source_selection() {
+        let config = ImagesConfig::default();
+        assert!(ImageSource::from_input(&input(), &config).is_err());
+
+        let both = AnalyzeImageInput {
+            image_uri: Some("s3://bucket/a.png".to_string()),
+            image_base64: Some(encode(&png())),
+            ..input()
+        };
+        assert!(ImageSource::from_input(&both, &config).is_err());
+
+        // A resource without a blob is loaded from its URI
+        let reference = AnalyzeImageInput {
+            image_resource: Some(ImageResource {
+                uri: "s3://bucket/a.png".to_string(),
+                mime_type: None,
+                blob: None,
+            }),
+            ..input()
+        };
+        assert_eq!(
+            ImageSource::from_input(&reference, &config).unwrap(),
+            ImageSource::S3 {
+                bucket: "bucket".to_string(),
+                key: "a.png".to_string()
+            }
+        );
+
+        assert!(ImageSource::parse_uri("http://example.com/a.png", &config).is_err());
+        assert!(ImageSource::parse_uri("ftp://example.com/a.png", &config).is_err());
+        assert!(ImageSource::parse_uri("data:text/plain;base64,aGk=", &config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_urls_need_flag() {
+        let path = std::env::temp_dir().join(format!("gum-wall-{}.png", std::process::id()));
+        std::fs::write(&path, png()).unwrap();
+        let uri = format!("file://{}", path.display());
+
+        assert!(ImageSource::parse_uri(&uri, &ImagesConfig::default()).is_err());
+
+        let config = ImagesConfig {
+            allow_file_urls: true,
+            ..ImagesConfig::default()
+        };
+        let source = ImageSource::parse_uri(&uri, &config).unwrap();
+        let image = load_image(source, &context(config.clone())).await.unwrap();
+        assert_eq!(image.mime_type, "image/png");
+
+        let small = ImagesConfig {
+            max_bytes: 10,
+            ..config
+        };
+        let source = ImageSource::parse_uri(&uri, &small).unwrap();
+        assert!(load_image(source, &context(small)).await.is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_check_image() {
+        let config = ImagesConfig::default();
+        assert_eq!(check_image(&png(), &config).unwrap(), "image/png");
+        assert!(matches!(
+            check_image(&png()[..20], &config),
+            Err(ImageError::Invalid(_))
+        ));
+        assert!(matches!(
+            check_image(b"%PDF-1.7", &config),
+            Err(ImageError::UnsupportedFormat)
+        ));
+
+        // Only the header is read: a BMP claiming 20000x20000 pixels is rejected
+        let image = image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0]));
+        let mut bomb = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bomb, image::ImageFormat::Bmp).unwrap();
+        let mut bomb = bomb.into_inner();
+        bomb[18..22].copy_from_slice(&20_000i32.to_le_bytes());
+        bomb[22..26].copy_from_slice(&20_000i32.to_le_bytes());
+        assert!(matches!(
+            check_image(&bomb, &config),
+            Err(ImageError::TooManyPixels { width: 20_000, .. })
+        ));
+
+        let tiny = ImagesConfig {
+            max_pixels: 3,
+            ..ImagesConfig::default()
+        };
+        assert!(check_image(&png(), &tiny).is_err());
+    }
+
+    #[test]
+    fn test_private_hosts_are_rejected() {
+        let config = ImagesConfig::default();
+        for uri in [
+            "https://127.0.0.1/a.png",
+            "https://169.254.169.254/latest/meta-data/",
+            "https://10.1.2.3/a.png",
+            "https://[::1]/a.png",
+            "https://[::ffff:192.168.0.1]/a.png",
+        ] {
+            assert!(ImageSource::parse_uri(uri, &config).is_err(), "{}", uri);
+        }
+        assert!(ImageSource::parse_uri("https://93.184.215.14/a.png", &config).is_ok());
+        assert!(ImageSource::parse_uri("https://example.com/a.png", &config).is_ok());
+
+        assert!(is_public_ip("8.8.8.8".parse().unwrap()));
+        assert!(is_public_ip("2606:4700::1111".parse().unwrap()));
+        assert!(!is_public_ip("100.64.0.1".parse().unwrap()));
+        assert!(!is_public_ip("fd00::1".parse().unwrap()));
+        assert!(!is_public_ip("fe80::1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_download_client_stays_off_local_network() {
+        let server = MockServer::start(|_| (200, "{}".to_string())).await;
+        // "localhost" resolves to a loopback address, which the client refuses
+        let url = server.url.replace("127.0.0.1", "localhost");
+        assert!(download_client().get(&url).send().await.is_err());
+        assert_eq!(server.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_checks() {
+        // The mock server answers with a JSON content type
+        let server = MockServer::start(|_| (200, "{\"not\": \"an image\"}".to_string())).await;
+        let client = reqwest::Client::new();
+        let config = ImagesConfig::default();
+        let error = fetch_url(&client, &server.url, &config).await.unwrap_err();
+        assert!(error.to_string().contains("not an image"));
+
+        let missing = MockServer::start(|_| (404, "{}".to_string())).await;
+        let error = fetch_url(&client, &missing.url, &config).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DownloadError>(),
+            Some(DownloadError::Status { status, .. }) if status.as_u16() == 404
+        ));
+
+        let small = ImagesConfig {
+            max_bytes: 5,
+            ..config
+        };
+        let error = fetch_url(&client, &server.url, &small).await.unwrap_err();
+        assert!(error.to_string().contains("too large"));
+    }
+}
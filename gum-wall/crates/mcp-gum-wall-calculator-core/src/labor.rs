@@ -0,0 +1,186 @@
+//! Installation labor and schedule estimation
+//!
+//! Every piece is chewed and then placed by a crew member, after the wall
+//! surface has been prepared. The crew works a fixed number of hours per
+//! day; the schedule fills each day with preparation first, then pieces.
+
+use anyhow::{anyhow, Result};
+use crate::config::LaborConfig;
+use crate::money::{self, CurrencyConverter};
+use crate::types::{LaborDay, LaborEstimate};
+
+/// Wall area covered by one piece when no wall area is given (2 cm grid)
+const DEFAULT_PIECE_AREA_M2: f64 = 0.02 * 0.02;
+
+/// Longest schedule produced before asking for a bigger crew
+pub const MAX_SCHEDULE_DAYS: u32 = 366;
+
+/// Estimate the labor needed to chew and place `pieces` on the wall
+///
+/// `chew_hours` is the total chewing time of all pieces; each day is planned
+/// with the average time per piece. `wall_area_m2` defaults to the area covered by the pieces on a 2 cm grid.
+/// The labor cost uses the configured hourly rate converted to the output
+/// currency.
+pub fn estimate_labor(
+    pieces: u32,
+    chew_hours: f64,
+    wall_area_m2: Option<f64>,
+    crew_size: u32,
+    config: &LaborConfig,
+    converter: &CurrencyConverter,
+) -> Result<LaborEstimate> {
+    if crew_size == 0 {
+        return Err(anyhow!("crew_size must be at least 1"));
+    }
+
+    let wall_area_m2 = self::wall_area_m2(pieces, wall_area_m2);
+    let chew_hours_per_piece = if pieces == 0 {
+        0.0
+    } else {
+        chew_hours / pieces as f64
+    };
+    let hours_per_piece = chew_hours_per_piece + 1.0 / config.placement_rate;
+    let daily_capacity = crew_size as f64 * config.hours_per_day;
+
+    let mut prep_left = wall_area_m2 * config.prep_minutes_per_m2 / 60.0;
+    let mut pieces_left = pieces;
+    let mut schedule = Vec::new();
+    let mut person_hours = 0.0;
+
+    while prep_left > 0.0 || pieces_left > 0 {
+        let day = schedule.len() as u32 + 1;
+        if day > MAX_SCHEDULE_DAYS {
+            return Err(anyhow!(
+                "Installation would take more than {} days with a crew of {}; use a bigger crew",
+                MAX_SCHEDULE_DAYS,
+                crew_size
+            ));
+        }
+
+        let prep_hours = prep_left.min(daily_capacity);
+        prep_left -= prep_hours;
+
+        // Small epsilon so a day that fits exactly isn't lost to rounding
+        let fits = ((daily_capacity - prep_hours) / hours_per_piece + 1e-9).floor() as u32;
+        let pieces_placed = fits.min(pieces_left);
+        if prep_hours == 0.0 && pieces_placed == 0 {
+            return Err(anyhow!("A single piece takes longer than a working day"));
+        }
+        pieces_left -= pieces_placed;
+
+        let worked = prep_hours + pieces_placed as f64 * hours_per_piece;
+        person_hours += worked;
+        let cumulative_pieces = pieces - pieces_left;
+
+        schedule.push(LaborDay {
+            day,
+            hours: round_2dp(worked / crew_size as f64),
+            prep_hours: round_2dp(prep_hours),
+            pieces_placed,
+            cumulative_pieces,
+            percent_complete: if pieces == 0 {
+                100.0
+            } else {
+                (cumulative_pieces as f64 * 1000.0 / pieces as f64).round() / 10.0
+            },
+        });
+    }
+
+    let person_hours = round_2dp(person_hours);
+    let hourly_rate = converter.convert(config.hourly_rate, None)?;
+    let labor_cost = money::round_money(
+        money::decimal_from_f64(person_hours)? * hourly_rate,
+        converter.currency(),
+    );
+
+    Ok(LaborEstimate {
+        crew_size,
+        wall_area_m2: round_2dp(wall_area_m2),
+        person_hours,
+        days: schedule.len() as u32,
+        labor_cost: money::to_f64(labor_cost),
+        schedule,
+    })
+}
+
+/// Wall area to prepare: `wall_area_m2`, or the area covered by the pieces on a 2 cm grid
+pub fn wall_area_m2(pieces: u32, wall_area_m2: Option<f64>) -> f64 {
+    wall_area_m2.unwrap_or(pieces as f64 * DEFAULT_PIECE_AREA_M2)
+}
+
+/// Round hours and areas to two decimal places
+fn round_2dp(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PricingConfig;
+    use rust_decimal::Decimal;
+
+    fn config() -> LaborConfig {
+        LaborConfig {
+            crew_size: 2,
+            placement_rate: 60.0,
+            prep_minutes_per_m2: 60.0,
+            hours_per_day: 8.0,
+            hourly_rate: Decimal::new(20, 0),
+        }
+    }
+
+    /// Five minutes of chewing per piece
+    fn chew_hours(pieces: u32) -> f64 {
+        pieces as f64 * 5.0 / 60.0
+    }
+
+    #[test]
+    fn test_schedule_fills_days() {
+        let pricing = PricingConfig::default();
+        let converter = CurrencyConverter::new(&pricing, None).unwrap();
+
+        // 6 minutes per piece, 2 m² at 1 hour each, 16 person-hours per day
+        let estimate = estimate_labor(300, chew_hours(300), Some(2.0), 2, &config(), &converter).unwrap();
+        assert_eq!(estimate.person_hours, 32.0);
+        assert_eq!(estimate.days, 2);
+        assert_eq!(estimate.labor_cost, 640.0);
+
+        let first = &estimate.schedule[0];
+        assert_eq!(first.prep_hours, 2.0);
+        assert_eq!(first.pieces_placed, 140);
+        assert_eq!(first.hours, 8.0);
+
+        let last = &estimate.schedule[1];
+        assert_eq!(last.pieces_placed, 160);
+        assert_eq!(last.cumulative_pieces, 300);
+        assert_eq!(last.percent_complete, 100.0);
+    }
+
+    #[test]
+    fn test_bigger_crew_finishes_sooner() {
+        let pricing = PricingConfig::default();
+        let converter = CurrencyConverter::new(&pricing, None).unwrap();
+
+        let small = estimate_labor(5000, chew_hours(5000), None, 2, &config(), &converter).unwrap();
+        let large = estimate_labor(5000, chew_hours(5000), None, 8, &config(), &converter).unwrap();
+        assert!(large.days < small.days);
+        assert_eq!(large.person_hours, small.person_hours);
+        assert_eq!(large.wall_area_m2, 2.0);
+    }
+
+    #[test]
+    fn test_schedule_limit() {
+        let pricing = PricingConfig::default();
+        let converter = CurrencyConverter::new(&pricing, None).unwrap();
+        assert!(estimate_labor(
+            1_000_000,
+            chew_hours(1_000_000),
+            None,
+            1,
+            &config(),
+            &converter
+        )
+        .is_err());
+        assert!(estimate_labor(100, chew_hours(100), None, 0, &config(), &converter).is_err());
+    }
+}
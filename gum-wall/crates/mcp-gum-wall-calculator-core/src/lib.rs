@@ -5,24 +5,95 @@
 //! - Calculating the cost of gum needed based on current prices
 //! - Generating SVG visualizations of the design
 
+mod admin;
+mod artifacts;
+mod cache;
+mod catalog;
+mod config;
+mod context;
+mod cost;
+mod error;
 mod graphql;
+mod image_source;
+mod labor;
+mod moderation;
+mod money;
+mod order;
+mod purchase;
 mod quantize;
+mod quote;
 mod s3;
+mod stats;
 mod svg;
 mod types;
+mod upload;
 mod vision;
 
+#[cfg(test)]
+mod test_support;
+
 use pmcp::types::capabilities::ServerCapabilities;
 use pmcp::{Server, TypedTool};
 use serde_json::json;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
 use validator::Validate;
 
+pub use cache::{CachedCatalog, DEFAULT_CACHE_TTL, DEFAULT_STALE_WINDOW};
+pub use catalog::{
+    catalog_from_config, catalog_from_config_with_client, parse_gum_types, AppSyncCatalog,
+    CatalogFormat, FallbackCatalog, FileCatalog, GumCatalog, InMemoryCatalog, WithFallback,
+};
+pub use config::{
+    AdminConfig, AppSyncConfig, ArtifactsConfig, BedrockVisionConfig, CatalogConfig,
+    CatalogSource, ConfigError, DiscountTier, GumDefaultsConfig, GumWallConfig, ImagesConfig,
+    LaborConfig, ModerationConfig, ModerationMode, OrdersConfig, OverageConfig, PricingConfig,
+    QuoteConfig, S3Config, Secret, UploadsConfig, VisionConfig, VisionProviderKind,
+    CONFIG_PATH_ENV,
+};
+pub use context::AppContext;
+pub use error::GumWallError;
+pub use vision::{provider_from_config, BedrockVision, LocalVision, OpenAiVision, VisionProvider};
 pub use types::*;
 
 /// Build the Gum Wall Calculator MCP server
+///
+/// Configuration is loaded from `GUM_WALL_CONFIG` and environment variables
+/// and validated before any tools are registered.
 pub fn build_gum_wall_calculator_server() -> pmcp::Result<Server> {
-    Server::builder()
+    let config = GumWallConfig::load()
+        .map_err(|e| GumWallError::Configuration(e.to_string()))?;
+    tracing::info!("Loaded configuration: {:?}", config);
+
+    // One HTTP client (and its connection pool) for the catalog and every tool call
+    let http = reqwest::Client::new();
+    let catalog = catalog_from_config_with_client(&config, http.clone())
+        .map_err(|e| GumWallError::Configuration(format!("gum catalog: {}", e)))?;
+    build_gum_wall_calculator_server_with_context(Arc::new(AppContext::new(config, catalog, http)))
+}
+
+/// Build the Gum Wall Calculator MCP server using the given configuration and gum catalog
+pub fn build_gum_wall_calculator_server_with_catalog(
+    config: GumWallConfig,
+    catalog: Arc<dyn GumCatalog>,
+) -> pmcp::Result<Server> {
+    let context = AppContext::new(config, catalog, reqwest::Client::new());
+    build_gum_wall_calculator_server_with_context(Arc::new(context))
+}
+
+/// Build the Gum Wall Calculator MCP server sharing the given context between all tool calls
+pub fn build_gum_wall_calculator_server_with_context(
+    context: Arc<AppContext>,
+) -> pmcp::Result<Server> {
+    let analyze_context = context.clone();
+    let cost_context = context.clone();
+    let quote_context = context.clone();
+    let list_context = context.clone();
+    let refresh_context = context.clone();
+
+    let builder = Server::builder()
         .name("gum-wall-calculator")
         .version("1.0.0")
         .capabilities(ServerCapabilities::tools_only())
@@ -31,93 +102,135 @@ pub fn build_gum_wall_calculator_server() -> pmcp::Result<Server> {
             "analyze_image_for_gum_wall",
             TypedTool::new(
                 "analyze_image_for_gum_wall",
-                |input: AnalyzeImageInput, _extra| {
+                move |input: AnalyzeImageInput, _extra| {
+                    let context = analyze_context.clone();
                     Box::pin(async move {
+                        // Validate input
+                        input
+                            .validate()
+                            .map_err(|e| GumWallError::Validation(e.to_string()))?;
+
+                        let images = &context.config.images;
+                        let source = image_source::ImageSource::from_input(&input, images)
+                            .map_err(GumWallError::from_image_source)?;
+
+                        // Decide up front where S3 output goes, before any work is done
+                        let output_mode = input.output.unwrap_or(context.config.artifacts.output);
+                        let artifact_location = match output_mode {
+                            OutputMode::S3 => Some(
+                                artifacts::ArtifactLocation::resolve(
+                                    &context.config.artifacts,
+                                    context.config.uploads.bucket.as_deref(),
+                                    &source,
+                                )
+                                .map_err(|e| GumWallError::InvalidInput(e.to_string()))?,
+                            ),
+                            OutputMode::Inline => None,
+                        };
+
                         // Log the input for debugging
                         tracing::info!(
-                            "analyze_image_for_gum_wall called with: s3_uri='{}', wall_width_cm={}, wall_height_cm={}, gum_diameter_cm={}",
-                            input.s3_uri,
+                            "analyze_image_for_gum_wall called with: image='{}', wall_width_cm={}, wall_height_cm={}, gum_diameter_cm={}",
+                            source.describe(),
                             input.wall_width_cm,
                             input.wall_height_cm,
                             input.gum_diameter_cm
                         );
 
-                        // Validate input
-                        input.validate().map_err(|e| {
-                            pmcp::Error::validation(format!("Validation failed: {}", e))
-                        })?;
-
-                        // Parse S3 URI
-                        let (bucket, key) = s3::parse_s3_uri(&input.s3_uri).map_err(|e| {
-                            pmcp::Error::validation(format!("Invalid S3 URI: {}", e))
-                        })?;
-
-                        // Create S3 client
-                        let s3_client = s3::create_s3_client().await.map_err(|e| {
-                            pmcp::Error::internal(format!("Failed to create S3 client: {}", e))
-                        })?;
-
-                        // Fetch image from S3
-                        let (image_bytes, content_type) =
-                            s3::fetch_image_from_s3(&s3_client, &bucket, &key)
-                                .await
-                                .map_err(|e| {
-                                    pmcp::Error::internal(format!(
-                                        "Failed to fetch image from S3: {}",
-                                        e
-                                    ))
-                                })?;
-
-                        let mime_type = s3::get_mime_type(&key, &content_type);
-
-                        // Encode image as base64 for vision API
-                        let image_base64 = base64::Engine::encode(
-                            &base64::engine::general_purpose::STANDARD,
-                            &image_bytes,
-                        );
-
-                        // Analyze image with GPT-4o-mini vision
-                        let image_description =
-                            vision::analyze_image(&image_base64, &mime_type)
-                                .await
-                                .unwrap_or_else(|e| {
-                                    format!("Could not analyze image: {}", e)
-                                });
-
-                        // Load image for processing
-                        let image = quantize::load_image_from_bytes(&image_bytes).map_err(|e| {
-                            pmcp::Error::internal(format!("Failed to load image: {}", e))
-                        })?;
+                        // Fetch the image
+                        let started = Instant::now();
+                        let mut timings = StageTimings::default();
+                        let (loaded, fetch_ms) =
+                            timed(image_source::load_image(source, &context)).await;
+                        timings.fetch_ms = fetch_ms;
+                        let image_source::LoadedImage {
+                            bytes: image_bytes,
+                            mime_type,
+                        } = loaded.map_err(GumWallError::from_image_load)?;
+
+                        // Vision analysis, catalog lookup and decoding run at the same time
+                        let ImageStages {
+                            image_description,
+                            image_analysis,
+                            moderation,
+                            gum_types,
+                            image,
+                        } = run_image_stages(
+                            &context,
+                            image_bytes.into(),
+                            &mime_type,
+                            input.skip_vision,
+                            &mut timings,
+                        )
+                        .await?;
 
                         // Calculate grid dimensions
                         let grid_cols = (input.wall_width_cm / input.gum_diameter_cm).floor() as u32;
                         let grid_rows =
                             (input.wall_height_cm / input.gum_diameter_cm).floor() as u32;
 
-                        // Fetch gum types from AppSync (with fallback)
-                        let gum_types = graphql::fetch_gum_types_with_fallback().await;
-
-                        // Quantize image to gum colors
-                        let quantization_result =
-                            quantize::quantize_image(&image, &gum_types, grid_cols, grid_rows);
-
-                        // Generate SVG
-                        let svg_config = svg::SvgConfig {
-                            cell_size: 15,
-                            show_numbers: true,
-                            show_grid: true,
-                            font_size_ratio: 0.4,
-                        };
-                        let svg_output =
-                            svg::generate_svg(&quantization_result.grid, &quantization_result.legend, &svg_config);
+                        // Quantize image to gum colors, guided by the analysis, and generate the
+                        // SVG on the blocking pool
+                        let analysis = image_analysis.clone();
+                        let (quantization_result, svg_output, quantize_ms, render_ms) =
+                            tokio::task::spawn_blocking(move || {
+                                let (quantization_result, quantize_ms) = timed_blocking(|| {
+                                    quantize::quantize_image(
+                                        &image,
+                                        &gum_types,
+                                        grid_cols,
+                                        grid_rows,
+                                        analysis.as_ref(),
+                                    )
+                                });
+                                let svg_config = svg::SvgConfig {
+                                    cell_size: 15,
+                                    show_numbers: true,
+                                    show_grid: true,
+                                    font_size_ratio: 0.4,
+                                };
+                                let (svg_output, render_ms) = timed_blocking(|| {
+                                    svg::generate_svg(
+                                        &quantization_result.grid,
+                                        &quantization_result.legend,
+                                        &svg_config,
+                                    )
+                                });
+                                (quantization_result, svg_output, quantize_ms, render_ms)
+                            })
+                            .await
+                            .map_err(|e| {
+                                GumWallError::Internal(format!("quantization failed: {}", e))
+                            })?;
+                        timings.quantize_ms = quantize_ms;
+                        timings.render_ms = render_ms;
 
                         // Build output
                         let total_gums: u32 = quantization_result.gum_counts.values().sum();
 
+                        // Add spare pieces for drops and rejects
+                        let overage = context
+                            .config
+                            .overage
+                            .with_overrides(input.overage_percent, input.min_spare_pieces);
+                        let purchase_counts: HashMap<String, u32> = quantization_result
+                            .gum_counts
+                            .iter()
+                            .map(|(gum_id, count)| {
+                                let spare_pieces = overage.spare_pieces(*count);
+                                (gum_id.clone(), count.saturating_add(spare_pieces))
+                            })
+                            .collect();
+                        let total_spare_pieces = purchase_counts
+                            .values()
+                            .fold(0u32, |total, count| total.saturating_add(*count))
+                            .saturating_sub(total_gums);
+
                         // Run-length encode the grid for compression
                         let grid_rle = types::rle_encode_grid(&quantization_result.grid);
 
-                        let output = AnalyzeImageOutput {
+                        timings.total_ms = elapsed_ms(started);
+                        let mut output = AnalyzeImageOutput {
                             grid_rle,
                             legend: quantization_result.legend,
                             dimensions: GridDimensions {
@@ -127,134 +240,475 @@ pub fn build_gum_wall_calculator_server() -> pmcp::Result<Server> {
                                     * quantization_result.cols,
                             },
                             gum_counts: quantization_result.gum_counts,
+                            purchase_counts,
                             total_gums,
+                            total_spare_pieces,
                             image_description,
-                            svg: svg_output,
+                            image_analysis,
+                            moderation,
+                            svg: None,
+                            artifacts: Vec::new(),
+                            timings,
                         };
 
-                        Ok(serde_json::to_value(output).unwrap())
+                        match artifact_location {
+                            Some(location) => {
+                                let upload_started = Instant::now();
+                                output.artifacts = artifacts::publish_design(
+                                    &context,
+                                    &location,
+                                    &output,
+                                    &quantization_result.grid,
+                                    svg_output,
+                                )
+                                .await
+                                .map_err(|e| GumWallError::StorageUnavailable(e.to_string()))?;
+                                output.timings.upload_ms = Some(elapsed_ms(upload_started));
+                                output.timings.total_ms = elapsed_ms(started);
+                            }
+                            None => output.svg = Some(svg_output),
+                        }
+
+                        Ok(serde_json::to_value(output)
+                            .map_err(|e| GumWallError::Internal(e.to_string()))?)
                     })
                 },
             )
             .with_description(
-                "Analyze an image from S3 and create a paint-by-number gum wall design. \
-                Returns a grid of gum colors, SVG visualization, and counts of each gum type needed.",
+                "Analyze an image and create a paint-by-number gum wall design. \
+                The image can be an s3:// or https:// URI, a data: URL, base64 data or an MCP \
+                resource. \
+                Returns a grid of gum colors, SVG visualization, and counts of each gum type needed. \
+                With output=s3 the SVG, PNG, PDF and design JSON are uploaded to S3 instead and \
+                returned as presigned download URLs under artifacts. \
+                purchase_counts add spare pieces for drops and rejects; pass gum_counts (not \
+                purchase_counts) to calculate_gum_cost, which adds the same spares itself.",
             ),
         )
         // Tool: Calculate cost based on gum counts
         .tool(
             "calculate_gum_cost",
-            TypedTool::new("calculate_gum_cost", |input: CalculateCostInput, _extra| {
+            TypedTool::new("calculate_gum_cost", move |input: CalculateCostInput, _extra| {
+                let context = cost_context.clone();
                 Box::pin(async move {
+                    input
+                        .validate()
+                        .map_err(|e| GumWallError::Validation(e.to_string()))?;
+
                     // Fetch gum types for pricing
-                    let gum_types = graphql::fetch_gum_types_with_fallback().await;
-                    let gum_map: HashMap<String, GumType> =
-                        gum_types.into_iter().map(|g| (g.gum_id.clone(), g)).collect();
-
-                    let mut breakdown: Vec<CostBreakdownItem> = Vec::new();
-                    let mut total_pieces: u32 = 0;
-                    let mut total_cost: f64 = 0.0;
-
-                    for (gum_id, count) in &input.gum_counts {
-                        if let Some(gum_type) = gum_map.get(gum_id) {
-                            let subtotal = gum_type.price_per_piece * (*count as f64);
-                            breakdown.push(CostBreakdownItem {
-                                gum_id: gum_id.clone(),
-                                name: gum_type.name.clone(),
-                                hex_color: gum_type.hex_color.clone(),
-                                count: *count,
-                                unit_price: gum_type.price_per_piece,
-                                subtotal,
-                            });
-                            total_pieces += count;
-                            total_cost += subtotal;
-                        } else {
-                            // Unknown gum type - use default price
-                            let default_price = 0.08;
-                            let subtotal = default_price * (*count as f64);
-                            breakdown.push(CostBreakdownItem {
-                                gum_id: gum_id.clone(),
-                                name: format!("Unknown ({})", gum_id),
-                                hex_color: "#CCCCCC".to_string(),
-                                count: *count,
-                                unit_price: default_price,
-                                subtotal,
-                            });
-                            total_pieces += count;
-                            total_cost += subtotal;
-                        }
-                    }
-
-                    // Sort by count (descending)
-                    breakdown.sort_by(|a, b| b.count.cmp(&a.count));
-
-                    // Calculate fun stats
-                    let fun_stats = FunStats {
-                        chewing_time_hours: (total_pieces as f64 * 5.0) / 60.0, // 5 min per piece
-                        calories_burned: (total_pieces as f64 * 11.0) as u32, // ~11 cal per hour of chewing, assume 1 piece = 1 hour equivalent
-                        packs_needed: (total_pieces as f64 / 5.0).ceil() as u32, // 5 pieces per pack
-                        weight_kg: (total_pieces as f64 * 3.0) / 1000.0, // 3g per piece
-                    };
-
-                    let output = CalculateCostOutput {
-                        breakdown,
-                        total_pieces,
-                        total_cost: (total_cost * 100.0).round() / 100.0, // Round to cents
-                        currency: "USD".to_string(),
-                        fun_stats,
-                    };
-
-                    Ok(serde_json::to_value(output).unwrap())
+                    let gum_types = context
+                        .catalog
+                        .list_gum_types()
+                        .await
+                        .map_err(|e| GumWallError::CatalogUnavailable(e.to_string()))?;
+                    let mut output = cost::calculate_cost(&input, &gum_types, &context.config)
+                        .map_err(|e| GumWallError::or_wrap(e, GumWallError::Internal))?;
+                    output.fallback_prices = context.catalog.is_fallback();
+
+                    Ok(serde_json::to_value(output)
+                        .map_err(|e| GumWallError::Internal(e.to_string()))?)
                 })
             })
             .with_description(
                 "Calculate the total cost of gum needed for a gum wall project. \
-                Takes a map of gum type IDs to counts and returns a detailed cost breakdown with fun statistics.",
+                Takes a map of gum type IDs to counts and returns a detailed cost breakdown with fun statistics. \
+                Unavailable or unknown gum types are not priced; they are reported as warnings with \
+                nearest-color substitutes, or rejected when strict is true. \
+                Prices are converted to the requested currency using the configured exchange rates. \
+                Spare pieces (overage_percent or min_spare_pieces) are added before buying packs. \
+                Also estimates installation labor: a day-by-day schedule and labor cost for the \
+                given crew_size and wall_area_m2, left out with a labor_warning when it can't \
+                be estimated.",
+            ),
+        )
+        // Tool: Generate a formal quote
+        .tool(
+            "generate_gum_quote",
+            TypedTool::new("generate_gum_quote", move |input: GenerateQuoteInput, _extra| {
+                let context = quote_context.clone();
+                Box::pin(async move {
+                    input
+                        .validate()
+                        .map_err(|e| GumWallError::Validation(e.to_string()))?;
+
+                    let gum_types = context
+                        .catalog
+                        .list_gum_types()
+                        .await
+                        .map_err(|e| GumWallError::CatalogUnavailable(e.to_string()))?;
+                    let now = chrono::Utc::now();
+                    let output = quote::generate_quote(
+                        &input,
+                        &gum_types,
+                        context.catalog.is_fallback(),
+                        &context.config,
+                        now,
+                    )
+                    .map_err(|e| GumWallError::or_wrap(e, GumWallError::Internal))?;
+
+                    Ok(serde_json::to_value(output)
+                        .map_err(|e| GumWallError::Internal(e.to_string()))?)
+                })
+            })
+            .with_description(
+                "Generate a formal quote for a gum wall project with a quote ID and validity date. \
+                Takes the same project fields as calculate_gum_cost under `cost`, applies the \
+                configured volume discounts, shipping by weight and sales tax, optionally includes \
+                installation labor, and renders the quote as Markdown or HTML.",
             ),
         )
         // Tool: Get available gum types
         .tool(
             "list_gum_types",
-            TypedTool::new("list_gum_types", |_input: EmptyInput, _extra| {
+            TypedTool::new("list_gum_types", move |_input: EmptyInput, _extra| {
+                let context = list_context.clone();
                 Box::pin(async move {
-                    let gum_types = graphql::fetch_gum_types_with_fallback().await;
+                    let gum_types = context
+                        .catalog
+                        .list_gum_types()
+                        .await
+                        .map_err(|e| GumWallError::CatalogUnavailable(e.to_string()))?;
 
                     Ok(json!({
+                        "available_count": gum_types.iter().filter(|g| g.is_available).count(),
                         "gum_types": gum_types,
                         "count": gum_types.len(),
-                        "source": if std::env::var("APPSYNC_API_URL").is_ok() {
-                            "appsync"
-                        } else {
-                            "fallback"
-                        }
+                        "source": context.catalog.source()
                     }))
                 })
             })
             .with_description(
-                "List all available gum types with their colors and prices. \
-                Returns gum types from DynamoDB via AppSync, or fallback data if unavailable.",
+                "List all gum types with their colors, prices and availability. \
+                Returns gum types from the configured catalog (DynamoDB via AppSync by default), \
+                or fallback data if unavailable.",
             ),
         )
-        .build()
+        // Tool: Force a refresh of the cached gum catalog
+        .tool(
+            "refresh_gum_catalog",
+            TypedTool::new("refresh_gum_catalog", move |_input: EmptyInput, _extra| {
+                let context = refresh_context.clone();
+                Box::pin(async move {
+                    let gum_types = context
+                        .catalog
+                        .refresh()
+                        .await
+                        .map_err(|e| GumWallError::CatalogUnavailable(e.to_string()))?;
+
+                    Ok(json!({
+                        "refreshed": true,
+                        "count": gum_types.len(),
+                        "source": context.catalog.source()
+                    }))
+                })
+            })
+            .with_description(
+                "Refresh the cached gum catalog immediately instead of waiting for the cache to expire. \
+                Use after prices or colors change in DynamoDB.",
+            ),
+        );
+
+    // Admin tools are opt-in because they write to the shared catalog
+    let config = &context.config;
+    let builder = match &config.appsync {
+        Some(appsync) if config.admin.enabled => {
+            tracing::info!("Admin tools enabled");
+            admin::register_admin_tools(builder, appsync.clone(), context.clone())
+        }
+        _ => builder,
+    };
+
+    // Ordering is opt-in because it creates real orders
+    let builder = match &config.appsync {
+        Some(appsync) if config.orders.enabled => {
+            tracing::info!("Order placement enabled");
+            order::register_order_tools(builder, appsync.clone(), context.clone())
+        }
+        _ => builder,
+    };
+
+    // Image uploads are opt-in because they need a bucket to write to
+    let builder = if config.uploads.bucket.is_some() {
+        tracing::info!("Image uploads enabled");
+        upload::register_upload_tools(builder, context.clone())
+    } else {
+        builder
+    };
+
+    builder.build()
+}
+
+/// Results of the stages that run before quantization
+struct ImageStages {
+    image_description: String,
+    image_analysis: Option<ImageAnalysis>,
+    moderation: Option<ModerationResult>,
+    gum_types: Vec<GumType>,
+    image: image::DynamicImage,
+}
+
+/// Run vision analysis, moderation, the catalog lookup and image decoding concurrently
+///
+/// Decoding is CPU-bound and runs on the blocking pool. A failed vision
+/// analysis only changes the description; a failed catalog lookup or decode
+/// fails the request, and so does an image that moderation rejects. Stage
+/// durations are recorded in `timings`.
+async fn run_image_stages(
+    context: &AppContext,
+    image_bytes: Arc<[u8]>,
+    mime_type: &str,
+    skip_vision: bool,
+    timings: &mut StageTimings,
+) -> Result<ImageStages, GumWallError> {
+    let vision = timed(async {
+        if skip_vision {
+            return ("Vision analysis skipped".to_string(), None);
+        }
+        match context.vision.analyze_image(&image_bytes, mime_type).await {
+            Ok(analysis) => (analysis.description.clone(), Some(analysis)),
+            Err(e) => {
+                let error = GumWallError::VisionFailed(e.to_string());
+                tracing::warn!(
+                    "Vision provider '{}' failed ({}): {}",
+                    context.vision.name(),
+                    error.code(),
+                    error
+                );
+                (format!("Could not analyze image: {}", e), None)
+            }
+        }
+    });
+    let moderation = timed(moderation::screen_image(context, &image_bytes, mime_type));
+    let catalog = timed(context.catalog.list_gum_types());
+    let decode_bytes = image_bytes.clone();
+    let decode = timed(tokio::task::spawn_blocking(move || {
+        quantize::load_image_from_bytes(&decode_bytes)
+    }));
+
+    let (vision, moderation, catalog, decode) = tokio::join!(vision, moderation, catalog, decode);
+    let ((image_description, image_analysis), vision_ms) = vision;
+    let (moderation, moderation_ms) = moderation;
+    let (gum_types, catalog_ms) = catalog;
+    let (image, decode_ms) = decode;
+    timings.vision_ms = if skip_vision { 0 } else { vision_ms };
+    timings.moderation_ms = if moderation.is_some() { moderation_ms } else { 0 };
+    timings.catalog_ms = catalog_ms;
+    timings.decode_ms = decode_ms;
+
+    if let Some(result) = &moderation {
+        if moderation::is_rejected(&context.config.moderation, result) {
+            let reason = result.reason.as_deref().unwrap_or("content not allowed");
+            return Err(GumWallError::ContentRejected(reason.to_string()));
+        }
+    }
+
+    let image = image
+        .map_err(|e| GumWallError::Internal(format!("image decoding failed: {}", e)))?
+        .map_err(|e| GumWallError::DecodeFailed(e.to_string()))?;
+    let gum_types = gum_types.map_err(|e| GumWallError::CatalogUnavailable(e.to_string()))?;
+
+    Ok(ImageStages {
+        image_description,
+        image_analysis,
+        moderation,
+        gum_types,
+        image,
+    })
+}
+
+/// Await a future and measure how long it took, in milliseconds
+async fn timed<T>(future: impl Future<Output = T>) -> (T, u64) {
+    let started = Instant::now();
+    let value = future.await;
+    (value, elapsed_ms(started))
+}
+
+/// Run a closure and measure how long it took, in milliseconds
+fn timed_blocking<T>(f: impl FnOnce() -> T) -> (T, u64) {
+    let started = Instant::now();
+    let value = f();
+    (value, elapsed_ms(started))
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{solid_png, test_context};
 
     #[tokio::test]
     async fn test_server_builds() {
-        let server = build_gum_wall_calculator_server();
+        let mut config = GumWallConfig::default();
+        config.catalog.source = Some(CatalogSource::Builtin);
+        let catalog = catalog_from_config(&config).unwrap();
+        let server = build_gum_wall_calculator_server_with_catalog(config, catalog);
         assert!(server.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_server_builds_with_in_memory_catalog() {
+        let catalog = Arc::new(InMemoryCatalog::new(graphql::get_fallback_gum_types()));
+        let server = build_gum_wall_calculator_server_with_catalog(GumWallConfig::default(), catalog);
+        assert!(server.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_server_builds_with_shared_context() {
+        let http = reqwest::Client::new();
+        let mut config = GumWallConfig::default();
+        config.catalog.source = Some(CatalogSource::Builtin);
+        let catalog = catalog_from_config_with_client(&config, http.clone()).unwrap();
+        let context = Arc::new(AppContext::new(config, catalog, http));
+        let server = build_gum_wall_calculator_server_with_context(context.clone());
+        assert!(server.is_ok());
+        // The handlers hold clones of the same context
+        assert!(Arc::strong_count(&context) > 1);
+    }
+
+    /// Holds each slow stage until `parties` stages are running at once
+    ///
+    /// Stages that run one after another never all arrive, so the first one
+    /// gives up and fails the test instead of hanging.
+    struct Rendezvous(tokio::sync::Barrier);
+
+    impl Rendezvous {
+        fn new(parties: usize) -> Arc<Self> {
+            Arc::new(Rendezvous(tokio::sync::Barrier::new(parties)))
+        }
+
+        async fn arrive(&self) {
+            tokio::time::timeout(std::time::Duration::from_secs(10), self.0.wait())
+                .await
+                .expect("image stages did not run concurrently");
+        }
+    }
+
+    /// Vision provider that meets the other stages, then takes 200ms
+    struct SlowVision(Arc<Rendezvous>);
+
+    #[async_trait::async_trait]
+    impl VisionProvider for SlowVision {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        async fn analyze_image(
+            &self,
+            image: &[u8],
+            mime_type: &str,
+        ) -> anyhow::Result<ImageAnalysis> {
+            self.0.arrive().await;
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            LocalVision.analyze_image(image, mime_type).await
+        }
+
+        async fn moderate_image(
+            &self,
+            image: &[u8],
+            mime_type: &str,
+        ) -> anyhow::Result<ModerationResult> {
+            self.0.arrive().await;
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            LocalVision.moderate_image(image, mime_type).await
+        }
+    }
+
+    /// Catalog that meets the other stages, then takes 200ms
+    struct SlowCatalog(Arc<Rendezvous>);
+
+    #[async_trait::async_trait]
+    impl GumCatalog for SlowCatalog {
+        fn source(&self) -> &str {
+            "slow"
+        }
+
+        async fn list_gum_types(&self) -> anyhow::Result<Vec<GumType>> {
+            self.0.arrive().await;
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            Ok(graphql::get_fallback_gum_types())
+        }
+    }
+
+    /// Context whose vision provider and catalog wait for `parties` stages
+    fn slow_context(parties: usize) -> AppContext {
+        let mut config = GumWallConfig::default();
+        config.moderation.mode = ModerationMode::Flag;
+        let rendezvous = Rendezvous::new(parties);
+        AppContext::new(config, Arc::new(SlowCatalog(rendezvous.clone())), reqwest::Client::new())
+            .with_vision(Arc::new(SlowVision(rendezvous)))
+    }
+
+    #[tokio::test]
+    async fn test_image_stages_run_concurrently() {
+        let png: Arc<[u8]> = solid_png(4, [255, 105, 180]).into();
+        let mut timings = StageTimings::default();
+
+        // Vision analysis, moderation and the catalog must all be running at once
+        let context = slow_context(3);
+        let stages = run_image_stages(&context, png.clone(), "image/png", false, &mut timings)
+            .await
+            .unwrap();
+        assert!(timings.vision_ms >= 200 && timings.catalog_ms >= 200);
+        assert!(timings.moderation_ms >= 200);
+        assert!(stages.image_analysis.is_some());
+        // The stub classifier takes this pink for skin
+        assert!(stages.moderation.unwrap().flagged);
+        assert_eq!(stages.image.width(), 4);
+        assert!(!stages.gum_types.is_empty());
+
+        // Without vision analysis only moderation and the catalog meet
+        let context = slow_context(2);
+        let stages = run_image_stages(&context, png, "image/png", true, &mut timings)
+            .await
+            .unwrap();
+        assert!(stages.image_analysis.is_none());
+        assert_eq!(timings.vision_ms, 0);
+
+        let garbage: Arc<[u8]> = b"not an image".as_slice().into();
+        assert!(run_image_stages(&context, garbage, "image/png", true, &mut timings)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_moderation_rejects_flagged_images() {
+        let mut config = GumWallConfig::default();
+        config.moderation.mode = ModerationMode::Reject;
+        let context = test_context(config);
+        let encode = |color: [u8; 3]| -> Arc<[u8]> { solid_png(4, color).into() };
+        let mut timings = StageTimings::default();
+
+        let skin = encode([224, 172, 140]);
+        let error = run_image_stages(&context, skin, "image/png", true, &mut timings)
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("rejected by content moderation"));
+
+        let sky = encode([30, 144, 255]);
+        let stages = run_image_stages(&context, sky, "image/png", true, &mut timings)
+            .await
+            .unwrap();
+        assert!(!stages.moderation.unwrap().flagged);
+    }
+
     #[test]
     fn test_analyze_input_validation() {
         let input = AnalyzeImageInput {
-            s3_uri: "s3://bucket/key.jpg".to_string(),
+            image_uri: Some("s3://bucket/key.jpg".to_string()),
+            image_base64: None,
+            image_mime_type: None,
+            image_resource: None,
             wall_width_cm: 200.0,
             wall_height_cm: 150.0,
             gum_diameter_cm: 2.0,
+            overage_percent: None,
+            min_spare_pieces: None,
+            output: None,
+            skip_vision: false,
         };
         assert!(input.validate().is_ok());
     }
@@ -265,7 +719,15 @@ mod tests {
         counts.insert("dubble_bubble_pink".to_string(), 100);
         counts.insert("juicy_fruit_yellow".to_string(), 50);
 
-        let input = CalculateCostInput { gum_counts: counts };
+        let input = CalculateCostInput {
+            gum_counts: counts,
+            strict: false,
+            currency: None,
+            overage_percent: None,
+            min_spare_pieces: None,
+            crew_size: None,
+            wall_area_m2: None,
+        };
 
         // Just verify the types compile correctly
         assert_eq!(input.gum_counts.len(), 2);
@@ -0,0 +1,142 @@
+//! Content moderation for public walls
+//!
+//! With `[moderation]` enabled, every analyzed image is screened by a vision
+//! provider before the design is returned. Depending on the mode, flagged
+//! images are reported in the output or refused.
+
+use crate::config::{ModerationConfig, ModerationMode};
+use crate::context::AppContext;
+use crate::types::{ModerationCategory, ModerationResult};
+
+/// Screen an image with the configured moderator
+///
+/// Returns `None` when moderation is off. A failed check counts as flagged
+/// when `fail_closed` is set.
+pub async fn screen_image(
+    context: &AppContext,
+    image: &[u8],
+    mime_type: &str,
+) -> Option<ModerationResult> {
+    let config = &context.config.moderation;
+    if config.mode == ModerationMode::Off {
+        return None;
+    }
+
+    let moderator = context.moderator();
+    match moderator.moderate_image(image, mime_type).await {
+        Ok(result) => Some(apply_policy(config, result)),
+        Err(e) => {
+            tracing::warn!("Moderation by '{}' failed: {}", moderator.name(), e);
+            Some(ModerationResult {
+                flagged: config.fail_closed,
+                categories: if config.fail_closed {
+                    vec![ModerationCategory::Other]
+                } else {
+                    Vec::new()
+                },
+                reason: Some(format!("Moderation check failed: {}", e)),
+            })
+        }
+    }
+}
+
+/// Keep only the categories this deployment screens for
+///
+/// A flagged result without categories counts as `other`. When categories
+/// are configured, a result stays flagged only if one of them applies.
+pub fn apply_policy(config: &ModerationConfig, mut result: ModerationResult) -> ModerationResult {
+    if !result.flagged {
+        return result;
+    }
+    if result.categories.is_empty() {
+        result.categories.push(ModerationCategory::Other);
+    }
+    if !config.categories.is_empty() {
+        result
+            .categories
+            .retain(|category| config.categories.contains(category));
+        if result.categories.is_empty() {
+            result.flagged = false;
+            result.reason = None;
+        }
+    }
+    result
+}
+
+/// Whether a screened image must be refused
+pub fn is_rejected(config: &ModerationConfig, result: &ModerationResult) -> bool {
+    config.mode == ModerationMode::Reject && result.flagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GumWallConfig, VisionProviderKind};
+    use crate::test_support::{solid_png, test_context};
+
+    fn context(mode: ModerationMode, fail_closed: bool) -> AppContext {
+        let mut config = GumWallConfig::default();
+        config.moderation.mode = mode;
+        config.moderation.provider = Some(VisionProviderKind::Local);
+        config.moderation.fail_closed = fail_closed;
+        test_context(config)
+    }
+
+    #[tokio::test]
+    async fn test_screen_image_with_stub_classifier() {
+        let skin = solid_png(8, [224, 172, 140]);
+        let sky = solid_png(8, [30, 144, 255]);
+
+        let off = context(ModerationMode::Off, true);
+        assert!(screen_image(&off, &skin, "image/png").await.is_none());
+
+        let reject = context(ModerationMode::Reject, true);
+        let result = screen_image(&reject, &skin, "image/png").await.unwrap();
+        assert!(is_rejected(&reject.config.moderation, &result));
+        let result = screen_image(&reject, &sky, "image/png").await.unwrap();
+        assert!(!is_rejected(&reject.config.moderation, &result));
+
+        let flag = context(ModerationMode::Flag, true);
+        let result = screen_image(&flag, &skin, "image/png").await.unwrap();
+        assert!(result.flagged);
+        assert!(!is_rejected(&flag.config.moderation, &result));
+
+        // A check that cannot run counts as flagged only when failing closed
+        let result = screen_image(&reject, b"not an image", "image/png")
+            .await
+            .unwrap();
+        assert!(result.flagged);
+        assert!(result
+            .reason
+            .unwrap()
+            .starts_with("Moderation check failed"));
+        let fail_open = context(ModerationMode::Reject, false);
+        let result = screen_image(&fail_open, b"not an image", "image/png")
+            .await
+            .unwrap();
+        assert!(!result.flagged);
+    }
+
+    #[test]
+    fn test_policy_categories() {
+        let flagged = |categories: Vec<ModerationCategory>| ModerationResult {
+            flagged: true,
+            categories,
+            reason: Some("reason".to_string()),
+        };
+        let mut config = ModerationConfig::default();
+        let result = apply_policy(&config, flagged(Vec::new()));
+        assert_eq!(result.categories, [ModerationCategory::Other]);
+
+        config.categories = vec![ModerationCategory::Sexual];
+        let result = apply_policy(&config, flagged(vec![ModerationCategory::Drugs]));
+        assert!(!result.flagged);
+        assert!(result.reason.is_none());
+        let result = apply_policy(
+            &config,
+            flagged(vec![ModerationCategory::Drugs, ModerationCategory::Sexual]),
+        );
+        assert!(result.flagged);
+        assert_eq!(result.categories, [ModerationCategory::Sexual]);
+    }
+}
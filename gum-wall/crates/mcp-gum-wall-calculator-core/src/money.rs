@@ -0,0 +1,177 @@
+//! Exact money arithmetic and currency conversion
+//!
+//! Prices arrive as `f64` from the catalog but are converted to `Decimal`
+//! before any arithmetic, so line subtotals and totals add up exactly.
+
+use anyhow::{anyhow, Result};
+use crate::config::PricingConfig;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Decimal places kept for per-piece prices after conversion
+pub const UNIT_PRICE_DP: u32 = 4;
+
+/// Convert a catalog price to a decimal
+pub fn decimal_from_f64(value: f64) -> Result<Decimal> {
+    Decimal::from_f64(value).ok_or_else(|| anyhow!("Invalid price: {}", value))
+}
+
+/// Convert a decimal amount back to `f64` for JSON output
+pub fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+/// Number of minor-unit digits for an ISO 4217 currency (e.g. 2 for cents)
+pub fn minor_unit_digits(currency: &str) -> u32 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// Format an amount with the currency's minor-unit digits, e.g. "12.50" USD or "1250" JPY
+pub fn format_money(amount: f64, currency: &str) -> String {
+    format!("{:.*}", minor_unit_digits(currency) as usize, amount)
+}
+
+/// Format a unit price with the currency's minor-unit digits, or up to
+/// [`UNIT_PRICE_DP`] when the price has more, e.g. "0.0525" or "1.50" USD
+pub fn format_unit_price(price: f64, currency: &str) -> String {
+    let digits = match Decimal::from_f64(price) {
+        Some(price) => round_unit_price(price).normalize().scale(),
+        None => UNIT_PRICE_DP,
+    };
+    format!("{:.*}", digits.max(minor_unit_digits(currency)) as usize, price)
+}
+
+/// Round to the currency's minor unit, half away from zero
+pub fn round_money(value: Decimal, currency: &str) -> Decimal {
+    value.round_dp_with_strategy(
+        minor_unit_digits(currency),
+        RoundingStrategy::MidpointAwayFromZero,
+    )
+}
+
+/// Round a per-piece price, half away from zero
+pub fn round_unit_price(value: Decimal) -> Decimal {
+    value.round_dp_with_strategy(UNIT_PRICE_DP, RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// Converts amounts into a single target currency using the configured rate table
+#[derive(Debug, Clone)]
+pub struct CurrencyConverter<'a> {
+    pricing: &'a PricingConfig,
+    target: String,
+}
+
+impl<'a> CurrencyConverter<'a> {
+    /// Create a converter to `target`, or to the base pricing currency when unset
+    pub fn new(pricing: &'a PricingConfig, target: Option<&str>) -> Result<Self> {
+        let target = target
+            .map(|c| c.trim().to_uppercase())
+            .unwrap_or_else(|| pricing.currency.clone());
+        let converter = CurrencyConverter { pricing, target };
+        converter.rate(&converter.target)?;
+        Ok(converter)
+    }
+
+    /// The currency amounts are converted to
+    pub fn currency(&self) -> &str {
+        &self.target
+    }
+
+    /// Convert `amount` in `from` (the base currency when unset) to the target currency
+    pub fn convert(&self, amount: Decimal, from: Option<&str>) -> Result<Decimal> {
+        let from = from
+            .map(|c| c.trim().to_uppercase())
+            .unwrap_or_else(|| self.pricing.currency.clone());
+        if from == self.target {
+            return Ok(amount);
+        }
+        Ok(amount * self.rate(&from)? / self.rate(&self.target)?)
+    }
+
+    /// Value of one unit of `currency` in the base currency
+    fn rate(&self, currency: &str) -> Result<Decimal> {
+        if currency == self.pricing.currency {
+            return Ok(Decimal::ONE);
+        }
+        self.pricing.rates.get(currency).copied().ok_or_else(|| {
+            anyhow!(
+                "No exchange rate configured for {} (base currency {})",
+                currency,
+                self.pricing.currency
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn pricing() -> PricingConfig {
+        PricingConfig {
+            currency: "USD".to_string(),
+            rates: [
+                ("EUR".to_string(), Decimal::from_str("1.10").unwrap()),
+                ("JPY".to_string(), Decimal::from_str("0.0067").unwrap()),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn test_round_money_uses_minor_units() {
+        let value = Decimal::from_str("12.345").unwrap();
+        assert_eq!(
+            round_money(value, "USD"),
+            Decimal::from_str("12.35").unwrap()
+        );
+        assert_eq!(round_money(value, "JPY"), Decimal::from_str("12").unwrap());
+        assert_eq!(
+            round_money(value, "KWD"),
+            Decimal::from_str("12.345").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_uses_minor_units() {
+        assert_eq!(format_money(12.5, "USD"), "12.50");
+        assert_eq!(format_money(1250.0, "JPY"), "1250");
+        assert_eq!(format_money(1.234, "KWD"), "1.234");
+        assert_eq!(format_unit_price(0.0525, "USD"), "0.0525");
+        assert_eq!(format_unit_price(1.5, "USD"), "1.50");
+        assert_eq!(format_unit_price(15.0, "JPY"), "15");
+        assert_eq!(format_unit_price(0.0308, "KWD"), "0.0308");
+    }
+
+    #[test]
+    fn test_convert_between_currencies() {
+        let pricing = pricing();
+        let to_usd = CurrencyConverter::new(&pricing, None).unwrap();
+        let eur = Decimal::from_str("2.00").unwrap();
+        assert_eq!(
+            to_usd.convert(eur, Some("eur")).unwrap(),
+            Decimal::from_str("2.2").unwrap()
+        );
+        assert_eq!(to_usd.convert(eur, None).unwrap(), eur);
+
+        let to_eur = CurrencyConverter::new(&pricing, Some("EUR")).unwrap();
+        let usd = Decimal::from_str("1.10").unwrap();
+        assert_eq!(to_eur.convert(usd, None).unwrap(), Decimal::ONE);
+
+        assert!(to_usd.convert(eur, Some("GBP")).is_err());
+        assert!(CurrencyConverter::new(&pricing, Some("GBP")).is_err());
+    }
+
+    #[test]
+    fn test_decimal_from_f64_is_exact_for_prices() {
+        let price = decimal_from_f64(0.07).unwrap();
+        assert_eq!(price * Decimal::from(3), Decimal::from_str("0.21").unwrap());
+    }
+}
@@ -0,0 +1,492 @@
+//! Placing gum orders through the AppSync `Order` model
+//!
+//! Orders are created from a calculate_gum_cost breakdown, priced again with
+//! the current catalog. The order ID is derived from the caller's idempotency
+//! key, so a retried request finds the existing order instead of creating a
+//! second one.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::config::{AppSyncConfig, GumWallConfig};
+use crate::context::AppContext;
+use crate::cost;
+use crate::error::GumWallError;
+use crate::graphql;
+use crate::money;
+use crate::types::{
+    CalculateCostInput, CalculateCostOutput, CreateOrderData, GetOrderData,
+    GumType, OrderRecord, PlaceOrderInput, PlaceOrderOutput, ShippingAddress,
+};
+use pmcp::{ServerBuilder, TypedTool};
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::sync::Arc;
+use validator::Validate;
+
+/// Fields returned for every `Order` record
+const ORDER_FIELDS: &str = "orderId items totalAmount status createdAt";
+
+/// Status of newly created orders
+const NEW_ORDER_STATUS: &str = "PENDING";
+
+/// Order ID for an idempotency key
+pub fn order_id(idempotency_key: &str) -> String {
+    format!("gw-{}", idempotency_key)
+}
+
+/// Check that a cost breakdown can be ordered and return its total
+///
+/// The breakdown comes back from the client, so the shopping list must be
+/// complete (no unpriced gum types) and add up to `total_cost`.
+pub fn checked_total(cost: &CalculateCostOutput) -> Result<Decimal> {
+    if cost.shopping_list.is_empty() {
+        return Err(GumWallError::InvalidInput(
+            "The cost breakdown has nothing to order".to_string(),
+        )
+        .into());
+    }
+    let unpriced: Vec<&str> = cost.warnings.iter().map(|w| w.gum_id.as_str()).collect();
+    if !unpriced.is_empty() {
+        return Err(GumWallError::InvalidInput(format!(
+            "Some gum types could not be priced ({}); recalculate the cost without them first",
+            unpriced.join(", ")
+        ))
+        .into());
+    }
+    if cost
+        .shopping_list
+        .iter()
+        .any(|item| item.quantity == 0 || item.pack_size == 0)
+    {
+        return Err(GumWallError::InvalidInput(
+            "Shopping list items need a pack size and quantity of at least 1".to_string(),
+        )
+        .into());
+    }
+
+    let mut total = Decimal::ZERO;
+    for item in &cost.shopping_list {
+        total += money::decimal_from_f64(item.subtotal)?;
+    }
+    let total = money::round_money(total, &cost.currency);
+    if total != money::round_money(money::decimal_from_f64(cost.total_cost)?, &cost.currency) {
+        return Err(GumWallError::InvalidInput(format!(
+            "The shopping list adds up to {} {}, not total_cost {}",
+            total, cost.currency, cost.total_cost
+        ))
+        .into());
+    }
+    Ok(total)
+}
+
+/// Price the purchase quantities of a breakdown again with the current catalog
+///
+/// The client's prices are not trusted: the order is rejected unless the
+/// repriced shopping list and total match the quoted ones.
+pub fn reprice(
+    cost: &CalculateCostOutput,
+    total: Decimal,
+    gum_types: &[GumType],
+    config: &GumWallConfig,
+) -> Result<CalculateCostOutput> {
+    // Spares are already in the purchase quantities
+    let input = CalculateCostInput {
+        gum_counts: cost
+            .breakdown
+            .iter()
+            .map(|item| (item.gum_id.clone(), item.purchase_quantity))
+            .collect(),
+        strict: true,
+        currency: Some(cost.currency.clone()),
+        overage_percent: Some(0.0),
+        min_spare_pieces: Some(0),
+        crew_size: None,
+        wall_area_m2: None,
+    };
+    let repriced = cost::calculate_cost(&input, gum_types, config)
+        .map_err(|e| GumWallError::InvalidInput(format!("Cannot reprice the order: {}", e)))?;
+    let repriced_total = money::round_money(
+        money::decimal_from_f64(repriced.total_cost)?,
+        &repriced.currency,
+    );
+    if repriced_total != total || order_items(&repriced) != order_items(cost) {
+        return Err(GumWallError::InvalidInput(format!(
+            "The quoted total of {} {} does not match the current price of {} {}; \
+            recalculate the cost and try again",
+            total, cost.currency, repriced_total, repriced.currency
+        ))
+        .into());
+    }
+    Ok(repriced)
+}
+
+/// The `items` JSON stored on the order
+fn order_items(cost: &CalculateCostOutput) -> serde_json::Value {
+    cost.shopping_list
+        .iter()
+        .map(|item| {
+            json!({
+                "gumId": item.gum_id,
+                "name": item.name,
+                "packSize": item.pack_size,
+                "quantity": item.quantity,
+                "packPrice": item.pack_price,
+                "subtotal": item.subtotal,
+                "currency": cost.currency
+            })
+        })
+        .collect()
+}
+
+/// The `shippingAddress` JSON stored on the order
+fn shipping_address(address: &ShippingAddress) -> serde_json::Value {
+    json!({
+        "name": address.name,
+        "line1": address.line1,
+        "line2": address.line2,
+        "city": address.city,
+        "region": address.region,
+        "postalCode": address.postal_code,
+        "country": address.country.to_uppercase()
+    })
+}
+
+/// Look up an order by ID
+pub async fn get_order(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    order_id: &str,
+) -> Result<Option<OrderRecord>> {
+    let query = format!(
+        "query GetOrder($orderId: ID!) {{ getOrder(orderId: $orderId) {{ {} }} }}",
+        ORDER_FIELDS
+    );
+    let data: GetOrderData = graphql::execute(
+        client,
+        &appsync.api_url,
+        appsync.api_key.expose(),
+        &query,
+        json!({ "orderId": order_id }),
+    )
+    .await?;
+    Ok(data.get_order)
+}
+
+/// Place the order described by `input`, or preview it when `dry_run` is set
+///
+/// The breakdown is priced again with `gum_types` first. An existing order
+/// with the same idempotency key is returned as a duplicate, provided it holds
+/// the same items and total.
+pub async fn place_order(
+    client: &reqwest::Client,
+    appsync: &AppSyncConfig,
+    input: &PlaceOrderInput,
+    gum_types: &[GumType],
+    config: &GumWallConfig,
+    now: DateTime<Utc>,
+) -> Result<PlaceOrderOutput> {
+    let total = checked_total(&input.cost)?;
+    let cost = reprice(&input.cost, total, gum_types, config)?;
+    let order_id = order_id(&input.idempotency_key);
+    let items = order_items(&cost);
+    let output = |status: &str, created_at: String, duplicate: bool| PlaceOrderOutput {
+        order_id: order_id.clone(),
+        status: status.to_string(),
+        created_at,
+        total_amount: money::to_f64(total),
+        currency: cost.currency.clone(),
+        items: cost.shopping_list.clone(),
+        dry_run: input.dry_run,
+        duplicate,
+        fallback_prices: false,
+    };
+
+    if input.dry_run {
+        return Ok(output(NEW_ORDER_STATUS, now.to_rfc3339(), false));
+    }
+
+    if let Some(existing) = get_order(client, appsync, &order_id).await? {
+        check_same_order(&existing, &items, total, &input.idempotency_key)?;
+        let status = existing.status.as_deref().unwrap_or(NEW_ORDER_STATUS);
+        return Ok(output(status, existing.created_at.clone(), true));
+    }
+
+    let query = format!(
+        "mutation CreateOrder($input: CreateOrderInput!) {{ createOrder(input: $input) {{ {} }} }}",
+        ORDER_FIELDS
+    );
+    // AWSJSON fields are sent as JSON strings
+    let created: Result<CreateOrderData> = graphql::execute(
+        client,
+        &appsync.api_url,
+        appsync.api_key.expose(),
+        &query,
+        json!({
+            "input": {
+                "orderId": order_id,
+                "items": items.to_string(),
+                "totalAmount": money::to_f64(total),
+                "status": NEW_ORDER_STATUS,
+                "shippingAddress": shipping_address(&input.shipping_address).to_string(),
+                "createdAt": now.to_rfc3339(),
+                "updatedAt": now.to_rfc3339()
+            }
+        }),
+    )
+    .await;
+
+    match created {
+        Ok(data) => {
+            let record = data.create_order;
+            let status = record.status.as_deref().unwrap_or(NEW_ORDER_STATUS);
+            Ok(output(status, record.created_at.clone(), false))
+        }
+        // A concurrent retry with the same key may have created the order first
+        Err(e) => match get_order(client, appsync, &order_id).await? {
+            Some(existing) => {
+                check_same_order(&existing, &items, total, &input.idempotency_key)?;
+                let status = existing.status.as_deref().unwrap_or(NEW_ORDER_STATUS);
+                Ok(output(status, existing.created_at.clone(), true))
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Make sure an existing order matches the one being placed with the same key
+fn check_same_order(
+    existing: &OrderRecord,
+    items: &serde_json::Value,
+    total: Decimal,
+    idempotency_key: &str,
+) -> Result<()> {
+    let stored_items = match &existing.items {
+        serde_json::Value::String(text) => serde_json::from_str(text)?,
+        other => other.clone(),
+    };
+    if &stored_items != items || existing.total_amount != money::to_f64(total) {
+        return Err(GumWallError::Conflict(format!(
+            "Idempotency key '{}' was already used for a different order",
+            idempotency_key
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Register the order tool on the server builder
+pub(crate) fn register_order_tools(
+    builder: ServerBuilder,
+    appsync: AppSyncConfig,
+    context: Arc<AppContext>,
+) -> ServerBuilder {
+    let appsync = Arc::new(appsync);
+
+    builder.tool(
+        "place_gum_order",
+        TypedTool::new("place_gum_order", move |input: PlaceOrderInput, _extra| {
+            let appsync = appsync.clone();
+            let context = context.clone();
+            Box::pin(async move {
+                input
+                    .validate()
+                    .map_err(|e| GumWallError::Validation(e.to_string()))?;
+                let gum_types = context
+                    .catalog
+                    .list_gum_types()
+                    .await
+                    .map_err(|e| GumWallError::CatalogUnavailable(e.to_string()))?;
+                tracing::info!(
+                    "Placing order '{}' (dry_run={})",
+                    order_id(&input.idempotency_key),
+                    input.dry_run
+                );
+
+                let mut output = place_order(
+                    &context.http,
+                    &appsync,
+                    &input,
+                    &gum_types,
+                    &context.config,
+                    Utc::now(),
+                )
+                .await
+                .map_err(|e| GumWallError::or_wrap(e, GumWallError::OrderFailed))?;
+                output.fallback_prices = context.catalog.is_fallback();
+
+                Ok(serde_json::to_value(output)
+                    .map_err(|e| GumWallError::Internal(e.to_string()))?)
+            })
+        })
+        .with_description(
+            "Order the gum on a calculate_gum_cost shopping list. Pass the full cost output, a \
+            shipping address and an idempotency key. The order is priced again with the current \
+            catalog and rejected if the total changed; retrying with the same key returns the \
+            existing order instead of creating a second one. Use dry_run to preview the order.",
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Secret;
+    use crate::graphql::get_fallback_gum_types;
+    use crate::test_support::MockServer;
+    use crate::types::CalculateCostInput;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn input(key: &str, dry_run: bool) -> PlaceOrderInput {
+        order_of(120, key, dry_run)
+    }
+
+    /// An order for `count` pieces of "big_red"
+    fn order_of(count: u32, key: &str, dry_run: bool) -> PlaceOrderInput {
+        let cost_input = CalculateCostInput {
+            gum_counts: [("big_red".to_string(), count)].into_iter().collect(),
+            strict: false,
+            currency: None,
+            overage_percent: None,
+            min_spare_pieces: None,
+            crew_size: None,
+            wall_area_m2: None,
+        };
+        PlaceOrderInput {
+            cost: cost::calculate_cost(&cost_input, &get_fallback_gum_types(), &config()).unwrap(),
+            shipping_address: ShippingAddress {
+                name: "Market Theater".to_string(),
+                line1: "1428 Post Alley".to_string(),
+                line2: None,
+                city: "Seattle".to_string(),
+                region: Some("WA".to_string()),
+                postal_code: "98101".to_string(),
+                country: "us".to_string(),
+            },
+            idempotency_key: key.to_string(),
+            dry_run,
+        }
+    }
+
+    fn config() -> GumWallConfig {
+        GumWallConfig::default()
+    }
+
+    /// Place `input` priced with the fallback catalog
+    async fn place(
+        client: &reqwest::Client,
+        appsync: &AppSyncConfig,
+        input: &PlaceOrderInput,
+    ) -> Result<PlaceOrderOutput> {
+        let gum_types = get_fallback_gum_types();
+        place_order(client, appsync, input, &gum_types, &config(), Utc::now()).await
+    }
+
+    /// Stub AppSync keeping orders in memory, like DynamoDB keyed by `orderId`
+    async fn mock_appsync() -> MockServer {
+        let orders: Mutex<HashMap<String, serde_json::Value>> = Mutex::new(HashMap::new());
+        MockServer::start(move |request| {
+            let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+            let variables = &body["variables"];
+            let query = body["query"].as_str().unwrap_or_default();
+            let mut orders = orders.lock().unwrap();
+
+            let data = if query.contains("getOrder") {
+                let order_id = variables["orderId"].as_str().unwrap();
+                json!({"getOrder": orders.get(order_id)})
+            } else if query.contains("createOrder") {
+                let input = variables["input"].clone();
+                let order_id = input["orderId"].as_str().unwrap().to_string();
+                if orders.contains_key(&order_id) {
+                    let error = json!({"data": null, "errors": [{"message": "The conditional request failed"}]});
+                    return (200, error.to_string());
+                }
+                orders.insert(order_id, input.clone());
+                json!({"createOrder": input})
+            } else {
+                return (400, "{}".to_string());
+            };
+            (200, json!({ "data": data }).to_string())
+        })
+        .await
+    }
+
+    fn appsync(server: &MockServer) -> AppSyncConfig {
+        AppSyncConfig {
+            api_url: server.url.clone(),
+            api_key: Secret::new("test-key"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_returns_existing_order() {
+        let server = mock_appsync().await;
+        let client = reqwest::Client::new();
+        let appsync = appsync(&server);
+
+        let first = place(&client, &appsync, &input("order-0001", false))
+            .await
+            .unwrap();
+        assert_eq!(first.order_id, "gw-order-0001");
+        assert_eq!(first.status, "PENDING");
+        assert_eq!(first.total_amount, 9.6);
+        assert!(!first.duplicate);
+
+        let retry = place(&client, &appsync, &input("order-0001", false))
+            .await
+            .unwrap();
+        assert!(retry.duplicate);
+        assert_eq!(retry.created_at, first.created_at);
+
+        // Same key, different items
+        let changed = order_of(240, "order-0001", false);
+        let error = place(&client, &appsync, &changed).await.unwrap_err();
+        let error = GumWallError::or_wrap(error, GumWallError::OrderFailed);
+        assert_eq!(error.code(), "conflict");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_does_not_call_appsync() {
+        let server = mock_appsync().await;
+        let output = place(&reqwest::Client::new(), &appsync(&server), &input("preview-1", true))
+            .await
+            .unwrap();
+        assert!(output.dry_run);
+        assert_eq!(output.items.len(), 1);
+        assert_eq!(server.hits(), 0);
+    }
+
+    #[test]
+    fn test_breakdown_must_add_up() {
+        let valid = input("order-0002", false);
+        assert!(valid.validate().is_ok());
+        assert!(checked_total(&valid.cost).is_ok());
+
+        let mut tampered = valid.clone();
+        tampered.cost.total_cost = 1.0;
+        assert!(checked_total(&tampered.cost).is_err());
+
+        let mut empty = valid.clone();
+        empty.cost.shopping_list.clear();
+        assert!(checked_total(&empty.cost).is_err());
+
+        let mut tampered = valid.clone();
+        tampered.cost.total_cost = 1.0;
+        tampered.cost.shopping_list[0].subtotal = 1.0;
+        tampered.cost.shopping_list[0].pack_price = 1.0 / 120.0;
+        let total = checked_total(&tampered.cost).unwrap();
+        let error = reprice(&tampered.cost, total, &get_fallback_gum_types(), &config());
+        let error = GumWallError::or_wrap(error.unwrap_err(), GumWallError::OrderFailed);
+        assert_eq!(error.code(), "invalid_input");
+
+        let total = checked_total(&valid.cost).unwrap();
+        let repriced = reprice(&valid.cost, total, &get_fallback_gum_types(), &config()).unwrap();
+        assert_eq!(order_items(&repriced), order_items(&valid.cost));
+
+        let bad_key = PlaceOrderInput {
+            idempotency_key: "short".to_string(),
+            ..valid
+        };
+        assert!(bad_key.validate().is_err());
+    }
+}
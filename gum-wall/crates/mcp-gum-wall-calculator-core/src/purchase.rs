@@ -0,0 +1,232 @@
+//! Pack-based purchasing: the cheapest way to buy a number of pieces
+//!
+//! Gum is sold in packs of different sizes. For each gum type the optimizer
+//! picks the combination of packs that covers the required pieces at the
+//! lowest cost, preferring fewer leftover pieces and then fewer packs when
+//! costs tie.
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use std::cmp::Reverse;
+
+/// Largest number of pieces planned for a single gum type
+pub const MAX_PIECES_PER_GUM: u32 = 1_000_000;
+
+/// A pack that can be bought
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackOffer {
+    /// Pieces per pack
+    pub pack_size: u32,
+    /// Price per pack
+    pub price: Decimal,
+}
+
+/// Packs of one size in a purchase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackSelection {
+    pub offer: PackOffer,
+    pub quantity: u32,
+}
+
+/// The packs chosen for one gum type
+#[derive(Debug, Clone, PartialEq)]
+pub struct Purchase {
+    /// Selected packs, largest pack size first
+    pub selections: Vec<PackSelection>,
+    /// Total pieces bought
+    pub pieces: u32,
+    /// Total cost of the packs
+    pub cost: Decimal,
+}
+
+impl Purchase {
+    /// Pieces bought beyond `required`
+    pub fn leftover(&self, required: u32) -> u32 {
+        self.pieces.saturating_sub(required)
+    }
+}
+
+/// Find the cheapest combination of packs covering at least `required` pieces
+///
+/// Offers with a zero pack size, more than [`MAX_PIECES_PER_GUM`] pieces or a
+/// negative price are ignored. Fails when no valid offer remains or
+/// `required` exceeds [`MAX_PIECES_PER_GUM`].
+pub fn cheapest_purchase(required: u32, offers: &[PackOffer]) -> Result<Purchase> {
+    let offers: Vec<PackOffer> = offers
+        .iter()
+        .copied()
+        .filter(|o| o.pack_size > 0 && o.pack_size <= MAX_PIECES_PER_GUM)
+        .filter(|o| o.price >= Decimal::ZERO)
+        .collect();
+    if offers.is_empty() {
+        return Err(anyhow!("No packs available"));
+    }
+    if required > MAX_PIECES_PER_GUM {
+        return Err(anyhow!(
+            "Cannot plan {} pieces of one gum type (maximum {})",
+            required,
+            MAX_PIECES_PER_GUM
+        ));
+    }
+    if required == 0 {
+        return Ok(Purchase {
+            selections: Vec::new(),
+            pieces: 0,
+            cost: Decimal::ZERO,
+        });
+    }
+
+    // A pack of at least `required` pieces covers it alone, and adding packs only
+    // costs more, so the best of those is the cheapest single pack.
+    let (large, small): (Vec<PackOffer>, Vec<PackOffer>) =
+        offers.into_iter().partition(|o| o.pack_size >= required);
+    let single = large
+        .into_iter()
+        .min_by(|a, b| (a.price, a.pack_size).cmp(&(b.price, b.pack_size)))
+        .map(|offer| Purchase {
+            selections: vec![PackSelection { offer, quantity: 1 }],
+            pieces: offer.pack_size,
+            cost: offer.price,
+        });
+    let combined = combine_packs(required, &small);
+
+    // Cheapest total, then fewest pieces (least leftover), then fewest packs
+    [single, combined]
+        .into_iter()
+        .flatten()
+        .min_by_key(|p| {
+            (
+                p.cost,
+                p.pieces,
+                p.selections.iter().map(|s| s.quantity).sum::<u32>(),
+            )
+        })
+        .ok_or_else(|| anyhow!("No combination of packs covers {} pieces", required))
+}
+
+/// Cheapest combination of packs smaller than `required` covering `required` pieces
+fn combine_packs(required: u32, offers: &[PackOffer]) -> Option<Purchase> {
+    // best[c] = cheapest (cost, packs) buying exactly c pieces, with the last pack used.
+    // Any optimal purchase overshoots by less than the largest pack, so that bounds the table;
+    // every pack is smaller than `required`, so the table has fewer than 2 * required entries.
+    let largest = offers.iter().map(|o| o.pack_size).max()? as usize;
+    let limit = required as usize + largest - 1;
+    let mut best: Vec<Option<(Decimal, u32, usize)>> = vec![None; limit + 1];
+    best[0] = Some((Decimal::ZERO, 0, usize::MAX));
+
+    for pieces in 1..=limit {
+        for (index, offer) in offers.iter().enumerate() {
+            let size = offer.pack_size as usize;
+            if size > pieces {
+                continue;
+            }
+            let Some((cost, packs, _)) = best[pieces - size] else {
+                continue;
+            };
+            let candidate = (cost + offer.price, packs + 1, index);
+            let better = match best[pieces] {
+                None => true,
+                Some((best_cost, best_packs, _)) => {
+                    (candidate.0, candidate.1) < (best_cost, best_packs)
+                }
+            };
+            if better {
+                best[pieces] = Some(candidate);
+            }
+        }
+    }
+
+    // Cheapest total, then fewest pieces (least leftover), then fewest packs
+    let (pieces, (cost, _, _)) = (required as usize..=limit)
+        .filter_map(|pieces| best[pieces].map(|entry| (pieces, entry)))
+        .min_by(|(a_pieces, a), (b_pieces, b)| (a.0, *a_pieces, a.1).cmp(&(b.0, *b_pieces, b.1)))?;
+
+    let mut quantities = vec![0u32; offers.len()];
+    let mut remaining = pieces;
+    while remaining > 0 {
+        let (_, _, index) = best[remaining].expect("reachable piece counts have an entry");
+        quantities[index] += 1;
+        remaining -= offers[index].pack_size as usize;
+    }
+
+    let mut selections: Vec<PackSelection> = offers
+        .iter()
+        .zip(quantities)
+        .filter(|(_, quantity)| *quantity > 0)
+        .map(|(offer, quantity)| PackSelection {
+            offer: *offer,
+            quantity,
+        })
+        .collect();
+    selections.sort_by_key(|s| Reverse(s.offer.pack_size));
+
+    Some(Purchase {
+        selections,
+        pieces: pieces as u32,
+        cost,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(pack_size: u32, cents: i64) -> PackOffer {
+        PackOffer {
+            pack_size,
+            price: Decimal::new(cents, 2),
+        }
+    }
+
+    #[test]
+    fn test_prefers_cheaper_large_packs() {
+        // 5-packs at $0.30 and 100-packs at $4.00
+        let purchase = cheapest_purchase(230, &[offer(5, 30), offer(100, 400)]).unwrap();
+        assert_eq!(purchase.cost, Decimal::new(980, 2));
+        assert_eq!(purchase.pieces, 230);
+        assert_eq!(purchase.selections[0].quantity, 2);
+        assert_eq!(purchase.selections[1].quantity, 6);
+    }
+
+    #[test]
+    fn test_rounds_up_to_cheapest_cover() {
+        // Buying a 100-pack is cheaper than 19 five-packs
+        let purchase = cheapest_purchase(93, &[offer(5, 30), offer(100, 400)]).unwrap();
+        assert_eq!(purchase.cost, Decimal::new(400, 2));
+        assert_eq!(purchase.leftover(93), 7);
+        assert_eq!(purchase.selections.len(), 1);
+        assert_eq!(purchase.selections[0].quantity, 1);
+    }
+
+    #[test]
+    fn test_ties_prefer_fewer_leftovers() {
+        // 12 pieces: one 15-pack and three 4-packs both cost $1.20
+        let purchase = cheapest_purchase(12, &[offer(4, 40), offer(15, 120)]).unwrap();
+        assert_eq!(purchase.pieces, 12);
+        assert_eq!(purchase.selections[0].offer.pack_size, 4);
+    }
+
+    #[test]
+    fn test_invalid_offers() {
+        assert!(cheapest_purchase(10, &[]).is_err());
+        assert!(cheapest_purchase(10, &[offer(0, 10)]).is_err());
+        assert!(cheapest_purchase(0, &[offer(5, 30)])
+            .unwrap()
+            .selections
+            .is_empty());
+    }
+
+    #[test]
+    fn test_huge_packs() {
+        // A catalog pack larger than any plan is ignored instead of sizing the table
+        let purchase = cheapest_purchase(10, &[offer(5, 30), offer(2_000_000_000, 100)]).unwrap();
+        assert_eq!(purchase.pieces, 10);
+        assert_eq!(purchase.cost, Decimal::new(60, 2));
+        assert!(cheapest_purchase(10, &[offer(u32::MAX, 100)]).is_err());
+
+        // A pack larger than needed but within the limit is bought once
+        let purchase = cheapest_purchase(10, &[offer(5, 300), offer(500_000, 100)]).unwrap();
+        assert_eq!(purchase.pieces, 500_000);
+        assert_eq!(purchase.selections[0].quantity, 1);
+    }
+}
@@ -1,18 +1,36 @@
 //! Color quantization - mapping image pixels to available gum colors
 
-use crate::types::{GumType, Rgb};
+use crate::types::{BoundingBox, GumType, ImageAnalysis, KeyRegion, Rgb};
 use image::{DynamicImage, GenericImageView, imageops::FilterType};
 use std::collections::HashMap;
 
 /// Calculate squared Euclidean distance between two RGB colors
 /// Using squared distance avoids sqrt and is fine for comparison
-fn color_distance_squared(a: Rgb, b: Rgb) -> u32 {
+pub(crate) fn color_distance_squared(a: Rgb, b: Rgb) -> u32 {
     let dr = a.r as i32 - b.r as i32;
     let dg = a.g as i32 - b.g as i32;
     let db = a.b as i32 - b.b as i32;
     (dr * dr + dg * dg + db * db) as u32
 }
 
+/// Find the available gum types closest to a color, nearest first
+///
+/// Returns up to `limit` gum types with their squared color distance.
+pub fn nearest_available_gums(
+    target: Rgb,
+    gum_types: &[GumType],
+    limit: usize,
+) -> Vec<(&GumType, u32)> {
+    let mut candidates: Vec<(&GumType, u32)> = gum_types
+        .iter()
+        .filter(|g| g.is_available)
+        .filter_map(|g| g.to_rgb().map(|rgb| (g, color_distance_squared(target, rgb))))
+        .collect();
+    candidates.sort_by_key(|(_, distance)| *distance);
+    candidates.truncate(limit);
+    candidates
+}
+
 /// Find the nearest gum color for a given RGB pixel
 fn find_nearest_gum<'a>(pixel: Rgb, palette: &'a [(GumType, Rgb)]) -> &'a GumType {
     palette
@@ -37,20 +55,32 @@ pub struct QuantizationResult {
 
 /// Quantize an image to the available gum color palette
 ///
+/// Gum types that are not available for purchase are left out of the palette.
+///
+/// The vision provider's analysis, when given, guides the result. With a focal
+/// point the image is cropped to the wall's aspect ratio around it instead of
+/// being stretched to fit. In key regions each cell leans toward the most
+/// distinctive pixel it covers, in proportion to the region's importance, so
+/// thin features such as eyes or outlines survive the downscale instead of
+/// being averaged away.
+///
 /// # Arguments
 /// * `image` - The source image
 /// * `gum_types` - Available gum colors
 /// * `grid_cols` - Number of columns in the output grid (gums horizontally)
 /// * `grid_rows` - Number of rows in the output grid (gums vertically)
+/// * `analysis` - Structured analysis from the vision provider
 pub fn quantize_image(
     image: &DynamicImage,
     gum_types: &[GumType],
     grid_cols: u32,
     grid_rows: u32,
+    analysis: Option<&ImageAnalysis>,
 ) -> QuantizationResult {
     // Build palette with pre-computed RGB values
     let palette: Vec<(GumType, Rgb)> = gum_types
         .iter()
+        .filter(|g| g.is_available)
         .filter_map(|g| g.to_rgb().map(|rgb| (g.clone(), rgb)))
         .collect();
 
@@ -64,8 +94,20 @@ pub fn quantize_image(
         };
     }
 
+    // Crop to the wall's aspect ratio around the focal point, if there is one
+    let (crop_x, crop_y, crop_width, crop_height) = match analysis.and_then(|a| a.focal_point) {
+        Some(focus) => focal_crop(image.width(), image.height(), grid_cols, grid_rows, &focus),
+        None => (0, 0, image.width(), image.height()),
+    };
+    let source = image.crop_imm(crop_x, crop_y, crop_width, crop_height);
+    let key_regions: &[KeyRegion] = analysis.map(|a| a.key_regions.as_slice()).unwrap_or_default();
+    let detail = (!key_regions.is_empty()).then(|| source.to_rgb8());
+    let (image_width, image_height) = (image.width() as f64, image.height() as f64);
+    let cell_width = crop_width as f64 / grid_cols as f64;
+    let cell_height = crop_height as f64 / grid_rows as f64;
+
     // Resize image to grid dimensions using high-quality sampling
-    let resized = image.resize_exact(grid_cols, grid_rows, FilterType::Lanczos3);
+    let resized = source.resize_exact(grid_cols, grid_rows, FilterType::Lanczos3);
 
     // Track which gum types are used and their indices
     let mut gum_to_index: HashMap<String, u8> = HashMap::new();
@@ -81,12 +123,27 @@ pub fn quantize_image(
 
         for x in 0..grid_cols {
             let pixel = resized.get_pixel(x, y);
-            let pixel_rgb = Rgb {
+            let mut pixel_rgb = Rgb {
                 r: pixel[0],
                 g: pixel[1],
                 b: pixel[2],
             };
 
+            // Bring out detail in key regions, judged at the cell center
+            if let Some(detail) = &detail {
+                let center_x = (crop_x as f64 + (x as f64 + 0.5) * cell_width) / image_width;
+                let center_y = (crop_y as f64 + (y as f64 + 0.5) * cell_height) / image_height;
+                let weight = key_regions
+                    .iter()
+                    .filter(|region| region.bbox.contains(center_x, center_y))
+                    .map(|region| region.importance)
+                    .fold(0.0, f64::max);
+                if weight > 0.0 {
+                    let block = cell_block(x, y, grid_cols, grid_rows, detail.dimensions());
+                    pixel_rgb = emphasize_detail(pixel_rgb, detail, block, weight);
+                }
+            }
+
             // Find nearest gum color
             let nearest_gum = find_nearest_gum(pixel_rgb, &palette);
 
@@ -116,6 +173,85 @@ pub fn quantize_image(
     }
 }
 
+/// Largest crop with the wall's aspect ratio, centered on the focal point where possible
+///
+/// Returns `(x, y, width, height)` in pixels.
+fn focal_crop(
+    width: u32,
+    height: u32,
+    grid_cols: u32,
+    grid_rows: u32,
+    focus: &BoundingBox,
+) -> (u32, u32, u32, u32) {
+    let target = grid_cols.max(1) as f64 / grid_rows.max(1) as f64;
+    let (crop_width, crop_height) = if width as f64 / height as f64 > target {
+        let crop_width = (height as f64 * target).round().max(1.0) as u32;
+        (crop_width.min(width), height)
+    } else {
+        let crop_height = (width as f64 / target).round().max(1.0) as u32;
+        (width, crop_height.min(height))
+    };
+
+    let (focus_x, focus_y) = focus.center();
+    let x = (focus_x * width as f64 - crop_width as f64 / 2.0)
+        .round()
+        .clamp(0.0, (width - crop_width) as f64);
+    let y = (focus_y * height as f64 - crop_height as f64 / 2.0)
+        .round()
+        .clamp(0.0, (height - crop_height) as f64);
+    (x as u32, y as u32, crop_width, crop_height)
+}
+
+/// Pixels of the source image covered by a grid cell, as `(x0, y0, x1, y1)` (end exclusive)
+fn cell_block(
+    x: u32,
+    y: u32,
+    grid_cols: u32,
+    grid_rows: u32,
+    (width, height): (u32, u32),
+) -> (u32, u32, u32, u32) {
+    let span = |index: u32, cells: u32, pixels: u32| {
+        let start = (index as u64 * pixels as u64 / cells as u64) as u32;
+        let end = ((index as u64 + 1) * pixels as u64 / cells as u64) as u32;
+        let start = start.min(pixels - 1);
+        (start, end.clamp(start + 1, pixels))
+    };
+    let (x0, x1) = span(x, grid_cols, width);
+    let (y0, y1) = span(y, grid_rows, height);
+    (x0, y0, x1, y1)
+}
+
+/// Move a cell's color toward the pixel in its block that differs most from it
+///
+/// `weight` runs from 0 (keep the cell color) to 1 (use the distinctive pixel).
+fn emphasize_detail(
+    cell: Rgb,
+    image: &image::RgbImage,
+    (x0, y0, x1, y1): (u32, u32, u32, u32),
+    weight: f64,
+) -> Rgb {
+    let mut distinctive = cell;
+    let mut best = 0;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let [r, g, b] = image.get_pixel(x, y).0;
+            let rgb = Rgb { r, g, b };
+            let distance = color_distance_squared(cell, rgb);
+            if distance > best {
+                best = distance;
+                distinctive = rgb;
+            }
+        }
+    }
+
+    let blend = |from: u8, to: u8| (from as f64 + weight * (to as f64 - from as f64)).round() as u8;
+    Rgb {
+        r: blend(cell.r, distinctive.r),
+        g: blend(cell.g, distinctive.g),
+        b: blend(cell.b, distinctive.b),
+    }
+}
+
 /// Load an image from bytes
 pub fn load_image_from_bytes(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
     image::load_from_memory(bytes).map_err(|e| anyhow::anyhow!("Failed to load image: {}", e))
@@ -135,6 +271,9 @@ mod tests {
                 brand: None,
                 flavor: None,
                 is_available: true,
+                currency: None,
+                packs: Vec::new(),
+                properties: Default::default(),
             },
             GumType {
                 gum_id: "green".to_string(),
@@ -144,6 +283,9 @@ mod tests {
                 brand: None,
                 flavor: None,
                 is_available: true,
+                currency: None,
+                packs: Vec::new(),
+                properties: Default::default(),
             },
             GumType {
                 gum_id: "blue".to_string(),
@@ -153,6 +295,9 @@ mod tests {
                 brand: None,
                 flavor: None,
                 is_available: true,
+                currency: None,
+                packs: Vec::new(),
+                properties: Default::default(),
             },
         ]
     }
@@ -202,7 +347,7 @@ mod tests {
         });
         let dynamic_img = DynamicImage::ImageRgb8(img);
 
-        let result = quantize_image(&dynamic_img, &palette, 2, 2);
+        let result = quantize_image(&dynamic_img, &palette, 2, 2, None);
 
         assert_eq!(result.rows, 2);
         assert_eq!(result.cols, 2);
@@ -216,4 +361,82 @@ mod tests {
         let total: u32 = result.gum_counts.values().sum();
         assert_eq!(total, 4);
     }
+
+    #[test]
+    fn test_unavailable_gums_excluded() {
+        let mut palette = create_test_palette();
+        palette[0].is_available = false; // red
+
+        let img = image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0]));
+        let result = quantize_image(&DynamicImage::ImageRgb8(img), &palette, 2, 2, None);
+        assert!(!result.gum_counts.contains_key("red"));
+
+        let nearest = nearest_available_gums(Rgb { r: 255, g: 0, b: 0 }, &palette, 5);
+        assert_eq!(nearest.len(), 2);
+        assert!(nearest.iter().all(|(g, _)| g.gum_id != "red"));
+    }
+
+    fn analysis(focal_point: Option<BoundingBox>, key_regions: Vec<KeyRegion>) -> ImageAnalysis {
+        ImageAnalysis {
+            subject: "test".to_string(),
+            description: "test".to_string(),
+            focal_point,
+            key_regions,
+            suggested_palette: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_focal_point_crop() {
+        let right = BoundingBox { x: 0.8, y: 0.0, width: 0.2, height: 1.0 };
+        // A wide image on a square wall keeps the full height around the focal point
+        assert_eq!(focal_crop(400, 200, 10, 10, &right), (200, 0, 200, 200));
+        let center = BoundingBox { x: 0.4, y: 0.4, width: 0.2, height: 0.2 };
+        assert_eq!(focal_crop(200, 400, 10, 5, &center), (0, 150, 200, 100));
+
+        // Left half red, right half blue: a one-gum wall shows the focal side
+        let img = image::RgbImage::from_fn(4, 2, |x, _| {
+            if x < 2 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let img = DynamicImage::ImageRgb8(img);
+        let palette = create_test_palette();
+        let guided = analysis(Some(right), Vec::new());
+        let result = quantize_image(&img, &palette, 1, 1, Some(&guided));
+        assert_eq!(result.legend["1"].gum_id, "blue");
+    }
+
+    #[test]
+    fn test_key_regions_keep_detail() {
+        // A one-pixel red line on blue is averaged away when downscaled
+        let img = image::RgbImage::from_fn(40, 40, |x, _| {
+            if x == 25 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let img = DynamicImage::ImageRgb8(img);
+        let palette = create_test_palette();
+        let plain = quantize_image(&img, &palette, 4, 4, None);
+        assert!(!plain.gum_counts.contains_key("red"));
+
+        let region = KeyRegion {
+            label: "line".to_string(),
+            bbox: BoundingBox { x: 0.5, y: 0.0, width: 0.5, height: 0.5 },
+            importance: 1.0,
+        };
+        let guided = analysis(None, vec![region]);
+        let result = quantize_image(&img, &palette, 4, 4, Some(&guided));
+        // Only the two cells of the line's column inside the region turn red
+        assert_eq!(result.gum_counts["red"], 2);
+        let red = result.legend.iter().find(|(_, g)| g.gum_id == "red").unwrap().0;
+        let red: u8 = red.parse().unwrap();
+        assert_eq!(result.grid[0][2], red);
+        assert_eq!(result.grid[1][2], red);
+        assert_ne!(result.grid[2][2], red);
+    }
 }
@@ -0,0 +1,561 @@
+//! Formal quotes: materials, volume discount, shipping, labor and tax
+//!
+//! A quote starts from the cost calculation, then applies the configured
+//! quote settings and renders the result as Markdown or HTML.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use crate::config::GumWallConfig;
+use crate::cost;
+use crate::money::{self, CurrencyConverter};
+use crate::stats::ProjectTotals;
+use crate::types::{
+    GenerateQuoteInput, GenerateQuoteOutput, GumType, Quote, QuoteFormat, QuoteLine,
+};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Build a quote for the project in `input`
+///
+/// Set `fallback_prices` when `gum_types` is the built-in fallback list; the
+/// quote is then marked and the document says its prices may be out of date.
+pub fn generate_quote(
+    input: &GenerateQuoteInput,
+    gum_types: &[GumType],
+    fallback_prices: bool,
+    config: &GumWallConfig,
+    now: DateTime<Utc>,
+) -> Result<GenerateQuoteOutput> {
+    let costs = cost::calculate_cost(&input.cost, gum_types, config)?;
+    let converter = CurrencyConverter::new(&config.pricing, Some(&costs.currency))?;
+    let currency = converter.currency();
+    let settings = &config.quote;
+
+    // One line per pack size on the shopping list
+    let mut lines: Vec<QuoteLine> = costs
+        .shopping_list
+        .iter()
+        .map(|item| QuoteLine {
+            description: if item.pack_size == 1 {
+                format!("{} (per piece)", item.name)
+            } else {
+                format!("{} ({}-pack)", item.name, item.pack_size)
+            },
+            quantity: item.quantity,
+            unit_price: item.pack_price,
+            amount: item.subtotal,
+        })
+        .collect();
+
+    // Volume discount on materials: the highest tier reached
+    let materials = money::decimal_from_f64(costs.total_cost)?;
+    let mut discount_percent = Decimal::ZERO;
+    for tier in &settings.discount_tiers {
+        if materials >= converter.convert(tier.min_subtotal, None)?
+            && tier.percent > discount_percent
+        {
+            discount_percent = tier.percent;
+        }
+    }
+    let discount = money::round_money(
+        materials * discount_percent / Decimal::ONE_HUNDRED,
+        currency,
+    );
+    let discounted = materials - discount;
+
+    // Shipping by the weight of everything bought, including spares and leftovers
+    let gum_map: HashMap<&str, &GumType> =
+        gum_types.iter().map(|g| (g.gum_id.as_str(), g)).collect();
+    let mut shipped = ProjectTotals::default();
+    for item in &costs.breakdown {
+        shipped.add(
+            gum_map.get(item.gum_id.as_str()).copied(),
+            item.pieces_purchased,
+            &config.gum_defaults,
+        );
+    }
+    let shipping_weight_kg = shipped.weight_g.round() / 1000.0;
+    let free_shipping = match settings.free_shipping_over {
+        Some(threshold) => discounted >= converter.convert(threshold, None)?,
+        None => false,
+    };
+    let shipping = if free_shipping || shipped.pieces == 0 {
+        Decimal::ZERO
+    } else {
+        let base = converter.convert(settings.shipping_base, None)?;
+        let per_kg = converter.convert(settings.shipping_per_kg, None)?;
+        money::round_money(
+            base + per_kg * money::decimal_from_f64(shipping_weight_kg)?,
+            currency,
+        )
+    };
+
+    let labor = if let (true, Some(labor)) = (input.include_labor, &costs.labor) {
+        lines.push(QuoteLine {
+            description: format!(
+                "Installation labor ({} person-hours, crew of {}, {} days)",
+                labor.person_hours, labor.crew_size, labor.days
+            ),
+            quantity: 1,
+            unit_price: labor.labor_cost,
+            amount: labor.labor_cost,
+        });
+        money::decimal_from_f64(labor.labor_cost)?
+    } else {
+        Decimal::ZERO
+    };
+
+    let mut taxable = discounted;
+    if settings.tax_shipping {
+        taxable += shipping;
+    }
+    if settings.tax_labor {
+        taxable += labor;
+    }
+    let tax = money::round_money(taxable * settings.tax_rate, currency);
+    let total = discounted + shipping + labor + tax;
+
+    let quote = Quote {
+        quote_id: quote_id(now),
+        created_at: now.to_rfc3339(),
+        valid_until: (now + Duration::days(settings.validity_days as i64))
+            .format("%Y-%m-%d")
+            .to_string(),
+        client_name: input.client_name.clone(),
+        project_name: input.project_name.clone(),
+        currency: currency.to_string(),
+        lines,
+        materials_subtotal: money::to_f64(materials),
+        discount_percent: money::to_f64(discount_percent),
+        discount: money::to_f64(discount),
+        shipping_weight_kg,
+        shipping: money::to_f64(shipping),
+        labor: money::to_f64(labor),
+        taxable_amount: money::to_f64(taxable),
+        tax_rate: money::to_f64(settings.tax_rate),
+        tax: money::to_f64(tax),
+        total: money::to_f64(total),
+        warnings: costs.warnings,
+        labor_warning: if input.include_labor {
+            costs.labor_warning
+        } else {
+            None
+        },
+        fallback_prices,
+    };
+
+    let document = match input.format {
+        QuoteFormat::Markdown => render_markdown(&quote),
+        QuoteFormat::Html => render_html(&quote),
+    };
+
+    Ok(GenerateQuoteOutput {
+        quote,
+        format: input.format,
+        document,
+    })
+}
+
+/// Quote ID from the date and a random suffix, e.g. "Q-20260101-1A2B3C4D"
+fn quote_id(now: DateTime<Utc>) -> String {
+    let suffix = uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase();
+    format!("Q-{}-{}", now.format("%Y%m%d"), suffix)
+}
+
+/// Shown on quotes priced with the built-in fallback list
+const FALLBACK_PRICES_NOTE: &str =
+    "Prices are from the built-in fallback list and may not match current prices.";
+
+/// Summary rows shown under the line items: (label, amount)
+fn summary_rows(quote: &Quote) -> Vec<(String, f64)> {
+    let mut rows = vec![("Materials".to_string(), quote.materials_subtotal)];
+    if quote.discount > 0.0 {
+        rows.push((
+            format!("Volume discount ({}%)", quote.discount_percent),
+            -quote.discount,
+        ));
+    }
+    rows.push((
+        format!("Shipping ({} kg)", quote.shipping_weight_kg),
+        quote.shipping,
+    ));
+    if quote.labor > 0.0 {
+        rows.push(("Installation labor".to_string(), quote.labor));
+    }
+    rows.push((format!("Tax ({}%)", percent(quote.tax_rate)), quote.tax));
+    rows
+}
+
+/// Render the quote as a Markdown document
+pub fn render_markdown(quote: &Quote) -> String {
+    let mut doc = format!("# Gum Wall Quote {}\n\n", quote.quote_id);
+    if let Some(project) = &quote.project_name {
+        doc.push_str(&format!("**Project:** {}  \n", escape_markdown(project)));
+    }
+    if let Some(client) = &quote.client_name {
+        doc.push_str(&format!("**Client:** {}  \n", escape_markdown(client)));
+    }
+    doc.push_str(&format!(
+        "**Date:** {}  \n**Valid until:** {}\n\n",
+        &quote.created_at[..10],
+        quote.valid_until
+    ));
+    if quote.fallback_prices {
+        doc.push_str(&format!("> **Note:** {}\n\n", FALLBACK_PRICES_NOTE));
+    }
+
+    doc.push_str("| Item | Qty | Unit price | Amount |\n|---|---:|---:|---:|\n");
+    let currency = quote.currency.as_str();
+    for line in &quote.lines {
+        doc.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            escape_markdown(&line.description),
+            line.quantity,
+            money::format_unit_price(line.unit_price, currency),
+            money::format_money(line.amount, currency)
+        ));
+    }
+    doc.push('\n');
+
+    for (label, amount) in summary_rows(quote) {
+        doc.push_str(&format!(
+            "- {}: {} {}\n",
+            label,
+            money::format_money(amount, currency),
+            currency
+        ));
+    }
+    doc.push_str(&format!(
+        "\n**Total: {} {}**\n",
+        money::format_money(quote.total, currency),
+        currency
+    ));
+
+    let not_included = not_included(quote);
+    if !not_included.is_empty() {
+        doc.push_str("\n## Not included\n\n");
+        for message in not_included {
+            doc.push_str(&format!("- {}\n", escape_markdown(message)));
+        }
+    }
+    doc
+}
+
+/// Render the quote as a standalone HTML document
+pub fn render_html(quote: &Quote) -> String {
+    let mut doc = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Gum Wall Quote {id}</title>\n</head>\n<body>\n<h1>Gum Wall Quote {id}</h1>\n",
+        id = escape_html(&quote.quote_id)
+    );
+    if let Some(project) = &quote.project_name {
+        doc.push_str(&format!(
+            "<p><strong>Project:</strong> {}</p>\n",
+            escape_html(project)
+        ));
+    }
+    if let Some(client) = &quote.client_name {
+        doc.push_str(&format!(
+            "<p><strong>Client:</strong> {}</p>\n",
+            escape_html(client)
+        ));
+    }
+    doc.push_str(&format!(
+        "<p><strong>Date:</strong> {}<br><strong>Valid until:</strong> {}</p>\n",
+        &quote.created_at[..10],
+        quote.valid_until
+    ));
+    if quote.fallback_prices {
+        doc.push_str(&format!(
+            "<p><strong>Note:</strong> {}</p>\n",
+            FALLBACK_PRICES_NOTE
+        ));
+    }
+
+    doc.push_str("<table>\n<tr><th>Item</th><th>Qty</th><th>Unit price</th><th>Amount</th></tr>\n");
+    let currency = quote.currency.as_str();
+    for line in &quote.lines {
+        doc.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&line.description),
+            line.quantity,
+            money::format_unit_price(line.unit_price, currency),
+            money::format_money(line.amount, currency)
+        ));
+    }
+    for (label, amount) in summary_rows(quote) {
+        doc.push_str(&format!(
+            "<tr><td colspan=\"3\">{}</td><td>{}</td></tr>\n",
+            escape_html(&label),
+            money::format_money(amount, currency)
+        ));
+    }
+    doc.push_str(&format!(
+        "<tr><th colspan=\"3\">Total ({})</th><th>{}</th></tr>\n</table>\n",
+        escape_html(currency),
+        money::format_money(quote.total, currency)
+    ));
+
+    let not_included = not_included(quote);
+    if !not_included.is_empty() {
+        doc.push_str("<h2>Not included</h2>\n<ul>\n");
+        for message in not_included {
+            doc.push_str(&format!("<li>{}</li>\n", escape_html(message)));
+        }
+        doc.push_str("</ul>\n");
+    }
+    doc.push_str("</body>\n</html>\n");
+    doc
+}
+
+/// Messages for the gum and labor that could not be quoted
+fn not_included(quote: &Quote) -> Vec<&str> {
+    quote
+        .warnings
+        .iter()
+        .map(|warning| warning.message.as_str())
+        .chain(quote.labor_warning.as_deref())
+        .collect()
+}
+
+/// A fraction as a percentage with at most two decimals, e.g. 0.0825 -> "8.25"
+fn percent(fraction: f64) -> String {
+    ((fraction * 10_000.0).round() / 100.0).to_string()
+}
+
+/// Escape text so Markdown shows it literally, on one line and inside table cells
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\r' | '\n' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DiscountTier;
+    use crate::types::CalculateCostInput;
+    use chrono::TimeZone;
+
+    fn gum_types() -> Vec<GumType> {
+        vec![GumType {
+            gum_id: "red".to_string(),
+            name: "Red <Cherry>".to_string(),
+            hex_color: "#FF0000".to_string(),
+            price_per_piece: 0.10,
+            brand: None,
+            flavor: None,
+            is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: Default::default(),
+        }]
+    }
+
+    fn input(count: u32, format: QuoteFormat) -> GenerateQuoteInput {
+        GenerateQuoteInput {
+            cost: CalculateCostInput {
+                gum_counts: [("red".to_string(), count)].into_iter().collect(),
+                strict: false,
+                currency: None,
+                overage_percent: None,
+                min_spare_pieces: None,
+                crew_size: None,
+                wall_area_m2: None,
+            },
+            client_name: Some("Pike Place".to_string()),
+            project_name: None,
+            include_labor: false,
+            format,
+        }
+    }
+
+    fn config() -> GumWallConfig {
+        let mut config = GumWallConfig::default();
+        config.quote.tax_rate = Decimal::new(10, 2);
+        config.quote.shipping_base = Decimal::new(5, 0);
+        config.quote.shipping_per_kg = Decimal::new(2, 0);
+        config.quote.discount_tiers = vec![
+            DiscountTier {
+                min_subtotal: Decimal::new(50, 0),
+                percent: Decimal::new(5, 0),
+            },
+            DiscountTier {
+                min_subtotal: Decimal::new(100, 0),
+                percent: Decimal::new(10, 0),
+            },
+        ];
+        config
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_quote_totals() {
+        // 1000 pieces at $0.10 = $100, 10% tier, 3 kg shipped
+        let output = generate_quote(
+            &input(1000, QuoteFormat::Markdown),
+            &gum_types(),
+            false,
+            &config(),
+            now(),
+        )
+        .unwrap();
+        let quote = &output.quote;
+        assert_eq!(quote.materials_subtotal, 100.0);
+        assert_eq!(quote.discount, 10.0);
+        assert_eq!(quote.shipping_weight_kg, 3.0);
+        assert_eq!(quote.shipping, 11.0);
+        assert_eq!(quote.taxable_amount, 90.0);
+        assert_eq!(quote.tax, 9.0);
+        assert_eq!(quote.total, 110.0);
+        assert_eq!(quote.valid_until, "2026-03-31");
+        assert!(quote.quote_id.starts_with("Q-20260301-"));
+    }
+
+    #[test]
+    fn test_free_shipping_and_labor() {
+        let mut config = config();
+        config.quote.free_shipping_over = Some(Decimal::new(40, 0));
+        let mut request = input(500, QuoteFormat::Markdown);
+        request.include_labor = true;
+
+        let quote = generate_quote(&request, &gum_types(), false, &config, now())
+            .unwrap()
+            .quote;
+        assert_eq!(quote.discount_percent, 5.0);
+        assert_eq!(quote.shipping, 0.0);
+        assert!(quote.labor > 0.0);
+        // Labor is not taxed by default
+        assert_eq!(quote.taxable_amount, 47.5);
+        assert!((quote.total - quote.labor - 52.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rendered_documents() {
+        let markdown = generate_quote(
+            &input(10, QuoteFormat::Markdown),
+            &gum_types(),
+            false,
+            &config(),
+            now(),
+        )
+        .unwrap();
+        assert!(markdown.document.contains(&markdown.quote.quote_id));
+        assert!(markdown.document.contains("**Client:** Pike Place"));
+        assert!(markdown.document.contains("Valid until:** 2026-03-31"));
+
+        let mut taxed = config();
+        taxed.quote.tax_rate = Decimal::new(825, 4);
+        let mut request = input(10, QuoteFormat::Markdown);
+        request.project_name = Some("**Lobby** | [north] wall".to_string());
+        let document = generate_quote(&request, &gum_types(), false, &taxed, now())
+            .unwrap()
+            .document;
+        assert!(document.contains("- Tax (8.25%):"));
+        let project = "**Project:** \\*\\*Lobby\\*\\* \\| \\[north\\] wall  \n";
+        assert!(document.contains(project));
+        assert!(document.contains("| Red \\<Cherry\\> (per piece) |"));
+
+        let html = generate_quote(
+            &input(10, QuoteFormat::Html),
+            &gum_types(),
+            false,
+            &config(),
+            now(),
+        )
+        .unwrap()
+        .document;
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Red &lt;Cherry&gt; (per piece)"));
+        assert!(!html.contains("<Cherry>"));
+    }
+
+    #[test]
+    fn test_fallback_prices_are_flagged() {
+        let live = generate_quote(
+            &input(10, QuoteFormat::Markdown),
+            &gum_types(),
+            false,
+            &config(),
+            now(),
+        )
+        .unwrap();
+        assert!(!live.quote.fallback_prices);
+        assert!(!live.document.contains(FALLBACK_PRICES_NOTE));
+
+        for format in [QuoteFormat::Markdown, QuoteFormat::Html] {
+            let output =
+                generate_quote(&input(10, format), &gum_types(), true, &config(), now()).unwrap();
+            assert!(output.quote.fallback_prices);
+            assert!(output.document.contains(FALLBACK_PRICES_NOTE));
+        }
+    }
+
+    #[test]
+    fn test_amounts_use_currency_minor_units() {
+        let mut rated = config();
+        rated.pricing.rates = [
+            ("JPY".to_string(), Decimal::new(1, 2)),
+            ("KWD".to_string(), Decimal::new(325, 2)),
+        ]
+        .into_iter()
+        .collect();
+
+        // 0.10 USD -> 10 JPY per piece
+        let mut request = input(10, QuoteFormat::Markdown);
+        request.cost.currency = Some("JPY".to_string());
+        let output = generate_quote(&request, &gum_types(), false, &rated, now()).unwrap();
+        assert!(output.document.contains("| Red \\<Cherry\\> (per piece) | 10 | 10 | 100 |"));
+        assert!(output.document.contains("- Materials: 100 JPY"));
+        assert!(!output.document.contains(".00"));
+
+        // 0.10 USD -> 0.0308 KWD per piece, kept to 4 places; amounts to 3
+        let mut request = input(10, QuoteFormat::Html);
+        request.cost.currency = Some("KWD".to_string());
+        let output = generate_quote(&request, &gum_types(), false, &rated, now()).unwrap();
+        assert_eq!(output.quote.lines[0].unit_price, 0.0308);
+        assert!(output
+            .document
+            .contains("<td>10</td><td>0.0308</td><td>0.308</td>"));
+        let total = money::format_money(output.quote.total, "KWD");
+        assert_eq!(total.split('.').nth(1).map(str::len), Some(3));
+        assert!(output.document.contains(&format!("<th>{}</th>", total)));
+    }
+
+    #[test]
+    fn test_labor_warning_is_listed_as_not_included() {
+        // A crew of one can't place this many pieces within the schedule limit
+        let mut request = input(1_000_000, QuoteFormat::Markdown);
+        request.include_labor = true;
+        request.cost.crew_size = Some(1);
+        let output = generate_quote(&request, &gum_types(), false, &config(), now()).unwrap();
+
+        assert_eq!(output.quote.labor, 0.0);
+        let warning = output.quote.labor_warning.as_deref().unwrap();
+        assert!(output.document.contains("## Not included"));
+        assert!(output.document.contains(&escape_markdown(warning)));
+
+        request.include_labor = false;
+        let output = generate_quote(&request, &gum_types(), false, &config(), now()).unwrap();
+        assert!(output.quote.labor_warning.is_none());
+        assert!(!output.document.contains("Not included"));
+    }
+}
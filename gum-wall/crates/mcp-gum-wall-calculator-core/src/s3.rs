@@ -1,7 +1,42 @@
-//! S3 image fetching utilities
+//! S3 image fetching and design file upload utilities
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::Result;
+use aws_sdk_s3::config::{Credentials, Region};
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::Client as S3Client;
+use crate::config::S3Config;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors raised while reading from or writing to S3
+#[derive(Debug, Error)]
+pub enum S3Error {
+    #[error("Invalid S3 URI: {0}")]
+    InvalidUri(String),
+    #[error("S3 object s3://{bucket}/{key} does not exist")]
+    NotFound { bucket: String, key: String },
+    #[error("Access to s3://{bucket}/{key} was denied")]
+    AccessDenied { bucket: String, key: String },
+    #[error("S3 GetObject failed for s3://{bucket}/{key}: {message}")]
+    GetObject {
+        bucket: String,
+        key: String,
+        message: String,
+    },
+    #[error("S3 object is too large ({size} bytes, maximum {max_bytes})")]
+    TooLarge { size: u64, max_bytes: u64 },
+    #[error("Failed to read S3 object body: {0}")]
+    Read(String),
+    #[error("S3 PutObject failed for s3://{bucket}/{key}: {message}")]
+    PutObject {
+        bucket: String,
+        key: String,
+        message: String,
+    },
+    #[error("Failed to presign a download URL: {0}")]
+    Presign(String),
+}
 
 /// Parse an S3 URI into bucket and key components
 ///
@@ -11,29 +46,36 @@ use aws_sdk_s3::Client as S3Client;
 /// assert_eq!(bucket, "my-bucket");
 /// assert_eq!(key, "path/to/image.jpg");
 /// ```
-pub fn parse_s3_uri(uri: &str) -> Result<(String, String)> {
+pub fn parse_s3_uri(uri: &str) -> Result<(String, String), S3Error> {
     let uri = uri.trim();
 
     if !uri.starts_with("s3://") {
-        return Err(anyhow!("Invalid S3 URI: must start with 's3://'"));
+        return Err(S3Error::InvalidUri("must start with 's3://'".to_string()));
     }
 
     let path = &uri[5..]; // Remove "s3://"
     let parts: Vec<&str> = path.splitn(2, '/').collect();
 
     if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
-        return Err(anyhow!("Invalid S3 URI format. Expected: s3://bucket/key"));
+        return Err(S3Error::InvalidUri(
+            "expected format s3://bucket/key".to_string(),
+        ));
     }
 
     Ok((parts[0].to_string(), parts[1].to_string()))
 }
 
-/// Fetch an image from S3 and return its bytes along with content type
+/// Fetch an object from S3 and return its bytes along with the declared content type
+///
+/// Objects larger than `max_bytes` are rejected from their Content-Length
+/// before downloading, and the body is streamed so an object that turns out
+/// larger than declared is cut off as soon as it passes the limit.
 pub async fn fetch_image_from_s3(
     client: &S3Client,
     bucket: &str,
     key: &str,
-) -> Result<(Vec<u8>, String)> {
+    max_bytes: u64,
+) -> Result<(Vec<u8>, String), S3Error> {
     tracing::info!("Fetching S3 object: bucket='{}', key='{}'", bucket, key);
 
     let response = client
@@ -44,25 +86,152 @@ pub async fn fetch_image_from_s3(
         .await
         .map_err(|e| {
             tracing::error!("S3 GetObject failed: {:?}", e);
-            anyhow!("S3 GetObject failed for s3://{}/{}: {}", bucket, key, e)
+            let status = e.raw_response().map(|r| r.status().as_u16());
+            if e.as_service_error().is_some_and(|e| e.is_no_such_key()) || status == Some(404) {
+                S3Error::NotFound {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                }
+            } else if status == Some(403) {
+                S3Error::AccessDenied {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                }
+            } else {
+                S3Error::GetObject {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                    message: e.to_string(),
+                }
+            }
         })?;
 
+    let declared = response.content_length().unwrap_or(0).max(0) as u64;
+    if declared > max_bytes {
+        return Err(S3Error::TooLarge {
+            size: declared,
+            max_bytes,
+        });
+    }
+
     let content_type = response
         .content_type()
-        .unwrap_or("image/jpeg")
+        .unwrap_or("application/octet-stream")
         .to_string();
 
-    let bytes = response
-        .body
-        .collect()
+    let mut body = response.body;
+    let mut bytes = Vec::with_capacity(declared as usize);
+    while let Some(chunk) = body
+        .try_next()
         .await
-        .context("Failed to read S3 object body")?
-        .into_bytes()
-        .to_vec();
+        .map_err(|e| S3Error::Read(e.to_string()))?
+    {
+        let size = (bytes.len() + chunk.len()) as u64;
+        if size > max_bytes {
+            return Err(S3Error::TooLarge { size, max_bytes });
+        }
+        bytes.extend_from_slice(&chunk);
+    }
 
     Ok((bytes, content_type))
 }
 
+/// Upload an object to S3
+pub async fn put_object(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    content_type: &str,
+    bytes: Vec<u8>,
+) -> Result<(), S3Error> {
+    tracing::info!("Uploading S3 object: bucket='{}', key='{}'", bucket, key);
+
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type(content_type)
+        .body(ByteStream::from(bytes))
+        .send()
+        .await
+        .map_err(|e| {
+            tracing::error!("S3 PutObject failed: {:?}", e);
+            if e.raw_response().map(|r| r.status().as_u16()) == Some(403) {
+                S3Error::AccessDenied {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                }
+            } else {
+                S3Error::PutObject {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                    message: e.to_string(),
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Create a presigned GET URL for an object, valid for `expires_in`
+///
+/// Signing happens locally; the object is not checked.
+pub async fn presign_get(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    expires_in: Duration,
+) -> Result<String, S3Error> {
+    let presigning =
+        PresigningConfig::expires_in(expires_in).map_err(|e| S3Error::Presign(e.to_string()))?;
+    let request = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .presigned(presigning)
+        .await
+        .map_err(|e| S3Error::Presign(e.to_string()))?;
+    Ok(request.uri().to_string())
+}
+
+/// A presigned upload: the URL and the headers the client must send with it
+#[derive(Debug, Clone)]
+pub struct PresignedUpload {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// Create a presigned PUT URL for an object, valid for `expires_in`
+///
+/// The content type and length are part of the signature, so S3 rejects an
+/// upload that differs from what was requested.
+pub async fn presign_put(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    content_type: &str,
+    content_length: u64,
+    expires_in: Duration,
+) -> Result<PresignedUpload, S3Error> {
+    let presigning =
+        PresigningConfig::expires_in(expires_in).map_err(|e| S3Error::Presign(e.to_string()))?;
+    let request = client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type(content_type)
+        .content_length(content_length as i64)
+        .presigned(presigning)
+        .await
+        .map_err(|e| S3Error::Presign(e.to_string()))?;
+    Ok(PresignedUpload {
+        url: request.uri().to_string(),
+        headers: request
+            .headers()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+    })
+}
+
 /// Determine MIME type from file extension or content type
 pub fn get_mime_type(key: &str, content_type: &str) -> String {
     // If content type is specific, use it
@@ -82,15 +251,41 @@ pub fn get_mime_type(key: &str, content_type: &str) -> String {
     }
 }
 
-/// Create an S3 client with default configuration
-pub async fn create_s3_client() -> Result<S3Client> {
-    let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-    Ok(S3Client::new(&config))
+/// Create an S3 client from the `[s3]` settings, using the AWS SDK defaults for unset values
+pub async fn create_s3_client(config: &S3Config) -> Result<S3Client> {
+    let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+    if let Some(region) = &config.region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    if let Some(profile) = &config.profile {
+        loader = loader.profile_name(profile);
+    }
+    if let Some(endpoint_url) = &config.endpoint_url {
+        loader = loader.endpoint_url(endpoint_url);
+    }
+    if let (Some(access_key_id), Some(secret_access_key)) =
+        (&config.access_key_id, &config.secret_access_key)
+    {
+        loader = loader.credentials_provider(Credentials::new(
+            access_key_id,
+            secret_access_key.expose(),
+            None,
+            None,
+            "gum-wall-config",
+        ));
+    }
+    let sdk_config = loader.load().await;
+
+    let s3_config = aws_sdk_s3::config::Builder::from(&sdk_config)
+        .force_path_style(config.force_path_style)
+        .build();
+    Ok(S3Client::from_conf(s3_config))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{png, MockS3};
 
     #[test]
     fn test_parse_s3_uri_valid() {
@@ -129,4 +324,59 @@ mod tests {
     fn test_get_mime_type_from_content_type() {
         assert_eq!(get_mime_type("file", "image/webp"), "image/webp");
     }
+
+    // Integration tests against a local S3-compatible stand-in (path-style, static keys)
+
+    #[tokio::test]
+    async fn test_fetch_from_s3_compatible_endpoint() {
+        let s3 = MockS3::start().await;
+        s3.put("art", "walls/blue.png", "application/octet-stream", png());
+        let client = create_s3_client(&s3.config()).await.unwrap();
+
+        let (bytes, content_type) = fetch_image_from_s3(&client, "art", "walls/blue.png", 1024)
+            .await
+            .unwrap();
+        assert_eq!(bytes, png());
+        assert_eq!(content_type, "application/octet-stream");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_errors_are_typed() {
+        let s3 = MockS3::start().await;
+        s3.put("art", "big.png", "image/png", vec![0u8; 2048]);
+        let client = create_s3_client(&s3.config()).await.unwrap();
+
+        let missing = fetch_image_from_s3(&client, "art", "missing.png", 1024).await;
+        assert!(matches!(missing, Err(S3Error::NotFound { .. })));
+
+        let denied = fetch_image_from_s3(&client, "forbidden", "a.png", 1024).await;
+        assert!(matches!(denied, Err(S3Error::AccessDenied { .. })));
+
+        let too_large = fetch_image_from_s3(&client, "art", "big.png", 1024).await;
+        assert!(matches!(
+            too_large,
+            Err(S3Error::TooLarge { size: 2048, max_bytes: 1024 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load_image_from_s3_compatible_endpoint() {
+        use crate::config::{GumWallConfig, ImagesConfig};
+        use crate::image_source::{load_image, ImageSource};
+        use crate::test_support::test_context;
+
+        let s3 = MockS3::start().await;
+        // Declared as JPEG, but the data is sniffed as PNG
+        s3.put("art", "mislabeled.jpg", "image/jpeg", png());
+
+        let source = ImageSource::parse_uri("s3://art/mislabeled.jpg", &ImagesConfig::default())
+            .unwrap();
+        let config = GumWallConfig {
+            s3: s3.config(),
+            ..GumWallConfig::default()
+        };
+        let context = test_context(config);
+        let image = load_image(source, &context).await.unwrap();
+        assert_eq!(image.mime_type, "image/png");
+    }
 }
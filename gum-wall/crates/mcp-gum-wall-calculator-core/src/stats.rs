@@ -0,0 +1,113 @@
+//! Project statistics from per-gum physical properties
+//!
+//! Each gum type may set its own weight, chew time, sugar and volume; unset
+//! values (and unknown gum types) fall back to the configured gum defaults.
+
+use crate::config::GumDefaultsConfig;
+use crate::types::{FunStats, GumProperties, GumType};
+
+/// Physical totals for the pieces placed on the wall
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProjectTotals {
+    pub pieces: u32,
+    pub chew_hours: f64,
+    pub weight_g: f64,
+    pub sugar_g: f64,
+    pub volume_cm3: f64,
+}
+
+impl ProjectTotals {
+    /// Add `count` pieces of a gum type (`None` for gum types not in the catalog)
+    pub fn add(&mut self, gum_type: Option<&GumType>, count: u32, defaults: &GumDefaultsConfig) {
+        let properties = gum_type.map(|g| &g.properties);
+        let value = |get: fn(&GumProperties) -> Option<f64>, default: f64| {
+            properties.and_then(get).unwrap_or(default) * count as f64
+        };
+
+        self.pieces = self.pieces.saturating_add(count);
+        self.chew_hours += value(|p| p.chew_minutes, defaults.chew_minutes) / 60.0;
+        self.weight_g += value(|p| p.weight_g, defaults.weight_g);
+        self.sugar_g += value(|p| p.sugar_g, defaults.sugar_g);
+        self.volume_cm3 += value(|p| p.volume_cm3, defaults.volume_cm3);
+    }
+
+    /// Fun statistics for these totals on a wall of `wall_area_m2`
+    pub fn fun_stats(
+        &self,
+        defaults: &GumDefaultsConfig,
+        packs_needed: u32,
+        wall_area_m2: f64,
+    ) -> FunStats {
+        let weight_kg = self.weight_g / 1000.0;
+        FunStats {
+            chewing_time_hours: round(self.chew_hours, 2),
+            calories_burned: (self.chew_hours * defaults.calories_per_chew_hour).round() as u32,
+            packs_needed,
+            weight_kg: round(weight_kg, 3),
+            sugar_kg: round(self.sugar_g / 1000.0, 3),
+            volume_liters: round(self.volume_cm3 / 1000.0, 3),
+            wall_load_kg_per_m2: if wall_area_m2 > 0.0 {
+                round(weight_kg / wall_area_m2, 3)
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gum(properties: GumProperties) -> GumType {
+        GumType {
+            gum_id: "gum".to_string(),
+            name: "Gum".to_string(),
+            hex_color: "#FFFFFF".to_string(),
+            price_per_piece: 0.05,
+            brand: None,
+            flavor: None,
+            is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties,
+        }
+    }
+
+    #[test]
+    fn test_defaults_and_overrides() {
+        let defaults = GumDefaultsConfig::default();
+        let sugar_free = gum(GumProperties {
+            weight_g: Some(1.5),
+            ..GumProperties::sugar_free()
+        });
+
+        let mut totals = ProjectTotals::default();
+        totals.add(Some(&sugar_free), 1000, &defaults);
+        totals.add(None, 1000, &defaults);
+
+        assert_eq!(totals.pieces, 2000);
+        assert_eq!(totals.weight_g, 4500.0);
+        assert_eq!(totals.sugar_g, 2000.0);
+        assert_eq!(totals.volume_cm3, 5000.0);
+    }
+
+    #[test]
+    fn test_calories_follow_chewing_time() {
+        let defaults = GumDefaultsConfig::default();
+        let mut totals = ProjectTotals::default();
+        // 1200 pieces at 5 minutes each = 100 hours of chewing
+        totals.add(Some(&gum(GumProperties::default())), 1200, &defaults);
+
+        let stats = totals.fun_stats(&defaults, 0, 2.0);
+        assert_eq!(stats.chewing_time_hours, 100.0);
+        assert_eq!(stats.calories_burned, 1100);
+        assert_eq!(stats.weight_kg, 3.6);
+        assert_eq!(stats.wall_load_kg_per_m2, 1.8);
+    }
+}
@@ -234,6 +234,9 @@ mod tests {
             brand: None,
             flavor: None,
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: Default::default(),
         });
         legend.insert("2".to_string(), GumType {
             gum_id: "blue".to_string(),
@@ -243,6 +246,9 @@ mod tests {
             brand: None,
             flavor: None,
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: Default::default(),
         });
         legend
     }
@@ -0,0 +1,282 @@
+//! Test helpers: a tiny local HTTP server for mocking AppSync and friends,
+//! plus shared fixtures
+
+use crate::catalog::FallbackCatalog;
+use crate::config::{GumWallConfig, S3Config, Secret};
+use crate::context::AppContext;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A request received by the mock server
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// The raw body, for binary uploads
+    pub bytes: Vec<u8>,
+}
+
+impl MockRequest {
+    /// Look up a header value (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Local HTTP server answering every request with the given handler
+pub struct MockServer {
+    pub url: String,
+    hits: Arc<AtomicUsize>,
+}
+
+/// A full response from the mock server
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("content-type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+}
+
+impl MockServer {
+    /// Start the server; `handler` returns `(status, body)` for each request
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> (u16, String) + Send + Sync + 'static,
+    {
+        Self::start_with_responses(move |request| {
+            let (status, body) = handler(request);
+            MockResponse::new(status, "application/json", body)
+        })
+        .await
+    }
+
+    /// Start the server with a handler that controls headers and binary bodies
+    pub async fn start_with_responses<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let handler = Arc::new(handler);
+
+        let server_hits = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let handler = handler.clone();
+                let hits = server_hits.clone();
+                tokio::spawn(async move {
+                    let Some(request) = read_request(&mut socket).await else {
+                        return;
+                    };
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    let response = handler(&request);
+                    let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+                    for (name, value) in &response.headers {
+                        head.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    head.push_str(&format!(
+                        "content-length: {}\r\nconnection: close\r\n\r\n",
+                        response.body.len()
+                    ));
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    let _ = socket.write_all(&response.body).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        MockServer { url, hits }
+    }
+
+    /// Number of requests served so far
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<MockRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // Read until the end of the headers
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buffer.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let bytes = buffer[header_end..].to_vec();
+    let body = String::from_utf8_lossy(&bytes).to_string();
+    Some(MockRequest {
+        method,
+        path,
+        headers,
+        body,
+        bytes,
+    })
+}
+
+/// Objects held by [`MockS3`], keyed by "bucket/key": (content type, bytes)
+type MockObjects = Arc<Mutex<HashMap<String, (String, Vec<u8>)>>>;
+
+/// A local S3-compatible stand-in serving path-style GetObject and PutObject requests
+///
+/// Requests for bucket "forbidden" are denied; missing objects return `NoSuchKey`.
+pub struct MockS3 {
+    pub server: MockServer,
+    objects: MockObjects,
+}
+
+impl MockS3 {
+    pub async fn start() -> Self {
+        let objects: MockObjects = Arc::default();
+        let store = objects.clone();
+        let server = MockServer::start_with_responses(move |request| {
+            let path = request.path.split('?').next().unwrap_or_default();
+            let Some((bucket, key)) = path.trim_start_matches('/').split_once('/') else {
+                return s3_error(400, "InvalidRequest");
+            };
+            if bucket == "forbidden" {
+                return s3_error(403, "AccessDenied");
+            }
+            let mut objects = store.lock().unwrap();
+            let name = format!("{}/{}", bucket, key);
+            match (request.method.as_str(), objects.get(&name)) {
+                ("GET", Some((content_type, bytes))) => {
+                    MockResponse::new(200, content_type, bytes.clone())
+                }
+                ("GET", None) => s3_error(404, "NoSuchKey"),
+                ("PUT", _) => {
+                    let content_type = request.header("content-type").unwrap_or_default();
+                    objects.insert(name, (content_type.to_string(), request.bytes.clone()));
+                    MockResponse::new(200, "application/xml", "")
+                }
+                _ => s3_error(405, "MethodNotAllowed"),
+            }
+        })
+        .await;
+        MockS3 { server, objects }
+    }
+
+    /// Store an object
+    pub fn put(&self, bucket: &str, key: &str, content_type: &str, bytes: Vec<u8>) {
+        self.objects.lock().unwrap().insert(
+            format!("{}/{}", bucket, key),
+            (content_type.to_string(), bytes),
+        );
+    }
+
+    /// A stored object: (content type, bytes)
+    pub fn get(&self, bucket: &str, key: &str) -> Option<(String, Vec<u8>)> {
+        self.objects.lock().unwrap().get(&format!("{}/{}", bucket, key)).cloned()
+    }
+
+    /// Keys stored in a bucket
+    pub fn keys(&self, bucket: &str) -> Vec<String> {
+        let prefix = format!("{}/", bucket);
+        let mut keys: Vec<String> = self
+            .objects
+            .lock()
+            .unwrap()
+            .keys()
+            .filter_map(|name| name.strip_prefix(&prefix).map(str::to_string))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// S3 settings pointing at this stand-in
+    pub fn config(&self) -> S3Config {
+        S3Config {
+            endpoint_url: Some(self.server.url.clone()),
+            region: Some("us-east-1".to_string()),
+            force_path_style: true,
+            profile: None,
+            access_key_id: Some("test".to_string()),
+            secret_access_key: Some(Secret::new("test")),
+        }
+    }
+}
+
+fn s3_error(status: u16, code: &str) -> MockResponse {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code><Message>{}</Message></Error>",
+        code, code
+    );
+    MockResponse::new(status, "application/xml", body)
+}
+
+/// Application context using the built-in gum catalog
+pub fn test_context(config: GumWallConfig) -> AppContext {
+    AppContext::new(config, Arc::new(FallbackCatalog), reqwest::Client::new())
+}
+
+/// A 2x2 red PNG
+pub fn png() -> Vec<u8> {
+    solid_png(2, [255, 0, 0])
+}
+
+/// A `size`x`size` PNG of a single color
+pub fn solid_png(size: u32, color: [u8; 3]) -> Vec<u8> {
+    encode_png(&image::RgbImage::from_pixel(size, size, image::Rgb(color)))
+}
+
+/// Encode an image as PNG
+pub fn encode_png(image: &image::RgbImage) -> Vec<u8> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+    bytes.into_inner()
+}
@@ -23,7 +23,7 @@ pub struct GumType {
     pub name: String,
     /// Hex color code (e.g., "#FF69B4")
     pub hex_color: String,
-    /// Price per piece in USD
+    /// Price per piece in the gum's currency
     pub price_per_piece: f64,
     /// Brand name
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -34,6 +34,62 @@ pub struct GumType {
     /// Whether this gum type is available for purchase
     #[serde(default = "default_true")]
     pub is_available: bool,
+    /// ISO 4217 currency of `price_per_piece`; the base pricing currency when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Pack sizes this gum is sold in; bought per piece when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packs: Vec<GumPackPrice>,
+    /// Physical properties used for project statistics
+    #[serde(default, skip_serializing_if = "GumProperties::is_empty")]
+    pub properties: GumProperties,
+}
+
+/// Physical properties of one piece; unset values use the configured gum defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema, Validate)]
+pub struct GumProperties {
+    /// Weight in grams
+    #[validate(range(min = 0.0, max = 1000.0))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight_g: Option<f64>,
+    /// Minutes of chewing before the piece can be placed
+    #[validate(range(min = 0.0, max = 1000.0))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chew_minutes: Option<f64>,
+    /// Sugar content in grams
+    #[validate(range(min = 0.0, max = 1000.0))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sugar_g: Option<f64>,
+    /// Volume in cubic centimeters
+    #[validate(range(min = 0.0, max = 1000.0))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_cm3: Option<f64>,
+}
+
+impl GumProperties {
+    /// Properties of a sugar-free gum, other values defaulted
+    pub fn sugar_free() -> Self {
+        GumProperties {
+            sugar_g: Some(0.0),
+            ..Default::default()
+        }
+    }
+
+    /// Whether no property is set
+    pub fn is_empty(&self) -> bool {
+        *self == GumProperties::default()
+    }
+}
+
+/// A pack size a gum type is sold in, with its price
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, Validate)]
+pub struct GumPackPrice {
+    /// Pieces per pack
+    #[validate(range(min = 1, max = 1_000_000))]
+    pub pack_size: u32,
+    /// Price per pack in the gum's currency
+    #[validate(range(exclusive_min = 0.0, max = 100_000.0))]
+    pub price: f64,
 }
 
 fn default_true() -> bool {
@@ -70,9 +126,33 @@ impl GumType {
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
 #[schemars(deny_unknown_fields)]
 pub struct AnalyzeImageInput {
-    /// S3 URI to the image (e.g., "s3://bucket/path/to/image.jpg")
-    #[schemars(description = "S3 URI to the image (format: s3://bucket/key)")]
-    pub s3_uri: String,
+    /// Image URI (e.g., "s3://bucket/path/to/image.jpg"); also accepted as `s3_uri`
+    #[schemars(
+        description = "Image URI: s3://bucket/key, https://..., data:image/...;base64,... or \
+        file:///path (file URIs only when enabled on the server). Provide exactly one of \
+        image_uri, image_base64 or image_resource."
+    )]
+    #[validate(length(min = 1))]
+    #[serde(default, alias = "s3_uri")]
+    pub image_uri: Option<String>,
+
+    /// Base64-encoded image data
+    #[schemars(description = "Base64-encoded image data")]
+    #[serde(default)]
+    pub image_base64: Option<String>,
+
+    /// MIME type of image_base64 (detected from the data when unset)
+    #[schemars(description = "MIME type of image_base64, e.g. image/png (default: detected)")]
+    #[serde(default)]
+    pub image_mime_type: Option<String>,
+
+    /// MCP resource holding the image
+    #[schemars(
+        description = "MCP resource with the image: its base64 blob, or a uri to load it from"
+    )]
+    #[validate(nested)]
+    #[serde(default)]
+    pub image_resource: Option<ImageResource>,
 
     /// Wall width in centimeters
     #[validate(range(min = 10.0, max = 10000.0))]
@@ -89,6 +169,75 @@ pub struct AnalyzeImageInput {
     #[schemars(description = "Diameter of each gum piece in centimeters (0.5-5.0, default: 2.0)")]
     #[serde(default = "default_gum_diameter")]
     pub gum_diameter_cm: f64,
+
+    /// Extra pieces to buy for drops and rejects, as a percentage of each count
+    #[validate(range(min = 0.0, max = 100.0))]
+    #[schemars(
+        description = "Spare pieces per gum type as a percentage of its count (0-100, default from server config)"
+    )]
+    #[serde(default)]
+    pub overage_percent: Option<f64>,
+
+    /// Minimum spare pieces per gum type
+    #[validate(range(max = 100_000))]
+    #[schemars(
+        description = "Minimum spare pieces per gum type (0-100000, default from server config)"
+    )]
+    #[serde(default)]
+    pub min_spare_pieces: Option<u32>,
+
+    /// How to return the design files
+    #[schemars(
+        description = "inline returns the SVG in the response; s3 uploads SVG, PNG, PDF and \
+        design JSON to S3 and returns presigned download URLs (default from server config)"
+    )]
+    #[serde(default)]
+    pub output: Option<OutputMode>,
+
+    /// Skip the vision analysis
+    #[schemars(
+        description = "Skip the vision model: faster, but no image description, focal point or \
+        key regions (default: false)"
+    )]
+    #[serde(default)]
+    pub skip_vision: bool,
+}
+
+/// How analyze_image_for_gum_wall returns the generated design files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// SVG inline in the tool response
+    #[default]
+    Inline,
+    /// Files uploaded to S3, returned as presigned URLs
+    S3,
+}
+
+impl std::str::FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "inline" => Ok(OutputMode::Inline),
+            "s3" => Ok(OutputMode::S3),
+            other => Err(format!("unknown output mode '{}' (expected inline or s3)", other)),
+        }
+    }
+}
+
+/// An MCP resource reference to an image (same fields as MCP blob resource contents)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+pub struct ImageResource {
+    /// Resource URI; the image is loaded from it when `blob` is unset
+    #[validate(length(min = 1, max = 2048))]
+    pub uri: String,
+    /// MIME type of the resource
+    #[serde(default, rename = "mimeType")]
+    pub mime_type: Option<String>,
+    /// Base64-encoded resource contents
+    #[serde(default)]
+    pub blob: Option<String>,
 }
 
 /// Grid dimensions
@@ -141,14 +290,102 @@ pub struct AnalyzeImageOutput {
     pub legend: HashMap<String, GumType>,
     /// Grid dimensions
     pub dimensions: GridDimensions,
-    /// Count of each gum type needed
+    /// Count of each gum type placed on the wall
     pub gum_counts: HashMap<String, u32>,
-    /// Total number of gums needed
+    /// Count of each gum type to buy (placed pieces plus spares)
+    pub purchase_counts: HashMap<String, u32>,
+    /// Total number of gums placed on the wall
     pub total_gums: u32,
+    /// Total number of spare pieces across all gum types
+    pub total_spare_pieces: u32,
     /// Description of the image from vision model
     pub image_description: String,
-    /// SVG representation of the paint-by-number design
-    pub svg: String,
+    /// Structured analysis from the vision model, when it returned one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_analysis: Option<ImageAnalysis>,
+    /// Content screening result, when moderation is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<ModerationResult>,
+    /// SVG representation of the paint-by-number design (inline output only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub svg: Option<String>,
+    /// Design files uploaded to S3 (s3 output only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<DesignArtifact>,
+    /// How long each stage of the analysis took
+    #[serde(default)]
+    pub timings: StageTimings,
+}
+
+/// How long each stage of an analysis took, in milliseconds
+///
+/// Vision analysis, the catalog lookup and image decoding run at the same
+/// time, so the stages add up to more than `total_ms`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct StageTimings {
+    /// Fetching the image
+    pub fetch_ms: u64,
+    /// Vision analysis (0 when skipped)
+    pub vision_ms: u64,
+    /// Content moderation (0 when disabled)
+    pub moderation_ms: u64,
+    /// Loading gum types from the catalog
+    pub catalog_ms: u64,
+    /// Decoding the image
+    pub decode_ms: u64,
+    /// Mapping the image to gum colors
+    pub quantize_ms: u64,
+    /// Generating the SVG
+    pub render_ms: u64,
+    /// Rendering and uploading the design files (s3 output only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_ms: Option<u64>,
+    /// The whole analysis
+    pub total_ms: u64,
+}
+
+/// File format of an uploaded design file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactFormat {
+    Svg,
+    Png,
+    Pdf,
+    /// The design output (grid, legend and counts) as JSON
+    Json,
+}
+
+impl ArtifactFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArtifactFormat::Svg => "svg",
+            ArtifactFormat::Png => "png",
+            ArtifactFormat::Pdf => "pdf",
+            ArtifactFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ArtifactFormat::Svg => "image/svg+xml",
+            ArtifactFormat::Png => "image/png",
+            ArtifactFormat::Pdf => "application/pdf",
+            ArtifactFormat::Json => "application/json",
+        }
+    }
+}
+
+/// A design file stored in S3
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DesignArtifact {
+    pub format: ArtifactFormat,
+    pub content_type: String,
+    /// Location of the file, e.g. "s3://bucket/photos/cat-gum-wall-1a2b3c4d.svg"
+    pub s3_uri: String,
+    /// Presigned download URL
+    pub url: String,
+    /// When the download URL expires (RFC 3339)
+    pub expires_at: String,
 }
 
 // ============================================================================
@@ -160,13 +397,72 @@ pub struct AnalyzeImageOutput {
 #[schemars(deny_unknown_fields)]
 pub struct EmptyInput {}
 
+/// Check that no gum type asks for more than [`crate::purchase::MAX_PIECES_PER_GUM`] pieces
+fn validate_gum_counts(counts: &HashMap<String, u32>) -> Result<(), validator::ValidationError> {
+    if counts
+        .values()
+        .all(|&count| count <= crate::purchase::MAX_PIECES_PER_GUM)
+    {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("gum_counts")
+            .with_message("each count must be at most 1000000".into()))
+    }
+}
+
 /// Input for the calculate_gum_cost tool
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
 #[schemars(deny_unknown_fields)]
 pub struct CalculateCostInput {
     /// Map of gum_id to count needed
-    #[schemars(description = "Map of gum type ID to count needed (e.g., {\"dubble_bubble_pink\": 500})")]
+    #[schemars(description = "Map of gum type ID to count needed, at most 1000000 each (e.g., {\"dubble_bubble_pink\": 500})")]
+    #[validate(custom(function = "validate_gum_counts"))]
     pub gum_counts: HashMap<String, u32>,
+
+    /// Fail instead of warning when a gum type is unavailable or unknown
+    #[schemars(
+        description = "Return an error instead of warnings when a gum type is unavailable or unknown (default: false)"
+    )]
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Currency to report costs in
+    #[schemars(
+        description = "ISO 4217 currency to report costs in (default: the server's pricing currency, usually USD)"
+    )]
+    #[validate(length(equal = 3))]
+    #[serde(default)]
+    pub currency: Option<String>,
+
+    /// Extra pieces to buy for drops and rejects, as a percentage of each count
+    #[validate(range(min = 0.0, max = 100.0))]
+    #[schemars(
+        description = "Spare pieces per gum type as a percentage of its count (0-100, default from server config)"
+    )]
+    #[serde(default)]
+    pub overage_percent: Option<f64>,
+
+    /// Minimum spare pieces per gum type
+    #[validate(range(max = 100_000))]
+    #[schemars(
+        description = "Minimum spare pieces per gum type (0-100000, default from server config)"
+    )]
+    #[serde(default)]
+    pub min_spare_pieces: Option<u32>,
+
+    /// Installation crew size
+    #[validate(range(min = 1, max = 100))]
+    #[schemars(description = "Number of people installing the wall (1-100, default from server config)")]
+    #[serde(default)]
+    pub crew_size: Option<u32>,
+
+    /// Wall area in square meters, used for surface preparation time
+    #[validate(range(min = 0.0, max = 10000.0))]
+    #[schemars(
+        description = "Wall area in square meters for preparation time (default: estimated from the piece count at 2 cm per piece)"
+    )]
+    #[serde(default)]
+    pub wall_area_m2: Option<f64>,
 }
 
 /// Cost breakdown for a single gum type
@@ -178,12 +474,64 @@ pub struct CostBreakdownItem {
     pub name: String,
     /// Hex color
     pub hex_color: String,
-    /// Number of pieces needed
+    /// Number of pieces placed on the wall
     pub count: u32,
-    /// Price per piece
+    /// Spare pieces added for drops and rejects
+    #[serde(default)]
+    pub spare_pieces: u32,
+    /// Pieces to buy (count plus spares)
+    #[serde(default)]
+    pub purchase_quantity: u32,
+    /// Price per piece, converted to the output currency
     pub unit_price: f64,
-    /// Subtotal for this gum type
+    /// Cost of the packs bought for this gum type, rounded to the currency's minor unit
     pub subtotal: f64,
+    /// Pieces bought (purchase quantity rounded up to whole packs)
+    #[serde(default)]
+    pub pieces_purchased: u32,
+    /// Pieces bought beyond the purchase quantity because of pack sizes
+    #[serde(default)]
+    pub leftover_pieces: u32,
+}
+
+/// Why part of a cost could not be calculated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CostWarningKind {
+    /// The gum type exists but is no longer available for purchase
+    Unavailable,
+    /// The gum type is not in the catalog
+    Unknown,
+}
+
+/// An available gum type suggested as a replacement
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GumSubstitute {
+    /// Gum type ID
+    pub gum_id: String,
+    /// Display name
+    pub name: String,
+    /// Hex color
+    pub hex_color: String,
+    /// Price per piece
+    pub price_per_piece: f64,
+    /// Euclidean RGB distance from the original color (lower is closer)
+    pub color_distance: f64,
+}
+
+/// A gum type that was requested but not priced
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CostWarning {
+    /// Gum type ID as requested
+    pub gum_id: String,
+    /// Why the gum type was not priced
+    pub kind: CostWarningKind,
+    /// Number of pieces requested
+    pub count: u32,
+    /// Human-readable explanation
+    pub message: String,
+    /// Nearest-color available alternatives
+    pub substitutes: Vec<GumSubstitute>,
 }
 
 /// Output from the calculate_gum_cost tool
@@ -191,27 +539,500 @@ pub struct CostBreakdownItem {
 pub struct CalculateCostOutput {
     /// Detailed breakdown by gum type
     pub breakdown: Vec<CostBreakdownItem>,
-    /// Total number of gum pieces
+    /// Total number of priced gum pieces placed on the wall
     pub total_pieces: u32,
-    /// Total cost in USD
+    /// Total spare pieces added to the priced gum types
+    #[serde(default)]
+    pub total_spare_pieces: u32,
+    /// Pieces of unavailable or unknown gum types (not included in the total)
+    #[serde(default)]
+    pub unpriced_pieces: u32,
+    /// Total cost in `currency` (sum of the rounded subtotals)
     pub total_cost: f64,
     /// Currency code
     pub currency: String,
+    /// Packs to buy, cheapest combination per gum type
+    #[serde(default)]
+    pub shopping_list: Vec<ShoppingListItem>,
+    /// Pieces bought beyond the purchase quantities across all gum types
+    #[serde(default)]
+    pub leftover_pieces: u32,
+    /// Gum types that could not be priced
+    #[serde(default)]
+    pub warnings: Vec<CostWarning>,
+    /// Installation schedule and labor cost; unset when labor could not be estimated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labor: Option<LaborEstimate>,
+    /// Why labor could not be estimated, when `labor` is unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labor_warning: Option<String>,
     /// Fun statistics
     pub fun_stats: FunStats,
+    /// True when prices come from the built-in fallback list, not the live catalog
+    #[serde(default)]
+    pub fallback_prices: bool,
+}
+
+/// Installation time and labor cost estimate
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LaborEstimate {
+    /// Number of people on the crew
+    pub crew_size: u32,
+    /// Wall area used for preparation time, in square meters
+    pub wall_area_m2: f64,
+    /// Total person-hours (preparation, chewing and placement)
+    pub person_hours: f64,
+    /// Working days needed
+    pub days: u32,
+    /// Labor cost in the output currency
+    pub labor_cost: f64,
+    /// Day-by-day plan
+    pub schedule: Vec<LaborDay>,
+}
+
+/// One working day of the installation plan
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LaborDay {
+    /// Day number, starting at 1
+    pub day: u32,
+    /// Hours worked by each crew member
+    pub hours: f64,
+    /// Person-hours spent preparing the wall surface
+    pub prep_hours: f64,
+    /// Pieces chewed and placed this day
+    pub pieces_placed: u32,
+    /// Pieces placed by the end of this day
+    pub cumulative_pieces: u32,
+    /// Share of the pieces placed by the end of this day (0-100)
+    pub percent_complete: f64,
+}
+
+/// A line on the shopping list: a number of packs of one size
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ShoppingListItem {
+    /// Gum type ID
+    pub gum_id: String,
+    /// Display name
+    pub name: String,
+    /// Pieces per pack (1 for gum bought per piece)
+    pub pack_size: u32,
+    /// Number of packs to buy
+    pub quantity: u32,
+    /// Price per pack, converted to the output currency
+    pub pack_price: f64,
+    /// Cost of this line
+    pub subtotal: f64,
 }
 
 /// Fun statistics about the gum wall project
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FunStats {
-    /// Estimated chewing time in hours (assuming 5 minutes per piece)
+    /// Total chewing time in hours (per-gum chew time)
     pub chewing_time_hours: f64,
-    /// Estimated calories burned chewing
+    /// Calories burned chewing, from the chewing time
     pub calories_burned: u32,
-    /// Number of packs needed (assuming 5 pieces per pack)
+    /// Number of packs on the shopping list
     pub packs_needed: u32,
-    /// Weight of gum in kilograms (assuming 3g per piece)
+    /// Weight of the placed gum in kilograms
     pub weight_kg: f64,
+    /// Sugar in the placed gum in kilograms
+    #[serde(default)]
+    pub sugar_kg: f64,
+    /// Volume of the placed gum in liters
+    #[serde(default)]
+    pub volume_liters: f64,
+    /// Gum weight per square meter of wall, for structural checks
+    #[serde(default)]
+    pub wall_load_kg_per_m2: f64,
+}
+
+// ============================================================================
+// Quote Tool
+// ============================================================================
+
+/// Format of the rendered quote document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+/// Input for the generate_gum_quote tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct GenerateQuoteInput {
+    /// Project to quote (same fields as calculate_gum_cost)
+    #[schemars(description = "Project to quote: the same fields as calculate_gum_cost")]
+    #[validate(nested)]
+    pub cost: CalculateCostInput,
+
+    /// Client name shown on the quote
+    #[validate(length(max = 200))]
+    #[serde(default)]
+    pub client_name: Option<String>,
+
+    /// Project name shown on the quote
+    #[validate(length(max = 200))]
+    #[serde(default)]
+    pub project_name: Option<String>,
+
+    /// Include installation labor in the quote
+    #[schemars(description = "Include installation labor in the quote (default: true)")]
+    #[serde(default = "default_true")]
+    pub include_labor: bool,
+
+    /// Format of the rendered document
+    #[schemars(description = "Rendered document format: markdown or html (default: markdown)")]
+    #[serde(default)]
+    pub format: QuoteFormat,
+}
+
+/// A priced line on a quote
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct QuoteLine {
+    /// What is being sold
+    pub description: String,
+    /// Number of units
+    pub quantity: u32,
+    /// Price per unit
+    pub unit_price: f64,
+    /// Line amount
+    pub amount: f64,
+}
+
+/// A formal quote for a gum wall project
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Quote {
+    /// Unique quote ID (e.g., "Q-20260101-1A2B3C4D")
+    pub quote_id: String,
+    /// Creation time (RFC 3339)
+    pub created_at: String,
+    /// Last day the quote is valid (YYYY-MM-DD)
+    pub valid_until: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_name: Option<String>,
+    /// Currency code for all amounts
+    pub currency: String,
+    /// Gum packs and labor
+    pub lines: Vec<QuoteLine>,
+    /// Gum cost before discount
+    pub materials_subtotal: f64,
+    /// Volume discount applied to materials, in percent
+    pub discount_percent: f64,
+    /// Volume discount amount
+    pub discount: f64,
+    /// Weight of the gum shipped, in kilograms
+    pub shipping_weight_kg: f64,
+    /// Shipping charge
+    pub shipping: f64,
+    /// Installation labor (0 when not included)
+    pub labor: f64,
+    /// Amount the tax is charged on
+    pub taxable_amount: f64,
+    /// Tax rate as a fraction
+    pub tax_rate: f64,
+    /// Tax amount
+    pub tax: f64,
+    /// Amount due
+    pub total: f64,
+    /// Gum types that could not be quoted
+    #[serde(default)]
+    pub warnings: Vec<CostWarning>,
+    /// Why requested installation labor could not be quoted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labor_warning: Option<String>,
+    /// True when prices come from the built-in fallback list, not the live catalog
+    #[serde(default)]
+    pub fallback_prices: bool,
+}
+
+/// Output of the generate_gum_quote tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GenerateQuoteOutput {
+    /// Structured quote
+    pub quote: Quote,
+    /// Format of `document`
+    pub format: QuoteFormat,
+    /// Rendered quote document
+    pub document: String,
+}
+
+// ============================================================================
+// Order Tool
+// ============================================================================
+
+/// Check that an idempotency key is 8-64 letters, digits, '-' or '_'
+fn validate_idempotency_key(value: &str) -> Result<(), validator::ValidationError> {
+    let valid_chars = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if (8..=64).contains(&value.len()) && valid_chars {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("idempotency_key")
+            .with_message("must be 8-64 letters, digits, '-' or '_'".into()))
+    }
+}
+
+/// Where an order is shipped
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct ShippingAddress {
+    /// Recipient name
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    /// Street address
+    #[validate(length(min = 1, max = 200))]
+    pub line1: String,
+    /// Apartment, suite, etc.
+    #[validate(length(max = 200))]
+    pub line2: Option<String>,
+    #[validate(length(min = 1, max = 100))]
+    pub city: String,
+    /// State, province or region
+    #[validate(length(max = 100))]
+    pub region: Option<String>,
+    #[validate(length(min = 1, max = 20))]
+    pub postal_code: String,
+    /// ISO 3166-1 alpha-2 country code (e.g., "US")
+    #[validate(length(equal = 2))]
+    pub country: String,
+}
+
+/// Input for the place_gum_order tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct PlaceOrderInput {
+    /// Cost breakdown returned by calculate_gum_cost
+    #[schemars(description = "The full output of calculate_gum_cost; its shopping_list is ordered")]
+    pub cost: CalculateCostOutput,
+
+    /// Shipping address
+    #[validate(nested)]
+    pub shipping_address: ShippingAddress,
+
+    /// Key identifying this order; retries with the same key return the same order
+    #[schemars(
+        description = "Unique key for this order (8-64 letters, digits, '-' or '_'). \
+        Retrying with the same key never creates a second order."
+    )]
+    #[validate(custom(function = "validate_idempotency_key"))]
+    pub idempotency_key: String,
+
+    /// Validate and preview the order without creating it
+    #[schemars(description = "Preview the order without creating it (default: false)")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Output from the place_gum_order tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PlaceOrderOutput {
+    /// Order ID (derived from the idempotency key)
+    pub order_id: String,
+    /// Order status (e.g., "PENDING")
+    pub status: String,
+    /// Creation time (RFC 3339)
+    pub created_at: String,
+    /// Amount charged for the gum
+    pub total_amount: f64,
+    /// Currency code of the amounts
+    pub currency: String,
+    /// Packs ordered
+    pub items: Vec<ShoppingListItem>,
+    /// True when the order was only previewed
+    pub dry_run: bool,
+    /// True when an order with this idempotency key already existed
+    pub duplicate: bool,
+    /// True when the order was priced with the built-in fallback list, not the live catalog
+    #[serde(default)]
+    pub fallback_prices: bool,
+}
+
+// ============================================================================
+// Upload Tool
+// ============================================================================
+
+/// Input for the create_upload_url tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct CreateUploadUrlInput {
+    /// Name of the file being uploaded (used in the object key)
+    #[validate(length(min = 1, max = 200))]
+    #[schemars(description = "Name of the image file, e.g. \"wall.jpg\"")]
+    pub file_name: String,
+
+    /// MIME type of the file
+    #[validate(length(min = 1))]
+    #[schemars(description = "MIME type of the image, e.g. image/jpeg")]
+    pub content_type: String,
+
+    /// Exact size of the file in bytes
+    #[validate(range(min = 1))]
+    #[schemars(description = "Exact size of the image in bytes")]
+    pub content_length: u64,
+}
+
+/// Output from the create_upload_url tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CreateUploadUrlOutput {
+    /// Presigned URL to upload the image to
+    pub upload_url: String,
+    /// HTTP method for the upload (always "PUT")
+    pub method: String,
+    /// Headers the upload request must send unchanged
+    pub headers: HashMap<String, String>,
+    /// URI to pass to analyze_image_for_gum_wall once the upload is done
+    pub image_uri: String,
+    /// When the upload URL expires (RFC 3339)
+    pub expires_at: String,
+    /// Largest upload accepted, in bytes
+    pub max_bytes: u64,
+}
+
+// ============================================================================
+// Admin Tools (catalog management)
+// ============================================================================
+
+/// Check that a string is a `#RRGGBB` hex color
+fn validate_hex_color(value: &str) -> Result<(), validator::ValidationError> {
+    let hex = value.strip_prefix('#').unwrap_or("");
+    if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("hex_color")
+            .with_message("must be a hex color like #FF69B4".into()))
+    }
+}
+
+/// Input for the create_gum_type tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct CreateGumTypeInput {
+    /// Unique identifier (e.g., "dubble_bubble_pink")
+    #[validate(length(min = 1, max = 64))]
+    pub gum_id: String,
+    /// Display name
+    #[validate(length(min = 1, max = 128))]
+    pub name: String,
+    /// Hex color code
+    #[validate(custom(function = "validate_hex_color"))]
+    #[schemars(description = "Hex color code in #RRGGBB format")]
+    pub hex_color: String,
+    /// Price per piece in the gum's currency
+    #[validate(range(exclusive_min = 0.0, max = 100.0))]
+    #[schemars(description = "Price per piece in `currency` (greater than 0, at most 100)")]
+    pub price_per_piece: f64,
+    /// Brand name
+    pub brand: Option<String>,
+    /// Flavor description
+    pub flavor: Option<String>,
+    /// Whether the gum type can be purchased (default: true)
+    #[serde(default = "default_true")]
+    pub is_available: bool,
+    /// ISO 4217 currency of the prices
+    #[validate(length(equal = 3))]
+    #[schemars(
+        description = "ISO 4217 currency of price_per_piece and pack prices (default: the server's pricing currency, usually USD)"
+    )]
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Pack sizes the gum is sold in
+    #[validate(length(max = 20), nested)]
+    #[schemars(description = "Pack sizes and prices the gum is sold in (default: sold per piece)")]
+    #[serde(default)]
+    pub packs: Vec<GumPackPrice>,
+    /// Physical properties of one piece
+    #[validate(nested)]
+    #[schemars(description = "Weight, chewing time, sugar and volume per piece (default: server defaults)")]
+    #[serde(default)]
+    pub properties: GumProperties,
+}
+
+/// Input for the update_gum_type tool; only provided fields are changed
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct UpdateGumTypeInput {
+    /// Gum type to update
+    #[validate(length(min = 1, max = 64))]
+    pub gum_id: String,
+    /// New display name
+    #[validate(length(min = 1, max = 128))]
+    pub name: Option<String>,
+    /// New hex color code
+    #[validate(custom(function = "validate_hex_color"))]
+    pub hex_color: Option<String>,
+    /// New price per piece in the gum's currency
+    #[validate(range(exclusive_min = 0.0, max = 100.0))]
+    pub price_per_piece: Option<f64>,
+    /// New brand name
+    pub brand: Option<String>,
+    /// New flavor description
+    pub flavor: Option<String>,
+    /// New availability
+    pub is_available: Option<bool>,
+    /// New ISO 4217 currency of the prices
+    #[validate(length(equal = 3))]
+    pub currency: Option<String>,
+    /// New pack sizes, replacing the current ones
+    #[validate(length(max = 20), nested)]
+    #[schemars(description = "Pack sizes and prices replacing the current ones (empty: sold per piece)")]
+    pub packs: Option<Vec<GumPackPrice>>,
+    /// New physical properties; only the values given are changed
+    #[validate(nested)]
+    pub properties: Option<GumProperties>,
+}
+
+/// Input for the retire_gum_type tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct RetireGumTypeInput {
+    /// Gum type to mark as unavailable
+    #[validate(length(min = 1, max = 64))]
+    pub gum_id: String,
+}
+
+/// A single price change
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+pub struct PriceUpdate {
+    /// Gum type to reprice
+    #[validate(length(min = 1, max = 64))]
+    pub gum_id: String,
+    /// New price per piece in the gum's currency
+    #[validate(range(exclusive_min = 0.0, max = 100.0))]
+    pub price_per_piece: f64,
+}
+
+/// Input for the bulk_update_gum_prices tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+#[schemars(deny_unknown_fields)]
+pub struct BulkPriceUpdateInput {
+    /// Price changes to apply (1-100)
+    #[validate(length(min = 1, max = 100), nested)]
+    pub updates: Vec<PriceUpdate>,
+}
+
+/// Result of one price change in a bulk update
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PriceUpdateResult {
+    pub gum_id: String,
+    /// Whether the price was updated
+    pub updated: bool,
+    /// Error message if the update failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Output from the bulk_update_gum_prices tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BulkPriceUpdateOutput {
+    pub results: Vec<PriceUpdateResult>,
+    pub updated_count: u32,
+    pub failed_count: u32,
 }
 
 // ============================================================================
@@ -239,6 +1060,45 @@ pub struct ListGumTypesData {
 #[derive(Debug, Deserialize)]
 pub struct ListGumTypesResult {
     pub items: Vec<GumTypeRecord>,
+    #[serde(rename = "nextToken", default)]
+    pub next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGumTypeData {
+    #[serde(rename = "createGumType")]
+    pub create_gum_type: GumTypeRecord,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateGumTypeData {
+    #[serde(rename = "updateGumType")]
+    pub update_gum_type: GumTypeRecord,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetOrderData {
+    #[serde(rename = "getOrder")]
+    pub get_order: Option<OrderRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrderData {
+    #[serde(rename = "createOrder")]
+    pub create_order: OrderRecord,
+}
+
+/// `Order` record from AppSync (`items` is AWSJSON, returned as a JSON string)
+#[derive(Debug, Deserialize)]
+pub struct OrderRecord {
+    #[serde(rename = "orderId")]
+    pub order_id: String,
+    pub items: serde_json::Value,
+    #[serde(rename = "totalAmount")]
+    pub total_amount: f64,
+    pub status: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
 }
 
 /// DynamoDB record from AppSync (matches Amplify schema field names)
@@ -256,6 +1116,33 @@ pub struct GumTypeRecord {
     pub flavor: Option<String>,
     #[serde(rename = "isAvailable", default = "default_true")]
     pub is_available: bool,
+    pub currency: Option<String>,
+    /// `GumProduct` records linked to this gum type
+    pub products: Option<GumProductConnection>,
+    #[serde(rename = "weightGrams")]
+    pub weight_g: Option<f64>,
+    #[serde(rename = "chewMinutes")]
+    pub chew_minutes: Option<f64>,
+    #[serde(rename = "sugarGrams")]
+    pub sugar_g: Option<f64>,
+    #[serde(rename = "volumeCm3")]
+    pub volume_cm3: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GumProductConnection {
+    pub items: Vec<GumProductRecord>,
+}
+
+/// `GumProduct` record from AppSync; products without a pack price are not sold for walls
+#[derive(Debug, Deserialize)]
+pub struct GumProductRecord {
+    #[serde(rename = "productId", default)]
+    pub product_id: Option<String>,
+    #[serde(rename = "packSize")]
+    pub pack_size: u32,
+    #[serde(rename = "packPrice")]
+    pub pack_price: Option<f64>,
 }
 
 impl From<GumTypeRecord> for GumType {
@@ -268,10 +1155,143 @@ impl From<GumTypeRecord> for GumType {
             brand: record.brand,
             flavor: record.flavor,
             is_available: record.is_available,
+            currency: record.currency,
+            packs: record
+                .products
+                .map(|products| products.items)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|product| {
+                    Some(GumPackPrice {
+                        pack_size: product.pack_size,
+                        price: product.pack_price?,
+                    })
+                })
+                .collect(),
+            properties: GumProperties {
+                weight_g: record.weight_g,
+                chew_minutes: record.chew_minutes,
+                sugar_g: record.sugar_g,
+                volume_cm3: record.volume_cm3,
+            },
         }
     }
 }
 
+// ============================================================================
+// Image Analysis (structured vision output)
+// ============================================================================
+
+/// A rectangle in normalized image coordinates (0-1, origin at the top left)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema, Validate)]
+#[validate(schema(function = "validate_bounding_box"))]
+pub struct BoundingBox {
+    /// Left edge as a fraction of the image width
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub x: f64,
+    /// Top edge as a fraction of the image height
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub y: f64,
+    /// Width as a fraction of the image width
+    #[validate(range(exclusive_min = 0.0, max = 1.0))]
+    pub width: f64,
+    /// Height as a fraction of the image height
+    #[validate(range(exclusive_min = 0.0, max = 1.0))]
+    pub height: f64,
+}
+
+impl BoundingBox {
+    /// Whether the normalized point lies inside the box
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    /// Center of the box
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// Check that a bounding box lies inside the image (with a little rounding slack)
+fn validate_bounding_box(bbox: &BoundingBox) -> Result<(), validator::ValidationError> {
+    if bbox.x + bbox.width <= 1.001 && bbox.y + bbox.height <= 1.001 {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("bounding_box")
+            .with_message("must lie inside the image".into()))
+    }
+}
+
+/// A part of the image whose detail should be preserved
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+pub struct KeyRegion {
+    /// What the region shows (e.g., "eyes")
+    #[validate(length(min = 1, max = 100))]
+    pub label: String,
+    /// Where the region is
+    #[validate(nested)]
+    pub bbox: BoundingBox,
+    /// How much detail matters here, from 0 (none) to 1 (most)
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub importance: f64,
+}
+
+/// Structured description of an image from the vision provider
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+pub struct ImageAnalysis {
+    /// The main subject or scene, in a few words
+    #[validate(length(min = 1, max = 200))]
+    pub subject: String,
+    /// What would translate well to a gum mosaic (1-3 sentences)
+    #[validate(length(min = 1, max = 1000))]
+    pub description: String,
+    /// The part of the image the design should be centered on
+    #[serde(default)]
+    #[validate(nested)]
+    pub focal_point: Option<BoundingBox>,
+    /// Parts of the image whose detail should be preserved
+    #[serde(default)]
+    #[validate(length(max = 8), nested)]
+    pub key_regions: Vec<KeyRegion>,
+    /// Colors that suit the design, as #RRGGBB hex codes
+    #[serde(default)]
+    #[validate(length(max = 12), custom(function = "validate_palette"))]
+    pub suggested_palette: Vec<String>,
+}
+
+/// Check that every palette entry is a `#RRGGBB` hex color
+fn validate_palette(palette: &[String]) -> Result<(), validator::ValidationError> {
+    palette.iter().try_for_each(|color| validate_hex_color(color))
+}
+
+/// Kind of content that cannot be shown on a public wall
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationCategory {
+    Sexual,
+    Violence,
+    Hate,
+    SelfHarm,
+    Drugs,
+    /// Anything else unsuitable for a public wall
+    #[serde(other)]
+    Other,
+}
+
+/// Result of screening an image before a wall is built from it
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Validate)]
+pub struct ModerationResult {
+    /// Whether the image is unsuitable for a public wall
+    pub flagged: bool,
+    /// Categories of unsuitable content found
+    #[serde(default)]
+    pub categories: Vec<ModerationCategory>,
+    /// Why the image was flagged, in one sentence
+    #[serde(default)]
+    #[validate(length(max = 500))]
+    pub reason: Option<String>,
+}
+
 // ============================================================================
 // Vision API Types
 // ============================================================================
@@ -282,6 +1302,15 @@ pub struct VisionRequest {
     pub model: String,
     pub messages: Vec<VisionMessage>,
     pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+/// Requested format of the model's reply (e.g., `json_object`)
+#[derive(Debug, Serialize)]
+pub struct ResponseFormat {
+    #[serde(rename = "type")]
+    pub format_type: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -320,6 +1349,68 @@ pub struct VisionResponseMessage {
     pub content: String,
 }
 
+/// Amazon Bedrock Converse API request
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverseRequest {
+    pub messages: Vec<ConverseMessage>,
+    pub inference_config: ConverseInferenceConfig,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConverseMessage {
+    pub role: String,
+    pub content: Vec<ConverseContent>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConverseContent {
+    Text(String),
+    Image(ConverseImage),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConverseImage {
+    /// Image format: "png", "jpeg", "gif" or "webp"
+    pub format: String,
+    pub source: ConverseImageSource,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConverseImageSource {
+    /// Base64-encoded image data
+    pub bytes: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverseInferenceConfig {
+    pub max_tokens: u32,
+}
+
+/// Amazon Bedrock Converse API response
+#[derive(Debug, Deserialize)]
+pub struct ConverseResponse {
+    pub output: ConverseOutput,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConverseOutput {
+    pub message: ConverseResponseMessage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConverseResponseMessage {
+    pub content: Vec<ConverseResponseContent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConverseResponseContent {
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -334,6 +1425,9 @@ mod tests {
             brand: None,
             flavor: None,
             is_available: true,
+            currency: None,
+            packs: Vec::new(),
+            properties: Default::default(),
         };
         let rgb = gum.to_rgb().unwrap();
         assert_eq!(rgb.r, 255);
@@ -344,21 +1438,71 @@ mod tests {
     #[test]
     fn test_analyze_input_validation() {
         let input = AnalyzeImageInput {
-            s3_uri: "s3://bucket/key.jpg".to_string(),
+            image_uri: Some("s3://bucket/key.jpg".to_string()),
+            image_base64: None,
+            image_mime_type: None,
+            image_resource: None,
             wall_width_cm: 200.0,
             wall_height_cm: 150.0,
             gum_diameter_cm: 2.0,
+            overage_percent: None,
+            min_spare_pieces: None,
+            output: None,
+            skip_vision: false,
         };
         assert!(input.validate().is_ok());
     }
 
+    #[test]
+    fn test_s3_uri_is_accepted_as_image_uri() {
+        let input: AnalyzeImageInput = serde_json::from_value(serde_json::json!({
+            "s3_uri": "s3://bucket/key.jpg",
+            "wall_width_cm": 200.0,
+            "wall_height_cm": 150.0
+        }))
+        .unwrap();
+        assert_eq!(input.image_uri.as_deref(), Some("s3://bucket/key.jpg"));
+    }
+
     #[test]
     fn test_invalid_wall_dimensions() {
         let input = AnalyzeImageInput {
-            s3_uri: "s3://bucket/key.jpg".to_string(),
+            image_uri: Some("s3://bucket/key.jpg".to_string()),
+            image_base64: None,
+            image_mime_type: None,
+            image_resource: None,
             wall_width_cm: 5.0, // Too small
             wall_height_cm: 150.0,
             gum_diameter_cm: 2.0,
+            overage_percent: None,
+            min_spare_pieces: None,
+            output: None,
+            skip_vision: false,
+        };
+        assert!(input.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_overage() {
+        let input = AnalyzeImageInput {
+            image_uri: Some("s3://bucket/key.jpg".to_string()),
+            image_base64: None,
+            image_mime_type: None,
+            image_resource: None,
+            wall_width_cm: 200.0,
+            wall_height_cm: 150.0,
+            gum_diameter_cm: 2.0,
+            overage_percent: Some(150.0),
+            min_spare_pieces: None,
+            output: None,
+            skip_vision: false,
+        };
+        assert!(input.validate().is_err());
+
+        let input = AnalyzeImageInput {
+            overage_percent: None,
+            min_spare_pieces: Some(u32::MAX),
+            ..input
         };
         assert!(input.validate().is_err());
     }
@@ -0,0 +1,217 @@
+//! Presigned image uploads
+//!
+//! The create_upload_url tool lets clients put an image into S3 for analysis
+//! without AWS credentials. Content type, size and key are decided here: the
+//! client only names the file, and the presigned URL only accepts an upload
+//! with exactly the requested type and length.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use crate::artifacts::file_stem;
+use crate::config::UploadsConfig;
+use crate::context::AppContext;
+use crate::error::GumWallError;
+use crate::s3;
+use crate::types::{CreateUploadUrlInput, CreateUploadUrlOutput};
+use pmcp::{ServerBuilder, TypedTool};
+use std::sync::Arc;
+use std::time::Duration;
+use validator::Validate;
+
+/// Check an upload against the allowed content types and size limit
+///
+/// Returns the normalized content type.
+pub fn check_upload(input: &CreateUploadUrlInput, config: &UploadsConfig) -> Result<String> {
+    let content_type = input
+        .content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    if !config
+        .allowed_content_types
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(&content_type))
+    {
+        return Err(anyhow!(
+            "Content type '{}' is not allowed; expected one of: {}",
+            content_type,
+            config.allowed_content_types.join(", ")
+        ));
+    }
+    if input.content_length > config.max_bytes {
+        return Err(anyhow!(
+            "Image is too large ({} bytes, maximum {})",
+            input.content_length,
+            config.max_bytes
+        ));
+    }
+    Ok(content_type)
+}
+
+/// Object key for an upload: `{prefix}{YYYY/MM/DD}/{random}-{name}.{ext}`
+///
+/// The extension always matches the content type, whatever the file was called.
+pub fn upload_key(prefix: &str, file_name: &str, content_type: &str, now: DateTime<Utc>) -> String {
+    let extension = match content_type {
+        "image/jpeg" => "jpg",
+        other => other.rsplit('/').next().unwrap_or("bin"),
+    };
+    let suffix = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    format!(
+        "{}{}/{}-{}.{}",
+        prefix,
+        now.format("%Y/%m/%d"),
+        suffix,
+        file_stem(file_name),
+        extension
+    )
+}
+
+/// Create a presigned upload URL for an image
+pub async fn create_upload_url(
+    context: &AppContext,
+    input: &CreateUploadUrlInput,
+    now: DateTime<Utc>,
+) -> Result<CreateUploadUrlOutput> {
+    let config = &context.config.uploads;
+    let bucket = config
+        .bucket
+        .as_deref()
+        .ok_or_else(|| anyhow!("Uploads are not configured (set uploads.bucket)"))?;
+    let content_type = check_upload(input, config)?;
+
+    let key = upload_key(&config.prefix, &input.file_name, &content_type, now);
+    let expires_in = Duration::from_secs(config.url_expiry_secs);
+    let client = context.s3().await?;
+    let upload = s3::presign_put(
+        client,
+        bucket,
+        &key,
+        &content_type,
+        input.content_length,
+        expires_in,
+    )
+    .await?;
+
+    Ok(CreateUploadUrlOutput {
+        upload_url: upload.url,
+        method: "PUT".to_string(),
+        headers: upload.headers.into_iter().collect(),
+        image_uri: format!("s3://{}/{}", bucket, key),
+        expires_at: (now + chrono::Duration::seconds(config.url_expiry_secs as i64)).to_rfc3339(),
+        max_bytes: config.max_bytes,
+    })
+}
+
+/// Register the upload tool on the server builder
+pub(crate) fn register_upload_tools(
+    builder: ServerBuilder,
+    context: Arc<AppContext>,
+) -> ServerBuilder {
+    builder.tool(
+        "create_upload_url",
+        TypedTool::new(
+            "create_upload_url",
+            move |input: CreateUploadUrlInput, _extra| {
+                let context = context.clone();
+                Box::pin(async move {
+                    input
+                        .validate()
+                        .map_err(|e| GumWallError::Validation(e.to_string()))?;
+                    check_upload(&input, &context.config.uploads)
+                        .map_err(|e| GumWallError::InvalidInput(e.to_string()))?;
+                    tracing::info!("Creating upload URL for '{}'", input.file_name);
+
+                    let output = create_upload_url(&context, &input, Utc::now())
+                        .await
+                        .map_err(|e| GumWallError::StorageUnavailable(e.to_string()))?;
+
+                    Ok(serde_json::to_value(output)
+                        .map_err(|e| GumWallError::Internal(e.to_string()))?)
+                })
+            },
+        )
+        .with_description(
+            "Get a presigned URL to upload an image to S3 for analysis. Give the file name, its \
+            content type and its exact size in bytes, then PUT the file to upload_url with the \
+            returned headers and pass image_uri to analyze_image_for_gum_wall.",
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GumWallConfig;
+    use crate::image_source::{load_image, ImageSource};
+    use crate::test_support::{png, test_context, MockS3};
+
+    fn input(content_type: &str, content_length: u64) -> CreateUploadUrlInput {
+        CreateUploadUrlInput {
+            file_name: "../Back Wall.PNG".to_string(),
+            content_type: content_type.to_string(),
+            content_length,
+        }
+    }
+
+    #[test]
+    fn test_upload_policy() {
+        let config = UploadsConfig::default();
+        assert_eq!(
+            check_upload(&input("Image/PNG; charset=binary", 100), &config).unwrap(),
+            "image/png"
+        );
+        assert!(check_upload(&input("image/svg+xml", 100), &config).is_err());
+        assert!(check_upload(&input("image/png", config.max_bytes + 1), &config).is_err());
+
+        // The client cannot escape the prefix or pick the extension
+        let now = "2026-10-18T12:00:00Z".parse().unwrap();
+        let key = upload_key("uploads/", "../Back Wall.PNG", "image/jpeg", now);
+        assert!(key.starts_with("uploads/2026/10/18/"));
+        assert!(key.ends_with("-___Back_Wall.jpg"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_to_s3_compatible_endpoint() {
+        let s3 = MockS3::start().await;
+        let mut config = GumWallConfig {
+            s3: s3.config(),
+            ..GumWallConfig::default()
+        };
+        config.uploads.bucket = Some("intake".to_string());
+        config.uploads.max_bytes = 1024;
+        let context = test_context(config);
+
+        let too_large = create_upload_url(&context, &input("image/png", 4096), Utc::now()).await;
+        assert!(too_large.is_err());
+        let not_allowed = create_upload_url(&context, &input("text/html", 10), Utc::now()).await;
+        assert!(not_allowed.is_err());
+
+        let image = png();
+        let output = create_upload_url(
+            &context,
+            &input("image/png", image.len() as u64),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+        assert!(output.image_uri.starts_with("s3://intake/uploads/"));
+        assert!(output.upload_url.contains("X-Amz-Signature="));
+        // Type and length are signed
+        assert!(output.upload_url.contains("content-length%3Bcontent-type"));
+        assert_eq!(output.headers["content-type"], "image/png");
+
+        let mut upload = reqwest::Client::new().put(&output.upload_url);
+        for (name, value) in &output.headers {
+            upload = upload.header(name, value);
+        }
+        let response = upload.body(image.clone()).send().await.unwrap();
+        assert!(response.status().is_success());
+
+        let source = ImageSource::parse_uri(&output.image_uri, &context.config.images).unwrap();
+        let loaded = load_image(source, &context).await.unwrap();
+        assert_eq!(loaded.bytes, image);
+    }
+}
@@ -1,81 +1,666 @@
-//! GPT-4o-mini Vision API integration
+//! Image analysis and moderation from vision models
+//!
+//! A [`VisionProvider`] returns an [`ImageAnalysis`]: the subject, a focal
+//! point, key regions and a suggested palette. The description goes into the
+//! tool output, and the focal point and key regions guide the quantizer.
+//! Providers also screen images for content that cannot go on a public wall.
+//! Providers exist for OpenAI-compatible chat completions APIs, the Amazon
+//! Bedrock Converse API, and an offline provider that analyzes the image from
+//! its own colors and brightness.
 
 use anyhow::{anyhow, Context, Result};
-use crate::types::{ImageUrl, VisionContent, VisionMessage, VisionRequest, VisionResponse};
+use async_trait::async_trait;
+use base64::Engine;
+use crate::config::{Secret, VisionConfig, VisionProviderKind};
+use crate::quantize;
+use crate::types::{
+    BoundingBox, ConverseContent, ConverseImage, ConverseImageSource, ConverseInferenceConfig,
+    ConverseMessage, ConverseRequest, ConverseResponse, ImageAnalysis, ImageUrl,
+    ModerationCategory, ModerationResult, ResponseFormat, Rgb, VisionContent, VisionMessage,
+    VisionRequest, VisionResponse,
+};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+use validator::Validate;
 
-const OPENAI_API_URL: &str = "https://api.openai.com/v1/chat/completions";
-const VISION_MODEL: &str = "gpt-4o-mini";
+const PROMPT: &str = r#"Analyze this image for a gum wall art project. The image will be converted into a paint-by-number style mosaic made of colored chewing gum pieces.
 
-/// Analyze an image using GPT-4o-mini vision capabilities
+Reply with only a JSON object that matches the JSON schema below:
+- subject: the main subject or scene, in a few words
+- description: what would translate well to a low-resolution gum mosaic (1-3 sentences, under 100 words)
+- focal_point: the part of the image the design should be centered on
+- key_regions: up to 8 regions whose detail should be preserved (e.g. eyes, text, outlines), each with an importance from 0 to 1
+- suggested_palette: up to 8 #RRGGBB colors that suit the design, favoring gum colors (pink, red, yellow, green, blue, white, purple, orange)
+
+Bounding boxes are fractions of the image size (0 to 1), measured from the top left corner.
+
+JSON schema:
+"#;
+
+const MODERATION_PROMPT: &str = r#"This image was submitted to be turned into a chewing gum mosaic on a public wall that anyone, including children, can see.
+
+Decide whether the image is unsuitable for such a wall: sexual content or nudity, graphic violence, hate symbols, self-harm, drug use, or anything else a venue would refuse to display. Ordinary photos of people, animals, landscapes, logos and artwork are suitable.
+
+Reply with only a JSON object that matches the JSON schema below. Set flagged to true only for unsuitable images, list the categories that apply, and give a one-sentence reason when flagged.
+
+JSON schema:
+"#;
+
+/// The prompt, followed by the JSON schema of [`ImageAnalysis`]
+fn prompt() -> String {
+    let schema = schemars::schema_for!(ImageAnalysis);
+    format!("{}{}", PROMPT, serde_json::to_string(&schema).unwrap_or_default())
+}
+
+/// The moderation prompt, followed by the JSON schema of [`ModerationResult`]
+fn moderation_prompt() -> String {
+    let schema = schemars::schema_for!(ModerationResult);
+    format!("{}{}", MODERATION_PROMPT, serde_json::to_string(&schema).unwrap_or_default())
+}
+
+/// Something that can analyze an image for gum wall art conversion
+#[async_trait]
+pub trait VisionProvider: Send + Sync {
+    /// Short name used in logs (e.g. "openai")
+    fn name(&self) -> &str;
+
+    /// Analyze the image for gum wall art conversion
+    async fn analyze_image(&self, image: &[u8], mime_type: &str) -> Result<ImageAnalysis>;
+
+    /// Screen the image for content that cannot be shown on a public wall
+    async fn moderate_image(&self, image: &[u8], mime_type: &str) -> Result<ModerationResult>;
+}
+
+/// Parse and validate the JSON analysis returned by a vision model
+///
+/// Text around the JSON object, such as a Markdown code fence, is ignored.
+pub fn parse_analysis(text: &str) -> Result<ImageAnalysis> {
+    parse_reply(text, "analysis")
+}
+
+/// Parse and validate the JSON moderation result returned by a vision model
+pub fn parse_moderation(text: &str) -> Result<ModerationResult> {
+    parse_reply(text, "moderation result")
+}
+
+fn parse_reply<T: DeserializeOwned + Validate>(text: &str, what: &str) -> Result<T> {
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err(anyhow!("Vision model did not return a JSON object")),
+    };
+    let reply: T = serde_json::from_str(json)
+        .with_context(|| format!("Vision model returned a malformed {}", what))?;
+    reply
+        .validate()
+        .map_err(|e| anyhow!("Vision model returned an invalid {}: {}", what, e))?;
+    Ok(reply)
+}
+
+/// Build the vision provider selected by the configuration
+pub fn provider_from_config(
+    config: &VisionConfig,
+    http: reqwest::Client,
+) -> Arc<dyn VisionProvider> {
+    match config.provider_kind() {
+        VisionProviderKind::Openai => Arc::new(OpenAiVision::new(config, http)),
+        VisionProviderKind::Bedrock => Arc::new(BedrockVision::new(config, http)),
+        VisionProviderKind::Local => Arc::new(LocalVision),
+    }
+}
+
+fn encode_base64(image: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(image)
+}
+
+/// Downscale an image so its longest edge is at most `max_edge` pixels
+///
+/// Images that already fit are returned unchanged. Downscaled images are
+/// re-encoded as JPEG, or as PNG when they have an alpha channel. Returns the
+/// bytes and MIME type to send.
+pub fn downscale_image<'a>(
+    image: &'a [u8],
+    mime_type: &'a str,
+    max_edge: u32,
+) -> Result<(Cow<'a, [u8]>, &'a str)> {
+    let (width, height) = image::ImageReader::new(Cursor::new(image))
+        .with_guessed_format()?
+        .into_dimensions()
+        .context("Failed to read image dimensions")?;
+    if width.max(height) <= max_edge {
+        return Ok((Cow::Borrowed(image), mime_type));
+    }
+
+    let resized = quantize::load_image_from_bytes(image)?.thumbnail(max_edge, max_edge);
+    let mut bytes = Cursor::new(Vec::new());
+    let mime_type = if resized.color().has_alpha() {
+        resized.write_to(&mut bytes, image::ImageFormat::Png)?;
+        "image/png"
+    } else {
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 85);
+        resized.to_rgb8().write_with_encoder(encoder)?;
+        "image/jpeg"
+    };
+    Ok((Cow::Owned(bytes.into_inner()), mime_type))
+}
+
+/// Longest wait between retries, whatever the server asks for
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Request timeout and retries for remote vision APIs
+#[derive(Debug, Clone)]
+struct RetryPolicy {
+    timeout: Duration,
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    fn new(config: &VisionConfig) -> Self {
+        RetryPolicy {
+            timeout: Duration::from_secs(config.timeout_secs),
+            max_retries: config.max_retries,
+            base_delay: Duration::from_millis(config.retry_delay_ms),
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0), doubling each time
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY)
+    }
+
+    /// Send a request, retrying rate limits (429), server errors (5xx) and timeouts
+    ///
+    /// `build` is called for every attempt. A `Retry-After` header given in
+    /// seconds replaces the backoff delay. The last response is returned
+    /// whatever its status.
+    async fn send(
+        &self,
+        service: &str,
+        build: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let result = build().timeout(self.timeout).send().await;
+            let retryable = match &result {
+                Ok(response) => {
+                    response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || response.status().is_server_error()
+                }
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+            if !retryable || attempt >= self.max_retries {
+                return result.with_context(|| format!("Failed to send request to {}", service));
+            }
+
+            let retry_after = result.as_ref().ok().and_then(|response| {
+                let seconds = response.headers().get(reqwest::header::RETRY_AFTER)?;
+                seconds.to_str().ok()?.trim().parse().ok().map(Duration::from_secs)
+            });
+            let delay = retry_after
+                .map(|delay| delay.min(MAX_RETRY_DELAY))
+                .unwrap_or_else(|| self.backoff(attempt));
+            match &result {
+                Ok(response) => tracing::warn!(
+                    "{} returned {}, retrying in {:?}",
+                    service,
+                    response.status(),
+                    delay
+                ),
+                Err(e) => {
+                    tracing::warn!("{} request failed ({}), retrying in {:?}", service, e, delay)
+                }
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+// ============================================================================
+// OpenAI-compatible chat completions
+// ============================================================================
+
+/// Vision model behind an OpenAI-compatible chat completions API (GPT-4o-mini by default)
+pub struct OpenAiVision {
+    client: reqwest::Client,
+    api_url: String,
+    model: String,
+    api_key: Option<Secret>,
+    max_tokens: u32,
+    max_image_edge: u32,
+    retry: RetryPolicy,
+}
+
+impl OpenAiVision {
+    pub fn new(config: &VisionConfig, client: reqwest::Client) -> Self {
+        OpenAiVision {
+            client,
+            api_url: config.api_url.clone(),
+            model: config.model.clone(),
+            api_key: config.api_key.clone(),
+            max_tokens: config.max_tokens,
+            max_image_edge: config.max_image_edge,
+            retry: RetryPolicy::new(config),
+        }
+    }
+
+    /// Send the prompt and image, returning the model's reply
+    async fn complete(&self, prompt: String, image: &[u8], mime_type: &str) -> Result<String> {
+        let api_key = self
+            .api_key
+            .as_ref()
+            .ok_or_else(|| anyhow!("Vision API key is not configured (set OPENAI_API_KEY)"))?;
+
+        let (image, mime_type) = downscale_image(image, mime_type, self.max_image_edge)?;
+        let data_url = format!("data:{};base64,{}", mime_type, encode_base64(&image));
+
+        let request = VisionRequest {
+            model: self.model.clone(),
+            messages: vec![VisionMessage {
+                role: "user".to_string(),
+                content: vec![
+                    VisionContent::Text { text: prompt },
+                    VisionContent::ImageUrl {
+                        image_url: ImageUrl { url: data_url },
+                    },
+                ],
+            }],
+            max_tokens: self.max_tokens,
+            response_format: Some(ResponseFormat {
+                format_type: "json_object".to_string(),
+            }),
+        };
+
+        let response = self
+            .retry
+            .send("OpenAI API", || {
+                self.client
+                    .post(&self.api_url)
+                    .header("Authorization", format!("Bearer {}", api_key.expose()))
+                    .header("Content-Type", "application/json")
+                    .json(&request)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_body = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "OpenAI API error ({}): {}",
+                status,
+                error_body
+            ));
+        }
+
+        let vision_response: VisionResponse = response
+            .json()
+            .await
+            .context("Failed to parse OpenAI API response")?;
+
+        vision_response
+            .choices
+            .into_iter()
+            .next()
+            .map(|c| c.message.content)
+            .ok_or_else(|| anyhow!("No response from vision model"))
+    }
+}
+
+#[async_trait]
+impl VisionProvider for OpenAiVision {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    async fn analyze_image(&self, image: &[u8], mime_type: &str) -> Result<ImageAnalysis> {
+        parse_analysis(&self.complete(prompt(), image, mime_type).await?)
+    }
+
+    async fn moderate_image(&self, image: &[u8], mime_type: &str) -> Result<ModerationResult> {
+        parse_moderation(&self.complete(moderation_prompt(), image, mime_type).await?)
+    }
+}
+
+// ============================================================================
+// Amazon Bedrock Converse
+// ============================================================================
+
+/// Vision model on Amazon Bedrock, called through the Converse API with a Bedrock API key
+pub struct BedrockVision {
+    client: reqwest::Client,
+    endpoint: String,
+    model_id: String,
+    api_key: Option<Secret>,
+    max_tokens: u32,
+    max_image_edge: u32,
+    retry: RetryPolicy,
+}
+
+impl BedrockVision {
+    pub fn new(config: &VisionConfig, client: reqwest::Client) -> Self {
+        BedrockVision {
+            client,
+            endpoint: config.bedrock.endpoint(),
+            model_id: config.bedrock.model_id.clone(),
+            api_key: config.bedrock.api_key.clone(),
+            max_tokens: config.max_tokens,
+            max_image_edge: config.max_image_edge,
+            retry: RetryPolicy::new(config),
+        }
+    }
+
+    /// `{endpoint}/model/{model_id}/converse`, with the model ID escaped as one path segment
+    fn converse_url(&self) -> Result<reqwest::Url> {
+        let mut url = reqwest::Url::parse(&self.endpoint)
+            .with_context(|| format!("Invalid Bedrock endpoint '{}'", self.endpoint))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid Bedrock endpoint '{}'", self.endpoint))?
+            .pop_if_empty()
+            .extend(["model", self.model_id.as_str(), "converse"]);
+        Ok(url)
+    }
+
+    /// Send the prompt and image, returning the model's reply
+    async fn complete(&self, prompt: String, image: &[u8], mime_type: &str) -> Result<String> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            anyhow!("Bedrock API key is not configured (set AWS_BEARER_TOKEN_BEDROCK)")
+        })?;
+        if !matches!(mime_type, "image/png" | "image/jpeg" | "image/gif" | "image/webp") {
+            return Err(anyhow!("Bedrock does not accept {} images", mime_type));
+        }
+        let (image, mime_type) = downscale_image(image, mime_type, self.max_image_edge)?;
+        let format = match mime_type {
+            "image/png" => "png",
+            "image/jpeg" => "jpeg",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            other => return Err(anyhow!("Bedrock does not accept {} images", other)),
+        };
+
+        let request = ConverseRequest {
+            messages: vec![ConverseMessage {
+                role: "user".to_string(),
+                content: vec![
+                    ConverseContent::Text(prompt),
+                    ConverseContent::Image(ConverseImage {
+                        format: format.to_string(),
+                        source: ConverseImageSource {
+                            bytes: encode_base64(&image),
+                        },
+                    }),
+                ],
+            }],
+            inference_config: ConverseInferenceConfig {
+                max_tokens: self.max_tokens,
+            },
+        };
+
+        let url = self.converse_url()?;
+        let response = self
+            .retry
+            .send("Bedrock", || {
+                self.client
+                    .post(url.clone())
+                    .bearer_auth(api_key.expose())
+                    .json(&request)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Bedrock API error ({}): {}", status, error_body));
+        }
+
+        let converse: ConverseResponse = response
+            .json()
+            .await
+            .context("Failed to parse Bedrock response")?;
+
+        let text: Vec<String> = converse
+            .output
+            .message
+            .content
+            .into_iter()
+            .filter_map(|content| content.text)
+            .collect();
+        if text.is_empty() {
+            return Err(anyhow!("No response from vision model"));
+        }
+        Ok(text.join("\n"))
+    }
+}
+
+#[async_trait]
+impl VisionProvider for BedrockVision {
+    fn name(&self) -> &str {
+        "bedrock"
+    }
+
+    async fn analyze_image(&self, image: &[u8], mime_type: &str) -> Result<ImageAnalysis> {
+        parse_analysis(&self.complete(prompt(), image, mime_type).await?)
+    }
+
+    async fn moderate_image(&self, image: &[u8], mime_type: &str) -> Result<ModerationResult> {
+        parse_moderation(&self.complete(moderation_prompt(), image, mime_type).await?)
+    }
+}
+
+// ============================================================================
+// Local (offline)
+// ============================================================================
+
+/// Named colors used by the local provider, close to the gum palette
+const NAMED_COLORS: [(&str, Rgb); 11] = [
+    ("black", Rgb { r: 20, g: 20, b: 20 }),
+    ("white", Rgb { r: 245, g: 245, b: 245 }),
+    ("gray", Rgb { r: 128, g: 128, b: 128 }),
+    ("red", Rgb { r: 220, g: 20, b: 60 }),
+    ("orange", Rgb { r: 255, g: 140, b: 0 }),
+    ("yellow", Rgb { r: 255, g: 215, b: 0 }),
+    ("green", Rgb { r: 34, g: 139, b: 34 }),
+    ("blue", Rgb { r: 30, g: 144, b: 255 }),
+    ("purple", Rgb { r: 128, g: 0, b: 128 }),
+    ("pink", Rgb { r: 255, g: 105, b: 180 }),
+    ("brown", Rgb { r: 139, g: 69, b: 19 }),
+];
+
+/// Offline provider analyzing an image from its dominant colors and brightness
+///
+/// Needs no network access and always gives the same analysis for the same
+/// image, which makes it suitable for tests. Its moderation is the stub
+/// [`classify_skin_tones`].
+pub struct LocalVision;
+
+impl LocalVision {
+    /// Decode the image, shrunk to at most 64 pixels on its longest edge
+    fn small_image(image: &[u8]) -> Result<image::RgbImage> {
+        let mut image = quantize::load_image_from_bytes(image)?;
+        if image.width().max(image.height()) > 64 {
+            image = image.thumbnail(64, 64);
+        }
+        Ok(image.to_rgb8())
+    }
+}
+
+#[async_trait]
+impl VisionProvider for LocalVision {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn analyze_image(&self, image: &[u8], _mime_type: &str) -> Result<ImageAnalysis> {
+        Ok(analyze_statistics(&Self::small_image(image)?))
+    }
+
+    async fn moderate_image(&self, image: &[u8], _mime_type: &str) -> Result<ModerationResult> {
+        Ok(classify_skin_tones(&Self::small_image(image)?))
+    }
+}
+
+/// Share of skin-toned pixels at which the stub classifier flags an image
+const SKIN_TONE_THRESHOLD: f64 = 0.5;
+
+/// Stub moderation classifier that flags images which are mostly skin-toned
+///
+/// A crude offline stand-in for a real classifier, for tests and local
+/// development. It only knows a simple RGB skin-color rule, so it cannot
+/// screen a public wall on its own.
+pub fn classify_skin_tones(image: &image::RgbImage) -> ModerationResult {
+    let pixels = image.pixels().count().max(1) as f64;
+    let skin = image.pixels().filter(|pixel| is_skin_tone(pixel.0)).count() as f64;
+    let share = skin / pixels;
+    if share < SKIN_TONE_THRESHOLD {
+        return ModerationResult {
+            flagged: false,
+            categories: Vec::new(),
+            reason: None,
+        };
+    }
+    ModerationResult {
+        flagged: true,
+        categories: vec![ModerationCategory::Sexual],
+        reason: Some(format!(
+            "{:.0}% of the image is skin-toned (local stub classifier)",
+            share * 100.0
+        )),
+    }
+}
+
+/// RGB skin-color rule for images taken in daylight (Kovac et al.)
+fn is_skin_tone([r, g, b]: [u8; 3]) -> bool {
+    let spread = r.max(g).max(b) - r.min(g).min(b);
+    r > 95 && g > 40 && b > 20 && spread > 15 && r.abs_diff(g) > 15 && r > g && r > b
+}
+
+/// Analyze an image by its brightness, contrast and dominant named colors
 ///
-/// Returns a description of the image suitable for gum wall art conversion
-pub async fn analyze_image(
-    image_base64: &str,
-    mime_type: &str,
-) -> Result<String> {
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .map_err(|_| anyhow!("OPENAI_API_KEY environment variable is not set"))?;
-
-    let prompt = r#"Analyze this image for a gum wall art project. The image will be converted into a paint-by-number style mosaic made of colored chewing gum pieces.
-
-Please describe:
-1. The main subject or scene in the image (1-2 sentences)
-2. The dominant colors that would work well with available gum colors (pink, red, yellow, green, blue, white, purple, orange)
-3. Key visual elements that should be preserved in a low-resolution pixel art version
-
-Keep your response concise (under 100 words) and focus on what would translate well to a gum mosaic."#;
-
-    let data_url = format!("data:{};base64,{}", mime_type, image_base64);
-
-    let request = VisionRequest {
-        model: VISION_MODEL.to_string(),
-        messages: vec![VisionMessage {
-            role: "user".to_string(),
-            content: vec![
-                VisionContent::Text { text: prompt.to_string() },
-                VisionContent::ImageUrl {
-                    image_url: ImageUrl { url: data_url },
-                },
-            ],
-        }],
-        max_tokens: 300,
+/// When one color covers most of the image, the pixels that differ from it
+/// are taken to be the subject and give the focal point.
+pub fn analyze_statistics(image: &image::RgbImage) -> ImageAnalysis {
+    let pixels = image.pixels().count().max(1) as f64;
+    let mut counts = [0u32; NAMED_COLORS.len()];
+    let mut nearest_colors = Vec::with_capacity(pixels as usize);
+    let mut luminance_sum = 0.0;
+    let mut luminance_squares = 0.0;
+
+    for pixel in image.pixels() {
+        let [r, g, b] = pixel.0;
+        let luminance = (0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64) / 255.0;
+        luminance_sum += luminance;
+        luminance_squares += luminance * luminance;
+
+        let rgb = Rgb { r, g, b };
+        let nearest = (0..NAMED_COLORS.len())
+            .min_by_key(|&i| quantize::color_distance_squared(rgb, NAMED_COLORS[i].1))
+            .unwrap_or(0);
+        counts[nearest] += 1;
+        nearest_colors.push(nearest);
+    }
+
+    let brightness = luminance_sum / pixels;
+    let contrast = (luminance_squares / pixels - brightness * brightness).max(0.0).sqrt();
+
+    // Colors covering at least 5% of the image, most common first (ties by name)
+    let mut dominant: Vec<(&str, Rgb, f64)> = NAMED_COLORS
+        .iter()
+        .zip(counts)
+        .map(|((name, rgb), count)| (*name, *rgb, count as f64 / pixels * 100.0))
+        .filter(|(_, _, percent)| *percent >= 5.0)
+        .collect();
+    dominant.sort_by(|a, b| b.2.total_cmp(&a.2).then(a.0.cmp(b.0)));
+    dominant.truncate(3);
+
+    let tone = match brightness {
+        b if b < 0.35 => "dark",
+        b if b > 0.65 => "bright",
+        _ => "medium-toned",
+    };
+    let contrast = match contrast {
+        c if c > 0.25 => ", high-contrast",
+        c if c < 0.1 => ", low-contrast",
+        _ => "",
     };
+    let colors: Vec<String> = dominant
+        .iter()
+        .map(|(name, _, percent)| format!("{} ({:.0}%)", name, percent))
+        .collect();
+    let names: Vec<String> = dominant.iter().map(|(name, _, _)| name.to_string()).collect();
+
+    let description = format!(
+        "A {}{} image, mostly {}. Average brightness {:.0}%. \
+         Described offline from image statistics.",
+        tone,
+        contrast,
+        join_words(&colors).unwrap_or_else(|| "a mix of colors".to_string()),
+        brightness * 100.0
+    );
+    let subject = format!(
+        "Image in {}",
+        join_words(&names).unwrap_or_else(|| "mixed colors".to_string())
+    );
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(OPENAI_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await
-        .context("Failed to send request to OpenAI API")?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_body = response.text().await.unwrap_or_default();
-        return Err(anyhow!(
-            "OpenAI API error ({}): {}",
-            status,
-            error_body
-        ));
-    }
-
-    let vision_response: VisionResponse = response
-        .json()
-        .await
-        .context("Failed to parse OpenAI API response")?;
-
-    vision_response
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .ok_or_else(|| anyhow!("No response from vision model"))
+    // Bounding box of the pixels that differ from a background color
+    let background = (0..counts.len()).max_by_key(|&i| counts[i]).unwrap_or(0);
+    let mut focal_point = None;
+    if counts[background] as f64 >= pixels / 2.0 && counts[background] as f64 != pixels {
+        let (width, height) = image.dimensions();
+        let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+        for (i, nearest) in nearest_colors.iter().enumerate() {
+            if *nearest != background {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1));
+            }
+        }
+        focal_point = Some(BoundingBox {
+            x: x0 as f64 / width as f64,
+            y: y0 as f64 / height as f64,
+            width: (x1 - x0) as f64 / width as f64,
+            height: (y1 - y0) as f64 / height as f64,
+        });
+    }
+
+    ImageAnalysis {
+        subject,
+        description,
+        focal_point,
+        key_regions: Vec::new(),
+        suggested_palette: dominant
+            .iter()
+            .map(|(_, rgb, _)| format!("#{:02X}{:02X}{:02X}", rgb.r, rgb.g, rgb.b))
+            .collect(),
+    }
+}
+
+/// "a", "a and b", "a, b and c"
+fn join_words(words: &[String]) -> Option<String> {
+    match words {
+        [] => None,
+        [only] => Some(only.clone()),
+        [first @ .., last] => Some(format!("{} and {}", first.join(", "), last)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{encode_png, solid_png, MockServer};
+
+    /// A PNG that is three quarters blue and one quarter white
+    fn png() -> Vec<u8> {
+        encode_png(&image::RgbImage::from_fn(8, 8, |x, _| {
+            if x < 6 {
+                image::Rgb([30, 144, 255])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        }))
+    }
 
     #[test]
     fn test_vision_request_serialization() {
@@ -93,10 +678,203 @@ mod tests {
                 ],
             }],
             max_tokens: 300,
+            response_format: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
         assert!(json.contains("gpt-4o-mini"));
         assert!(json.contains("image_url"));
+        assert!(!json.contains("response_format"));
+    }
+
+    /// A valid analysis as a vision model would return it
+    const ANALYSIS: &str = r##"{
+        "subject": "A blue sky",
+        "description": "Clear sky with one white cloud.",
+        "focal_point": {"x": 0.5, "y": 0.1, "width": 0.3, "height": 0.2},
+        "key_regions": [
+            {"label": "cloud", "bbox": {"x": 0.5, "y": 0.1, "width": 0.3, "height": 0.2},
+             "importance": 0.8}
+        ],
+        "suggested_palette": ["#1E90FF", "#FFFFFF"]
+    }"##;
+
+    #[test]
+    fn test_parse_analysis() {
+        let fenced = format!("```json\n{}\n```", ANALYSIS);
+        let analysis = parse_analysis(&fenced).unwrap();
+        assert_eq!(analysis.subject, "A blue sky");
+        assert_eq!(analysis.key_regions[0].label, "cloud");
+        assert_eq!(analysis.suggested_palette.len(), 2);
+
+        // Optional fields may be left out
+        let minimal = parse_analysis(r#"{"subject": "Cat", "description": "A cat."}"#).unwrap();
+        assert!(minimal.focal_point.is_none());
+        assert!(minimal.key_regions.is_empty());
+
+        assert!(parse_analysis("A blue sky").is_err());
+        let outside = ANALYSIS.replace("\"width\": 0.3", "\"width\": 0.7");
+        assert!(parse_analysis(&outside).is_err());
+        let bad_color = ANALYSIS.replace("#FFFFFF", "white");
+        assert!(parse_analysis(&bad_color).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_provider_is_deterministic() {
+        let analysis = LocalVision.analyze_image(&png(), "image/png").await.unwrap();
+        let again = LocalVision.analyze_image(&png(), "image/png").await.unwrap();
+        assert_eq!(analysis.description, again.description);
+        assert!(analysis
+            .description
+            .starts_with("A medium-toned image, mostly blue (75%) and white (25%)."));
+        assert_eq!(analysis.subject, "Image in blue and white");
+        assert_eq!(analysis.suggested_palette, ["#1E90FF", "#F5F5F5"]);
+        // The white strip stands out from the blue background
+        assert_eq!(
+            analysis.focal_point,
+            Some(BoundingBox { x: 0.75, y: 0.0, width: 0.25, height: 1.0 })
+        );
+        assert!(analysis.validate().is_ok());
+
+        assert!(LocalVision.analyze_image(b"not an image", "image/png").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_stub_classifier() {
+        let result = LocalVision.moderate_image(&png(), "image/png").await.unwrap();
+        assert!(!result.flagged);
+
+        let skin = solid_png(8, [224, 172, 140]);
+        let result = LocalVision.moderate_image(&skin, "image/png").await.unwrap();
+        assert!(result.flagged);
+        assert_eq!(result.categories, [ModerationCategory::Sexual]);
+        assert!(result.reason.unwrap().starts_with("100% of the image is skin-toned"));
+    }
+
+    #[test]
+    fn test_parse_moderation() {
+        let result = parse_moderation(
+            r#"{"flagged": true, "categories": ["violence", "weapons"], "reason": "A gun."}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            result.categories,
+            [ModerationCategory::Violence, ModerationCategory::Other]
+        );
+        assert!(!parse_moderation(r#"{"flagged": false}"#).unwrap().flagged);
+        assert!(parse_moderation(r#"{"categories": []}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_provider() {
+        let server = MockServer::start(|request| {
+            assert_eq!(request.header("authorization"), Some("Bearer sk-test"));
+            assert!(request.body.contains("\"model\":\"llava\""));
+            assert!(request.body.contains("\"response_format\":{\"type\":\"json_object\"}"));
+            let reply = serde_json::json!({"choices": [{"message": {"content": ANALYSIS}}]});
+            (200, reply.to_string())
+        })
+        .await;
+        let config = VisionConfig {
+            api_url: format!("{}/v1/chat/completions", server.url),
+            model: "llava".to_string(),
+            api_key: Some(Secret::new("sk-test")),
+            ..VisionConfig::default()
+        };
+        let provider = provider_from_config(&config, reqwest::Client::new());
+        assert_eq!(provider.name(), "openai");
+        let analysis = provider.analyze_image(&png(), "image/png").await.unwrap();
+        assert_eq!(analysis.subject, "A blue sky");
+    }
+
+    #[test]
+    fn test_downscale_image() {
+        let small = png();
+        let (bytes, mime_type) = downscale_image(&small, "image/png", 64).unwrap();
+        assert!(matches!(bytes, Cow::Borrowed(_)));
+        assert_eq!(mime_type, "image/png");
+
+        let large = encode_png(&image::RgbImage::from_pixel(200, 100, image::Rgb([30, 144, 255])));
+        let (bytes, mime_type) = downscale_image(&large, "image/png", 64).unwrap();
+        assert_eq!(mime_type, "image/jpeg");
+        let resized = quantize::load_image_from_bytes(&bytes).unwrap();
+        assert_eq!((resized.width(), resized.height()), (64, 32));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_and_server_errors_are_retried() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server_calls = calls.clone();
+        let server = MockServer::start(move |request| {
+            match server_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => (429, r#"{"error":"rate limited"}"#.to_string()),
+                1 => (503, r#"{"error":"unavailable"}"#.to_string()),
+                2 => (400, r#"{"error":"bad request"}"#.to_string()),
+                _ => {
+                    assert!(request.body.contains("data:image/png;base64"));
+                    let message = serde_json::json!({"content": ANALYSIS});
+                    let reply = serde_json::json!({"choices": [{"message": message}]});
+                    (200, reply.to_string())
+                }
+            }
+        })
+        .await;
+        let mut config = VisionConfig {
+            api_url: format!("{}/v1/chat/completions", server.url),
+            api_key: Some(Secret::new("sk-test")),
+            retry_delay_ms: 1,
+            ..VisionConfig::default()
+        };
+
+        // Two retryable failures, then a client error that is not retried
+        let provider = OpenAiVision::new(&config, reqwest::Client::new());
+        let error = provider.analyze_image(&png(), "image/png").await.unwrap_err();
+        assert!(error.to_string().contains("400"));
+        assert_eq!(server.hits(), 3);
+
+        assert!(provider.analyze_image(&png(), "image/png").await.is_ok());
+        assert_eq!(server.hits(), 4);
+
+        // Without retries the first failure is returned
+        calls.store(0, std::sync::atomic::Ordering::SeqCst);
+        config.max_retries = 0;
+        let provider = OpenAiVision::new(&config, reqwest::Client::new());
+        let error = provider.analyze_image(&png(), "image/png").await.unwrap_err();
+        assert!(error.to_string().contains("429"));
+        assert_eq!(server.hits(), 5);
+    }
+
+    #[test]
+    fn test_retry_backoff_doubles() {
+        let policy = RetryPolicy::new(&VisionConfig::default());
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2000));
+        assert_eq!(policy.backoff(20), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_bedrock_provider() {
+        let server = MockServer::start(|request| {
+            assert_eq!(
+                request.path,
+                "/model/anthropic.claude-3-haiku-20240307-v1:0/converse"
+            );
+            assert_eq!(request.header("authorization"), Some("Bearer bedrock-key"));
+            assert!(request.body.contains("\"format\":\"png\""));
+            assert!(request.body.contains("\"inferenceConfig\":{\"maxTokens\":300}"));
+            let reply = serde_json::json!({
+                "output": {"message": {"role": "assistant", "content": [{"text": ANALYSIS}]}}
+            });
+            (200, reply.to_string())
+        })
+        .await;
+        let mut config = VisionConfig::default();
+        config.bedrock.endpoint_url = Some(server.url.clone());
+        config.bedrock.api_key = Some(Secret::new("bedrock-key"));
+        let provider = provider_from_config(&config, reqwest::Client::new());
+        assert_eq!(provider.name(), "bedrock");
+        let analysis = provider.analyze_image(&png(), "image/png").await.unwrap();
+        assert_eq!(analysis.focal_point.unwrap().width, 0.3);
+        assert!(provider.analyze_image(&png(), "image/bmp").await.is_err());
     }
 }
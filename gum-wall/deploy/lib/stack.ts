@@ -8,6 +8,21 @@ export class McpServerStack extends cdk.Stack {
   constructor(scope: Construct, id: string, props?: cdk.StackProps) {
     super(scope, id, props);
 
+    // Server settings passed through from the deploy environment (no embedded secrets)
+    // (APPSYNC_API_URL/APPSYNC_API_KEY are deprecated aliases of the GUM_WALL_ names).
+    // Without AppSync settings the server refuses to start unless GUM_WALL_CATALOG_SOURCE=builtin.
+    const passthroughEnv = [
+      'GUM_WALL_APPSYNC_API_URL',
+      'GUM_WALL_APPSYNC_API_KEY',
+      'APPSYNC_API_URL',
+      'APPSYNC_API_KEY',
+      'GUM_WALL_CATALOG_SOURCE',
+      'OPENAI_API_KEY',
+    ].reduce(
+      (env, name) => (process.env[name] ? { ...env, [name]: process.env[name] as string } : env),
+      {} as Record<string, string>,
+    );
+
     // Lambda function (ARM64 for better price/performance)
     const mcpFunction = new lambda.Function(this, 'McpFunction', {
       functionName: 'gum-wall-calculator',
@@ -19,6 +34,7 @@ export class McpServerStack extends cdk.Stack {
       timeout: cdk.Duration.seconds(30),
       environment: {
         RUST_LOG: 'info',
+        ...passthroughEnv,
       },
       tracing: lambda.Tracing.ACTIVE,
     });